@@ -1,21 +1,38 @@
+mod theme;
+
 use chess_engine::{
-    chess_match::{ChessMatch, KingState},
+    analysis::Analyzer,
+    chess_match::{ChessMatch, DrawReason, KingState, MatchResult, MatchStatus, PromotionPolicy},
+    chess_move::Move,
+    clock::{format_time, TimeControl},
+    evaluation::Evaluation,
     movement_log::MovementLogger,
-    piece_base::{MoveDirection, PieceColor, PieceType},
-    piece_location::PieceLocation,
+    opening_book::OpeningBook,
+    persistence::{MatchStore, SavedMatch, SqliteStore},
+    piece_base::{MoveDirection, PieceColor, PieceType, TargetKind},
+    piece_location::{PieceLocation, FILES},
+    player::{GameRunner, Player, RandomPlayer},
+    search::{AiPlayer, Difficulty, SearchConfig, SearchLimit, SearchResult},
 };
 use log::{debug, info};
+use theme::{PieceGlyphs, Theme};
 use uuid::Uuid;
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::{
+    borrow::Cow,
     env,
     error::Error,
     fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 use tui::{
@@ -25,33 +42,315 @@ use tui::{
     text::{Span, Spans},
     widgets::{
         canvas::{Canvas, Context, Rectangle},
-        Block, Borders, Clear, Paragraph,
+        Block, Borders, Clear, List, ListItem, ListState, Paragraph,
     },
     Frame, Terminal,
 };
 
+/// The board canvas spans this in both directions: the squares from 0 to 17, with room for
+/// the coordinate labels below and to the left.
+const CANVAS_BOUNDS: [f64; 2] = [-1.0, 17.0];
+/// The width of a square on the canvas, and the distance from one square to the next.
+const SQUARE_SIZE: f64 = 2.0;
+const SQUARE_PITCH: f64 = 2.125;
+
+/// Where each part of the UI goes on screen.
+struct Areas {
+    board: Rect,
+    // the square part of the board area the canvas is drawn in
+    board_canvas: Rect,
+    eval_bar: Rect,
+    material: Rect,
+    moves: Rect,
+    clock: Rect,
+    move_entry: Rect,
+    status: Rect,
+}
+
+impl Areas {
+    fn new(size: Rect, app: &App) -> Areas {
+        let clock_height = if app.chess_match.get_clock().is_some() {
+            3
+        } else {
+            0
+        };
+        let move_entry_height = if app.move_entry.is_some() { 3 } else { 0 };
+        let eval_bar_width = if app.analysis.is_some() { 7 } else { 0 };
+        let rects = Layout::default()
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(clock_height),
+                    Constraint::Length(move_entry_height),
+                    Constraint::Length(2),
+                ]
+                .as_ref(),
+            )
+            .margin(0)
+            .split(size);
+        let main = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(eval_bar_width),
+                    Constraint::Length(26),
+                ]
+                .as_ref(),
+            )
+            .split(rects[0]);
+        let side_panel = Layout::default()
+            .constraints([Constraint::Length(5), Constraint::Min(0)].as_ref())
+            .split(main[2]);
+
+        Areas {
+            board: main[0],
+            board_canvas: square_board(main[0]),
+            eval_bar: main[1],
+            material: side_panel[0],
+            moves: side_panel[1],
+            clock: rects[1],
+            move_entry: rects[2],
+            status: rects[3],
+        }
+    }
+}
+
+/// The finished game being annotated on its own thread, see `App::annotate_game`.
+struct Annotating {
+    started: Instant,
+    annotated: JoinHandle<ChessMatch>,
+}
+
+/// Analysis mode: the evaluation of the position on the board, kept up to date by a search
+/// on its own thread.
+#[derive(Default)]
+struct Analysis {
+    // the Zobrist key of the position `evaluation` is for
+    key: Option<u64>,
+    evaluation: Option<Evaluation>,
+    running: Option<(u64, JoinHandle<Evaluation>)>,
+}
+
+/// Who the player faces in a new game.
+#[derive(Clone, Copy, PartialEq)]
+enum Opponent {
+    Human,
+    Computer(Difficulty),
+}
+
+/// A search for the computer's move, running on its own thread so the UI keeps drawing.
+struct Thinking {
+    // the match and move the search was started on, so a result for a position that has
+    // since changed can be dropped
+    match_id: Uuid,
+    ply: usize,
+    started: Instant,
+    search: JoinHandle<Option<SearchResult>>,
+}
+
+/// The new game menu: the choices so far and the highlighted row.
+struct NewGameMenu {
+    color: PieceColor,
+    opponent: Opponent,
+    selected: usize,
+}
+
+impl NewGameMenu {
+    const ROWS: usize = 4;
+}
+
+/// How long a hint stays on the board.
+const HINT_DURATION: Duration = Duration::from_secs(3);
+/// How long the engine searches for a hint.
+const HINT_SEARCH_TIME: Duration = Duration::from_millis(500);
+
+/// A move the engine suggested, shown until it times out or a move is made.
+struct Hint {
+    chess_move: Move,
+    text: String,
+    // the number of moves played when the hint was asked for
+    ply: usize,
+    shown_at: Instant,
+}
+
+/// Whether the file dialog writes the match out or reads one in.
+#[derive(Clone, Copy, PartialEq)]
+enum FileMode {
+    Save,
+    Load,
+}
+
+/// The save and load dialog: the file name being typed and the saves already on disk.
+struct FileDialog {
+    mode: FileMode,
+    filename: String,
+    files: Vec<String>,
+    selected: Option<usize>,
+    // set once Enter is pressed on the name of a file that already exists
+    confirm_overwrite: bool,
+    error: Option<String>,
+}
+
+/// The saved games popup, listing what's in the store.
+struct SavedGames {
+    games: Vec<SavedMatch>,
+    selected: usize,
+}
+
 struct App {
     pub chess_match: ChessMatch,
     current_tile: (i32, i32),
     selected_tile: Option<(i32, i32)>,
-    show_saved_popup: bool,
+    // what the last save or export wrote, shown until Esc
+    saved_message: Option<String>,
     game_over_text: Option<String>,
+    theme: Theme,
+    // plays `computer_color` when set
+    computer: Option<Arc<Mutex<AiPlayer>>>,
+    computer_color: PieceColor,
+    difficulty: Difficulty,
+    thinking: Option<Thinking>,
+    annotating: Option<Annotating>,
+    analysis: Option<Analysis>,
+    store: Box<dyn MatchStore>,
+    saved_games: Option<SavedGames>,
+    file_dialog: Option<FileDialog>,
+    // where the file dialog reads and writes saves
+    save_dir: PathBuf,
+    // the move being typed, while the move bar is open
+    move_entry: Option<String>,
+    move_entry_error: Option<String>,
+    // how many rows the move list is scrolled back from the latest move
+    move_list_scroll: usize,
+    // black at the bottom when set
+    flipped: bool,
+    // the number of moves into the game shown while reviewing it
+    replay: Option<usize>,
+    new_game_menu: Option<NewGameMenu>,
+    hint: Option<Hint>,
+    // why the last click or key press on the board did nothing, for the status bar
+    status_message: Option<String>,
 }
 
 impl App {
-    fn new(chess_match: ChessMatch) -> App {
+    fn new(
+        chess_match: ChessMatch,
+        theme: Theme,
+        computer: Option<Difficulty>,
+        store: Box<dyn MatchStore>,
+    ) -> App {
         App {
             chess_match,
             current_tile: (0, 0),
             selected_tile: None,
-            show_saved_popup: false,
+            saved_message: None,
             game_over_text: None,
+            theme,
+            computer: computer.map(computer_player),
+            computer_color: PieceColor::Black,
+            difficulty: computer.unwrap_or(Difficulty::Medium),
+            thinking: None,
+            annotating: None,
+            analysis: None,
+            store,
+            saved_games: None,
+            file_dialog: None,
+            save_dir: PathBuf::from("."),
+            move_entry: None,
+            move_entry_error: None,
+            move_list_scroll: 0,
+            flipped: false,
+            replay: None,
+            new_game_menu: None,
+            hint: None,
+            status_message: None,
         }
     }
 
-    fn on_tick(&mut self) {}
+    fn on_tick(&mut self) {
+        if self.hint.as_ref().is_some_and(|hint| {
+            hint.shown_at.elapsed() >= HINT_DURATION
+                || hint.ply != self.chess_match.get_log_entries().len()
+        }) {
+            self.hint = None;
+        }
+        if self.chess_match.check_flag() {
+            self.handle_game_over();
+        }
+        self.finish_annotating();
+        self.update_evaluation();
+        if self.replay.is_none() {
+            self.play_computer_move();
+        }
+    }
 
+    /// Starts the computer searching when it is its turn, and plays the move it found once
+    /// the search is done.
+    fn play_computer_move(&mut self) {
+        if let Some(thinking) = self.thinking.take() {
+            if !thinking.search.is_finished() {
+                self.thinking = Some(thinking);
+                return;
+            }
+            let result = thinking.search.join().unwrap_or(None);
+            let unchanged = thinking.match_id == self.chess_match.get_match_id()
+                && thinking.ply == self.chess_match.get_log_entries().len();
+            if let Some(result) = result.filter(|_| unchanged) {
+                self.play_search_result(result);
+                return;
+            }
+        }
+
+        let (_, color) = self.chess_match.get_current_turn_and_color();
+        if color != self.computer_color
+            || self.game_over_text.is_some()
+            || self.chess_match.is_game_over()
+        {
+            return;
+        }
+        let computer = match self.computer.as_ref() {
+            Some(computer) => Arc::clone(computer),
+            None => return,
+        };
+
+        let position = self.chess_match.copy();
+        self.thinking = Some(Thinking {
+            match_id: self.chess_match.get_match_id(),
+            ply: self.chess_match.get_log_entries().len(),
+            started: Instant::now(),
+            search: thread::spawn(move || {
+                let mut computer = computer.lock().unwrap_or_else(|e| e.into_inner());
+                computer.search(&position)
+            }),
+        });
+    }
+
+    fn play_search_result(&mut self, result: SearchResult) {
+        // the computer doesn't wait for the promotion prompt, it plays the piece its
+        // search picked
+        let chess_move = result.best_move;
+        let promote_to = chess_move.promotion.unwrap_or(PieceType::Queen);
+        if let Err(e) = self.chess_match.move_piece_with_promotion(
+            &chess_move.piece_id,
+            &chess_move.to,
+            promote_to,
+        ) {
+            info!("{}", e);
+        }
+        self.handle_game_over();
+    }
+
+    /// Moves the cursor one square in `direction` as seen on screen, so with the board
+    /// flipped up heads towards white's side.
     fn set_current_tile(&mut self, direction: MoveDirection) {
+        let direction = match direction {
+            MoveDirection::North if self.flipped => MoveDirection::South,
+            MoveDirection::South if self.flipped => MoveDirection::North,
+            MoveDirection::East if self.flipped => MoveDirection::West,
+            MoveDirection::West if self.flipped => MoveDirection::East,
+            direction => direction,
+        };
         match direction {
             MoveDirection::East => {
                 let current_x = self.current_tile.0;
@@ -81,33 +380,540 @@ impl App {
         }
     }
 
+    /// Ends the game once it is decided, recording a draw by repetition since the engine
+    /// leaves that to be claimed, and shows why it ended.
     fn handle_game_over(&mut self) {
-        if self.chess_match.get_white_king_state() == KingState::InCheckMate {
-            self.game_over_text = Some("Game Over! Black Wins!".to_string());
-        } else if self.chess_match.get_black_king_state() == KingState::InCheckMate {
-            self.game_over_text = Some("Game Over! White Wins!".to_string());
+        if self.chess_match.get_status() == MatchStatus::InProgress
+            && self.chess_match.is_threefold_repetition()
+        {
+            self.chess_match.record_result(MatchResult::Draw {
+                reason: DrawReason::ThreefoldRepetition,
+            });
+        }
+
+        match self.chess_match.get_status() {
+            MatchStatus::InProgress => {}
+            MatchStatus::Completed => {
+                self.game_over_text = Some(format!(
+                    "{} Press r for a rematch or n for a new game.",
+                    game_over_message(self.chess_match.result())
+                ));
+            }
+        }
+    }
+
+    fn start_rematch(&mut self) {
+        self.chess_match = self.chess_match.rematch();
+        self.chess_match.calculate_valid_moves();
+        self.current_tile = (0, 0);
+        self.selected_tile = None;
+        self.game_over_text = None;
+    }
+
+    fn open_new_game_menu(&mut self) {
+        self.new_game_menu = Some(NewGameMenu {
+            color: self.computer_color.opposite(),
+            opponent: if self.computer.is_some() {
+                Opponent::Computer(self.difficulty)
+            } else {
+                Opponent::Human
+            },
+            selected: 0,
+        });
+    }
+
+    fn select_new_game_row(&mut self, direction: MoveDirection) {
+        if let Some(menu) = self.new_game_menu.as_mut() {
+            match direction {
+                MoveDirection::North => menu.selected = menu.selected.saturating_sub(1),
+                MoveDirection::South => {
+                    menu.selected = (menu.selected + 1).min(NewGameMenu::ROWS - 1)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Toggles the highlighted choice, or starts the game or opens the saved games when
+    /// those rows are highlighted.
+    fn activate_new_game_row(&mut self) {
+        let menu = match self.new_game_menu.as_mut() {
+            Some(menu) => menu,
+            None => return,
+        };
+        match menu.selected {
+            0 => menu.color = menu.color.opposite(),
+            1 => {
+                menu.opponent = match menu.opponent {
+                    Opponent::Human => Opponent::Computer(Difficulty::Easy),
+                    Opponent::Computer(Difficulty::Easy) => Opponent::Computer(Difficulty::Medium),
+                    Opponent::Computer(Difficulty::Medium) => Opponent::Computer(Difficulty::Hard),
+                    Opponent::Computer(Difficulty::Hard) => Opponent::Human,
+                }
+            }
+            2 => {
+                let (color, opponent) = (menu.color, menu.opponent);
+                self.start_new_game(color, opponent);
+            }
+            _ => {
+                self.new_game_menu = None;
+                self.open_saved_games();
+            }
+        }
+    }
+
+    /// Starts a fresh game with the player on `color`, keeping the time control.
+    fn start_new_game(&mut self, color: PieceColor, opponent: Opponent) {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        if let Some(clock) = self.chess_match.get_clock() {
+            chess_match.set_time_control(clock.get_time_control());
+        }
+        chess_match.calculate_valid_moves();
+        chess_match.set_promotion_policy(PromotionPolicy::Prompt);
+        self.chess_match = chess_match;
+
+        self.computer = match opponent {
+            Opponent::Human => None,
+            Opponent::Computer(difficulty) => match self.computer.take() {
+                Some(computer) if difficulty == self.difficulty => Some(computer),
+                _ => Some(computer_player(difficulty)),
+            },
+        };
+        if let Opponent::Computer(difficulty) = opponent {
+            self.difficulty = difficulty;
+        }
+        self.computer_color = color.opposite();
+        self.flipped = color == PieceColor::Black;
+        self.current_tile = (0, 0);
+        self.selected_tile = None;
+        self.game_over_text = None;
+        self.replay = None;
+        self.move_list_scroll = 0;
+        self.new_game_menu = None;
+    }
+
+    fn promote_pending_pawn(&mut self, piece_type: PieceType) {
+        if let Some(pending) = self.chess_match.get_pending_promotion() {
+            if let Err(e) = self.chess_match.promote_pawn(&pending.piece_id, piece_type) {
+                info!("{}", e);
+            }
+            self.handle_game_over();
+        }
+    }
+
+    fn undo_move(&mut self) {
+        self.chess_match.cancel_promotion();
+        if self.chess_match.undo_move().is_none() {
+            return;
+        }
+        // take back the computer's reply too, so it is the player's turn again
+        let (_, color) = self.chess_match.get_current_turn_and_color();
+        if self.computer.is_some() && color == self.computer_color {
+            self.chess_match.undo_move();
+        }
+        self.selected_tile = None;
+        self.game_over_text = None;
+    }
+
+    /// Pauses the clock of a timed game, e.g. to step away, or starts it again. Playing a move
+    /// starts a paused clock as well.
+    fn toggle_clock(&mut self) {
+        let paused = self.chess_match.get_clock().is_some_and(|c| c.is_paused());
+        let toggled = if paused {
+            self.chess_match.resume_clock()
+        } else {
+            self.chess_match.pause_clock()
+        };
+        if !toggled {
+            self.status_message = Some("The clock isn't running.".to_string());
+        }
+    }
+
+    /// Searches for a move for the side to move and flashes it on the board, along with the
+    /// line the search expects to follow.
+    fn show_hint(&mut self) {
+        if self.game_over_text.is_some() || self.chess_match.is_game_over() {
+            return;
+        }
+        let line = match self
+            .chess_match
+            .best_line(SearchLimit::Time(HINT_SEARCH_TIME))
+        {
+            Some(line) => line,
+            None => return,
+        };
+        let mut text = format!(
+            "Hint: {}, {}",
+            line.pv[0],
+            self.chess_match.explain_move(&line.best_move)
+        );
+        if line.pv.len() > 1 {
+            text.push_str(&format!(". Line: {}", line.pv.join(" ")));
+        }
+        self.hint = Some(Hint {
+            text,
+            chess_move: line.best_move,
+            ply: self.chess_match.get_log_entries().len(),
+            shown_at: Instant::now(),
+        });
+    }
+
+    fn open_file_dialog(&mut self, mode: FileMode) {
+        let filename = match mode {
+            FileMode::Save => format!("{}.json", self.chess_match.get_match_id()),
+            FileMode::Load => String::new(),
+        };
+        self.saved_games = None;
+        self.file_dialog = Some(FileDialog {
+            mode,
+            filename,
+            files: save_files(&self.save_dir),
+            selected: None,
+            confirm_overwrite: false,
+            error: None,
+        });
+    }
+
+    /// Moves through the saves in the file dialog, copying the highlighted name into it.
+    fn select_file(&mut self, direction: MoveDirection) {
+        let dialog = match self.file_dialog.as_mut() {
+            Some(dialog) if !dialog.files.is_empty() => dialog,
+            _ => return,
+        };
+        let last = dialog.files.len() - 1;
+        let selected = match (direction, dialog.selected) {
+            (MoveDirection::North, Some(i)) => i.saturating_sub(1),
+            (MoveDirection::North, None) => last,
+            (MoveDirection::South, Some(i)) => (i + 1).min(last),
+            _ => 0,
+        };
+        dialog.selected = Some(selected);
+        dialog.filename = dialog.files[selected].clone();
+        dialog.confirm_overwrite = false;
+        dialog.error = None;
+    }
+
+    fn edit_filename(&mut self, c: Option<char>) {
+        if let Some(dialog) = self.file_dialog.as_mut() {
+            match c {
+                Some(c) => dialog.filename.push(c),
+                None => {
+                    dialog.filename.pop();
+                }
+            }
+            dialog.selected = None;
+            dialog.confirm_overwrite = false;
+            dialog.error = None;
+        }
+    }
+
+    /// Saves to or loads from the file named in the dialog. Saving over an existing file asks
+    /// first, and failures stay in the dialog.
+    fn submit_file_dialog(&mut self) {
+        let (mode, filename, confirmed) = match self.file_dialog.as_ref() {
+            Some(dialog) => (
+                dialog.mode,
+                dialog.filename.trim().to_string(),
+                dialog.confirm_overwrite,
+            ),
+            None => return,
+        };
+        if filename.is_empty() {
+            self.set_file_error("Enter a file name.".to_string());
+            return;
+        }
+        let path = self.save_dir.join(&filename);
+
+        match mode {
+            FileMode::Save if path.exists() && !confirmed => {
+                if let Some(dialog) = self.file_dialog.as_mut() {
+                    dialog.confirm_overwrite = true;
+                }
+            }
+            FileMode::Save => {
+                if let Err(e) = fs::write(&path, self.chess_match.get_json_string()) {
+                    self.set_file_error(e.to_string());
+                    return;
+                }
+                // keep the store in step, so the game can also be resumed from the saved games
+                if let Err(e) = self.store.save(&self.chess_match) {
+                    info!("{}", e);
+                }
+                self.file_dialog = None;
+                self.saved_message = Some(format!("Match saved to {}.", path.display()));
+            }
+            FileMode::Load => {
+                let loaded = fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| ChessMatch::from_json(&json).map_err(|e| e.to_string()));
+                match loaded {
+                    Ok(chess_match) => {
+                        self.file_dialog = None;
+                        self.load_match(chess_match);
+                    }
+                    Err(e) => self.set_file_error(format!("{}: {}", filename, e)),
+                }
+            }
+        }
+    }
+
+    fn set_file_error(&mut self, error: String) {
+        if let Some(dialog) = self.file_dialog.as_mut() {
+            dialog.error = Some(error);
+            dialog.confirm_overwrite = false;
+        }
+    }
+
+    /// Swaps the match being played for a saved one.
+    fn load_match(&mut self, chess_match: ChessMatch) {
+        self.chess_match = chess_match;
+        self.chess_match.calculate_valid_moves();
+        self.chess_match
+            .set_promotion_policy(PromotionPolicy::Prompt);
+        self.current_tile = (0, 0);
+        self.selected_tile = None;
+        self.game_over_text = None;
+        self.hint = None;
+        self.move_list_scroll = 0;
+        self.handle_game_over();
+    }
+
+    fn open_saved_games(&mut self) {
+        self.file_dialog = None;
+        match self.store.list() {
+            Ok(games) => self.saved_games = Some(SavedGames { games, selected: 0 }),
+            Err(e) => info!("{}", e),
+        }
+    }
+
+    fn select_saved_game(&mut self, direction: MoveDirection) {
+        if let Some(saved) = self.saved_games.as_mut() {
+            match direction {
+                MoveDirection::North => saved.selected = saved.selected.saturating_sub(1),
+                MoveDirection::South if saved.selected + 1 < saved.games.len() => {
+                    saved.selected += 1
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn resume_selected_game(&mut self) {
+        let match_id = match self.selected_saved_game() {
+            Some(game) => game.match_id,
+            None => return,
+        };
+        match self.store.load(&match_id) {
+            Ok(chess_match) => {
+                self.saved_games = None;
+                self.load_match(chess_match);
+            }
+            Err(e) => info!("{}", e),
+        }
+    }
+
+    fn delete_selected_game(&mut self) {
+        let match_id = match self.selected_saved_game() {
+            Some(game) => game.match_id,
+            None => return,
+        };
+        if let Err(e) = self.store.delete(&match_id) {
+            info!("{}", e);
+        }
+        let selected = self.saved_games.as_ref().map_or(0, |s| s.selected);
+        self.open_saved_games();
+        if let Some(saved) = self.saved_games.as_mut() {
+            saved.selected = selected.min(saved.games.len().saturating_sub(1));
+        }
+    }
+
+    fn selected_saved_game(&self) -> Option<&SavedMatch> {
+        self.saved_games
+            .as_ref()
+            .and_then(|saved| saved.games.get(saved.selected))
+    }
+
+    fn open_move_entry(&mut self) {
+        self.move_entry = Some(String::new());
+        self.move_entry_error = None;
+    }
+
+    fn close_move_entry(&mut self) {
+        self.move_entry = None;
+        self.move_entry_error = None;
+    }
+
+    /// Plays the move typed into the move bar, in SAN or coordinates. An illegal move keeps
+    /// the bar open with the reason shown next to it.
+    fn submit_move_entry(&mut self) {
+        let text = match self.move_entry.as_ref() {
+            Some(text) if !text.trim().is_empty() => text.clone(),
+            _ => return,
+        };
+        match self.chess_match.apply_san(&text) {
+            Ok(_) => {
+                self.close_move_entry();
+                self.selected_tile = None;
+                self.handle_game_over();
+            }
+            Err(e) => self.move_entry_error = Some(e.to_string()),
+        }
+    }
+
+    /// Starts reviewing the game from its first position.
+    /// Scores every move of the finished game and marks the good ones and the mistakes, on
+    /// a thread of its own since it takes a search per legal move.
+    fn annotate_game(&mut self) {
+        if self.annotating.is_some() {
+            return;
+        }
+        if self.game_over_text.is_none() && !self.chess_match.is_game_over() {
+            self.status_message = Some("Finish the game before annotating it.".to_string());
+            return;
+        }
+        let mut chess_match = self.chess_match.copy();
+        self.annotating = Some(Annotating {
+            started: Instant::now(),
+            annotated: thread::spawn(move || {
+                Analyzer::default().annotate(&mut chess_match);
+                chess_match
+            }),
+        });
+    }
+
+    fn finish_annotating(&mut self) {
+        match self.annotating.as_ref() {
+            Some(annotating) if annotating.annotated.is_finished() => {}
+            _ => return,
+        }
+        let annotating = self.annotating.take().unwrap();
+        if let Ok(annotated) = annotating.annotated.join() {
+            // dropped if another game was loaded in the meantime
+            if annotated.get_match_id() == self.chess_match.get_match_id()
+                && annotated.get_log_entries().len() == self.chess_match.get_log_entries().len()
+            {
+                self.chess_match = annotated;
+            }
         }
     }
 
+    fn toggle_analysis(&mut self) {
+        self.analysis = match self.analysis {
+            Some(_) => None,
+            None => Some(Analysis::default()),
+        };
+        self.update_evaluation();
+    }
+
+    /// Picks up a finished evaluation, and starts evaluating the position shown if it has
+    /// changed since.
+    fn update_evaluation(&mut self) {
+        if self.analysis.is_none() {
+            return;
+        }
+        let shown = self.shown_match().into_owned();
+        let analysis = match self.analysis.as_mut() {
+            Some(analysis) => analysis,
+            None => return,
+        };
+        if let Some((key, running)) = analysis.running.take() {
+            if !running.is_finished() {
+                analysis.running = Some((key, running));
+                return;
+            }
+            if let Ok(evaluation) = running.join() {
+                analysis.key = Some(key);
+                analysis.evaluation = Some(evaluation);
+            }
+        }
+
+        let key = shown.zobrist_key();
+        if analysis.key != Some(key) {
+            analysis.running = Some((
+                key,
+                thread::spawn(move || shown.evaluate_current_position()),
+            ));
+        }
+    }
+
+    fn start_replay(&mut self) {
+        self.replay = Some(0);
+        self.selected_tile = None;
+    }
+
+    /// Steps through the game while reviewing it: back a move going West, forward going
+    /// East, and to the start or end going South or North.
+    fn step_replay(&mut self, direction: MoveDirection) {
+        let last = self.chess_match.get_log_entries().len();
+        if let Some(ply) = self.replay.as_mut() {
+            *ply = match direction {
+                MoveDirection::West => ply.saturating_sub(1),
+                MoveDirection::East => (*ply + 1).min(last),
+                MoveDirection::South => 0,
+                MoveDirection::North => last,
+                _ => *ply,
+            };
+        }
+    }
+
+    /// The position on the board: the one being reviewed, or else the game as it stands.
+    fn shown_match(&self) -> Cow<'_, ChessMatch> {
+        match self
+            .replay
+            .and_then(|ply| self.chess_match.position_at_move(ply))
+        {
+            Some(position) => Cow::Owned(position),
+            None => Cow::Borrowed(&self.chess_match),
+        }
+    }
+
+    /// Selects the square clicked on, or moves the selected piece there, like pressing space
+    /// with the cursor on it.
+    fn click(&mut self, canvas_area: Rect, column: u16, row: u16) {
+        if let Some(tile) = clicked_tile(canvas_area, column, row) {
+            self.current_tile = screen_tile(tile, self.flipped);
+            self.set_selected_tile();
+        }
+    }
+
+    fn scroll_move_list(&mut self, direction: MoveDirection) {
+        const PAGE: usize = 10;
+        let rows = MovementLogger::get_move_rows(&self.chess_match).len();
+        self.move_list_scroll = match direction {
+            MoveDirection::North => (self.move_list_scroll + PAGE).min(rows.saturating_sub(1)),
+            _ => self.move_list_scroll.saturating_sub(PAGE),
+        };
+    }
+
     fn print_match_log(&self) {
         let formatted_log = MovementLogger::get_formatted_entries(&self.chess_match);
         info!("{}", formatted_log);
+        info!("{}", MovementLogger::get_time_report(&self.chess_match));
     }
 
     fn set_selected_tile(&mut self) {
+        self.status_message = None;
         if self.selected_tile.is_none() {
             // check if current player has a piece on selected tile
             let (_, current_color) = self.chess_match.get_current_turn_and_color();
             let (loc_x, loc_y) = self.current_tile;
-            let piece = self
-                .chess_match
-                .get_piece_at_location(PieceLocation::new_from_x_y(loc_x, loc_y + 1));
-            if piece.is_some() {
+            let location = PieceLocation::new_from_x_y(loc_x, loc_y + 1);
+            let piece = self.chess_match.get_piece_at_location(location.clone());
+            if self.computer.is_some() && current_color == self.computer_color {
+                self.status_message = Some("Not your turn, the computer is thinking.".to_string());
+            } else if piece.is_some() {
                 let piece = piece.unwrap();
                 debug!("Valid moves: {:?}", piece.get_valid_moves());
                 if piece.color == current_color {
                     self.selected_tile = Some(self.current_tile);
+                } else {
+                    self.status_message = Some(format!(
+                        "Not your turn: {} to move.",
+                        color_name(current_color)
+                    ));
                 }
+            } else {
+                self.status_message = Some(format!("There is no piece on {}.", location));
             }
         } else {
             if self.selected_tile.unwrap() == self.current_tile {
@@ -125,12 +931,11 @@ impl App {
                     let piece = piece.unwrap();
                     let (new_loc_x, new_loc_y) = self.current_tile;
                     let new_location = PieceLocation::new_from_x_y(new_loc_x, new_loc_y + 1);
-                    self.chess_match.move_piece(&piece.id, &new_location);
-                    if self.chess_match.get_white_king_state() == KingState::InCheckMate
-                        || self.chess_match.get_black_king_state() == KingState::InCheckMate
-                    {
-                        self.handle_game_over();
+                    if let Err(e) = self.chess_match.move_piece(&piece.id, &new_location) {
+                        info!("{}", e);
+                        self.status_message = Some(e.to_string());
                     }
+                    self.handle_game_over();
                     self.selected_tile = None;
                 } else {
                     self.selected_tile = Some(self.current_tile);
@@ -143,20 +948,53 @@ impl App {
 fn main() -> Result<(), Box<dyn Error>> {
     env_logger::init();
     let args: Vec<String> = env::args().collect();
-    let mut chess_match = if args.len() > 1 {
-        let json_string =
-            fs::read_to_string(args[1].clone()).expect("Unable to read specified file.");
-        ChessMatch::new_from_json(json_string)
+    // --replay=FILE opens a saved match for review
+    let replay_file = flag_value(&args, "replay");
+    let match_file = replay_file.or_else(|| {
+        args.iter()
+            .skip(1)
+            .find(|a| !a.starts_with("--"))
+            .map(String::as_str)
+    });
+    let mut chess_match = if let Some(match_file) = match_file {
+        let json_string = fs::read_to_string(match_file).expect("Unable to read specified file.");
+        ChessMatch::from_json(&json_string)?
     } else {
         ChessMatch::new(Uuid::new_v4(), Uuid::new_v4())
     };
     chess_match.calculate_valid_moves();
+    chess_match.set_promotion_policy(PromotionPolicy::Prompt);
+    let time_control = args.iter().find_map(|a| match a.as_str() {
+        "--blitz" => Some(TimeControl::blitz()),
+        "--rapid" => Some(TimeControl::rapid()),
+        "--classical" => Some(TimeControl::classical()),
+        _ => None,
+    });
+    if let Some(time_control) = time_control {
+        chess_match.set_time_control(time_control);
+    }
 
-    let mut show_ui = true;
-    if args.len() > 2 && args[2] == "--headless" {
-        show_ui = false;
+    let show_ui = !args.iter().any(|a| a == "--headless");
+    let mut theme = match (flag_value(&args, "theme-file"), flag_value(&args, "theme")) {
+        (Some(path), _) => Theme::load(path)?,
+        (None, Some(name)) => Theme::named(name).ok_or_else(|| theme::unknown_theme(name))?,
+        (None, None) => Theme::default(),
+    };
+    if args.iter().any(|a| a == "--ascii") {
+        theme.glyphs = PieceGlyphs::Ascii;
     }
-    if show_ui {
+    // --vs-ai=easy|medium|hard, or --vs-computer for the hardest
+    let computer = match flag_value(&args, "vs-ai") {
+        Some(name) => Some(Difficulty::named(name).ok_or_else(|| {
+            format!("unknown difficulty {}, expected easy, medium or hard", name)
+        })?),
+        None if args.iter().any(|a| a == "--vs-ai") => Some(Difficulty::Medium),
+        None if args.iter().any(|a| a == "--vs-computer") => Some(Difficulty::Hard),
+        None => None,
+    };
+    if !show_ui {
+        run_self_play(chess_match, &args)?;
+    } else {
         // setup terminal
         enable_raw_mode()?;
         let mut stdout = io::stdout();
@@ -166,7 +1004,14 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         // create app and run it
         let tick_rate = Duration::from_millis(250);
-        let mut app = App::new(chess_match);
+        let store = SqliteStore::open(flag_value(&args, "db").unwrap_or("matches.db"))?;
+        let mut app = App::new(chess_match, theme, computer, Box::new(store));
+        if let Some(dir) = flag_value(&args, "save-dir") {
+            app.save_dir = PathBuf::from(dir);
+        }
+        if replay_file.is_some() {
+            app.start_replay();
+        }
         let res = run_app(&mut terminal, &mut app, tick_rate);
 
         // restore terminal
@@ -190,6 +1035,107 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// How the game ended, for the game over popup.
+fn computer_player(difficulty: Difficulty) -> Arc<Mutex<AiPlayer>> {
+    Arc::new(Mutex::new(
+        AiPlayer::new(difficulty.config()).with_book(OpeningBook::bundled()),
+    ))
+}
+
+fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::White => "White",
+        PieceColor::Black => "Black",
+    }
+}
+
+fn game_over_message(result: MatchResult) -> &'static str {
+    match result {
+        MatchResult::WhiteWonCheckmate => "Checkmate! White Wins!",
+        MatchResult::BlackWonCheckmate => "Checkmate! Black Wins!",
+        MatchResult::WhiteWonResignation => "Black resigned. White Wins!",
+        MatchResult::BlackWonResignation => "White resigned. Black Wins!",
+        MatchResult::WhiteWonTimeout => "Black ran out of time. White Wins!",
+        MatchResult::BlackWonTimeout => "White ran out of time. Black Wins!",
+        MatchResult::Draw { reason } => match reason {
+            DrawReason::Stalemate => "Stalemate! The game is drawn.",
+            DrawReason::ThreefoldRepetition => "Draw by threefold repetition.",
+            DrawReason::FiftyMoveRule => "Draw by the fifty-move rule.",
+            DrawReason::InsufficientMaterial => "Draw: neither side can checkmate.",
+            DrawReason::Agreement => "Draw agreed.",
+            DrawReason::MoveLimit => "Draw: the move limit was reached.",
+        },
+        MatchResult::Aborted => "Game aborted.",
+        MatchResult::InProgress => "Game Over!",
+    }
+}
+
+/// The value of a `--name=value` argument.
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let prefix = format!("--{}=", name);
+    args.iter().find_map(|a| a.strip_prefix(prefix.as_str()))
+}
+
+/// The JSON saves in `dir`, by name.
+fn save_files(dir: &Path) -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|e| e == "json"))
+                .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Plays the match out between two computer players and prints the game as PGN, for
+/// checking engine strength without the UI. `--white-depth=N` and `--black-depth=N` set
+/// each side's search depth, with 0 making that side play random moves.
+/// `--max-moves=N` adjudicates a draw after N full moves and `--output=FILE` also writes
+/// the PGN to a file.
+fn run_self_play(chess_match: ChessMatch, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let number = |name: &str, default: u32| -> Result<u32, Box<dyn Error>> {
+        match flag_value(args, name) {
+            Some(value) => Ok(value
+                .parse()
+                .map_err(|_| format!("--{} must be a number, got {}", name, value))?),
+            None => Ok(default),
+        }
+    };
+    let player = |depth: u32| -> Box<dyn Player> {
+        if depth == 0 {
+            Box::new(RandomPlayer::new())
+        } else {
+            Box::new(AiPlayer::new(SearchConfig {
+                max_depth: depth,
+                time_limit_ms: None,
+                ..SearchConfig::default()
+            }))
+        }
+    };
+
+    let finished = GameRunner::new(
+        player(number("white-depth", 2)?),
+        player(number("black-depth", 2)?),
+    )
+    .from_match(chess_match)
+    .max_moves(number("max-moves", 150)?)
+    .run();
+
+    let pgn = finished.to_pgn();
+    println!("{}", pgn);
+    println!("Result: {:?}", finished.result());
+    if let Some(output) = flag_value(args, "output") {
+        fs::write(output, pgn)?;
+    }
+
+    Ok(())
+}
+
 fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     app: &mut App,
@@ -203,23 +1149,217 @@ fn run_app<B: Backend>(
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| Duration::from_secs(0));
         if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
+            let event = event::read()?;
+            if let Event::Resize(width, height) = event {
+                // lay everything out again for the new size on the next draw
+                terminal.resize(Rect::new(0, 0, width, height))?;
+            }
+            if let Event::Mouse(mouse) = event {
+                let busy = app.saved_games.is_some()
+                    || app.file_dialog.is_some()
+                    || app.new_game_menu.is_some()
+                    || app.move_entry.is_some()
+                    || app.replay.is_some()
+                    || app.chess_match.get_pending_promotion().is_some();
+                if mouse.kind == MouseEventKind::Down(MouseButton::Left) && !busy {
+                    let canvas_area = Areas::new(terminal.size()?, app).board_canvas;
+                    app.click(canvas_area, mouse.column, mouse.row);
+                }
+            }
+            if let Event::Key(key) = event {
+                let promoting = app.chess_match.get_pending_promotion().is_some();
+                let browsing = app.saved_games.is_some();
+                let filing = app.file_dialog.is_some();
+                let confirming = app
+                    .file_dialog
+                    .as_ref()
+                    .is_some_and(|dialog| dialog.confirm_overwrite);
+                let entering = app.move_entry.is_some();
+                let replaying = app.replay.is_some();
+                let choosing = app.new_game_menu.is_some();
                 match key.code {
+                    KeyCode::Up if browsing => {
+                        app.select_saved_game(MoveDirection::North);
+                    }
+                    KeyCode::Down if browsing => {
+                        app.select_saved_game(MoveDirection::South);
+                    }
+                    KeyCode::Enter if browsing => {
+                        app.resume_selected_game();
+                    }
+                    KeyCode::Char('d') if browsing => {
+                        app.delete_selected_game();
+                    }
+                    KeyCode::Tab if browsing => {
+                        app.open_file_dialog(FileMode::Load);
+                    }
+                    KeyCode::Esc if browsing => {
+                        app.saved_games = None;
+                    }
+                    _ if browsing => {}
+                    KeyCode::Enter | KeyCode::Char('y') if confirming => {
+                        app.submit_file_dialog();
+                    }
+                    KeyCode::Esc | KeyCode::Char('n') if confirming => {
+                        if let Some(dialog) = app.file_dialog.as_mut() {
+                            dialog.confirm_overwrite = false;
+                        }
+                    }
+                    _ if confirming => {}
+                    KeyCode::Enter if filing => {
+                        app.submit_file_dialog();
+                    }
+                    KeyCode::Esc if filing => {
+                        app.file_dialog = None;
+                    }
+                    KeyCode::Up if filing => {
+                        app.select_file(MoveDirection::North);
+                    }
+                    KeyCode::Down if filing => {
+                        app.select_file(MoveDirection::South);
+                    }
+                    KeyCode::Tab
+                        if app
+                            .file_dialog
+                            .as_ref()
+                            .is_some_and(|dialog| dialog.mode == FileMode::Load) =>
+                    {
+                        app.open_saved_games();
+                    }
+                    KeyCode::Backspace if filing => {
+                        app.edit_filename(None);
+                    }
+                    KeyCode::Char(c) if filing => {
+                        app.edit_filename(Some(c));
+                    }
+                    _ if filing => {}
+                    KeyCode::Up if choosing => {
+                        app.select_new_game_row(MoveDirection::North);
+                    }
+                    KeyCode::Down if choosing => {
+                        app.select_new_game_row(MoveDirection::South);
+                    }
+                    KeyCode::Enter | KeyCode::Left | KeyCode::Right | KeyCode::Char(' ')
+                        if choosing =>
+                    {
+                        app.activate_new_game_row();
+                    }
+                    KeyCode::Esc if choosing => {
+                        app.new_game_menu = None;
+                    }
+                    _ if choosing => {}
+                    KeyCode::Enter if entering => {
+                        app.submit_move_entry();
+                    }
+                    KeyCode::Esc if entering => {
+                        app.close_move_entry();
+                    }
+                    KeyCode::Backspace if entering => {
+                        if let Some(text) = app.move_entry.as_mut() {
+                            text.pop();
+                        }
+                        app.move_entry_error = None;
+                    }
+                    KeyCode::Char(c) if entering => {
+                        if let Some(text) = app.move_entry.as_mut() {
+                            text.push(c);
+                        }
+                        app.move_entry_error = None;
+                    }
+                    _ if entering => {}
+                    KeyCode::Char('e') if !promoting => {
+                        app.toggle_analysis();
+                    }
+                    KeyCode::Left if replaying => {
+                        app.step_replay(MoveDirection::West);
+                    }
+                    KeyCode::Right if replaying => {
+                        app.step_replay(MoveDirection::East);
+                    }
+                    KeyCode::Home if replaying => {
+                        app.step_replay(MoveDirection::South);
+                    }
+                    KeyCode::End if replaying => {
+                        app.step_replay(MoveDirection::North);
+                    }
+                    KeyCode::Esc | KeyCode::Char('v') if replaying => {
+                        app.replay = None;
+                    }
+                    KeyCode::Char('q') if replaying => {
+                        return Ok(());
+                    }
+                    KeyCode::Char('f') if replaying => {
+                        app.flipped = !app.flipped;
+                    }
+                    KeyCode::Char('a') if replaying => {
+                        app.annotate_game();
+                    }
+                    _ if replaying => {}
+                    KeyCode::Char('q') if promoting => {
+                        app.promote_pending_pawn(PieceType::Queen);
+                    }
+                    KeyCode::Char('r') if promoting => {
+                        app.promote_pending_pawn(PieceType::Rook);
+                    }
+                    KeyCode::Char('b') if promoting => {
+                        app.promote_pending_pawn(PieceType::Bishop);
+                    }
+                    KeyCode::Char('n') if promoting => {
+                        app.promote_pending_pawn(PieceType::Knight);
+                    }
+                    KeyCode::Esc if promoting => {
+                        app.chess_match.cancel_promotion();
+                    }
                     KeyCode::Char('q') => {
                         return Ok(());
                     }
+                    KeyCode::Char(':') | KeyCode::Char('m') if !promoting => {
+                        app.open_move_entry();
+                    }
                     KeyCode::Char('s') => {
-                        let json_data = app.chess_match.get_json_string();
-                        let filename = format!("{}.json", app.chess_match.get_match_id());
-                        app.show_saved_popup = true;
-                        std::fs::write(filename, json_data)
-                            .expect("Error writing match data to disk");
+                        app.open_file_dialog(FileMode::Save);
+                    }
+                    KeyCode::Char('o') => {
+                        app.open_file_dialog(FileMode::Load);
+                    }
+                    KeyCode::Char('p') => {
+                        let filename = format!("{}.pgn", app.chess_match.get_match_id());
+                        std::fs::write(&filename, app.chess_match.to_pgn())
+                            .expect("Error writing PGN to disk");
+                        app.saved_message = Some(format!("PGN written to {}.", filename));
                     }
                     KeyCode::Char('l') => {
                         app.print_match_log();
                     }
+                    KeyCode::Char('u') => {
+                        app.undo_move();
+                    }
+                    KeyCode::Char('c') => {
+                        app.toggle_clock();
+                    }
+                    KeyCode::Char('h') if !promoting => {
+                        app.show_hint();
+                    }
+                    KeyCode::Char('f') => {
+                        app.flipped = !app.flipped;
+                    }
+                    KeyCode::Char('n') => {
+                        app.open_new_game_menu();
+                    }
+                    KeyCode::Char('v') if !promoting => {
+                        app.start_replay();
+                    }
+                    KeyCode::PageUp => {
+                        app.scroll_move_list(MoveDirection::North);
+                    }
+                    KeyCode::PageDown => {
+                        app.scroll_move_list(MoveDirection::South);
+                    }
+                    KeyCode::Char('r') if app.game_over_text.is_some() => {
+                        app.start_rematch();
+                    }
                     KeyCode::Esc => {
-                        app.show_saved_popup = false;
+                        app.saved_message = None;
                     }
                     KeyCode::Down => {
                         app.set_current_tile(MoveDirection::South);
@@ -249,27 +1389,72 @@ fn run_app<B: Backend>(
 }
 
 fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
-    let rects = Layout::default()
-        .constraints([Constraint::Ratio(1, 1)].as_ref())
-        .margin(0)
-        .split(f.size());
+    let areas = Areas::new(f.size(), app);
+    let shown = app.shown_match();
+    let title = match app.replay {
+        Some(ply) => format!(
+            "Replay: move {} of {}. Left/Right to step, Home/End to jump, a to annotate, \
+             Esc to leave",
+            ply,
+            app.chess_match.get_log_entries().len()
+        ),
+        None => match &app.hint {
+            Some(hint) => hint.text.clone(),
+            None => "Chess".to_string(),
+        },
+    };
+    f.render_widget(
+        Block::default().borders(Borders::ALL).title(title),
+        areas.board,
+    );
     let canvas = Canvas::default()
-        .block(Block::default().borders(Borders::ALL).title("Chess"))
         .paint(|ctx| {
-            draw_pieces(ctx, &app.chess_match);
-            draw_board(ctx, &app.current_tile, &app.selected_tile, &app.chess_match);
+            draw_coordinates(ctx, app.flipped);
+            draw_pieces(ctx, &shown, &app.theme, app.flipped);
+            draw_board(
+                ctx,
+                &app.current_tile,
+                &app.selected_tile,
+                &shown,
+                app.hint.as_ref().map(|hint| &hint.chess_move),
+                &app.theme,
+                app.flipped,
+            );
         })
-        .x_bounds([0.0, 17.0])
-        .y_bounds([0.0, 17.0]);
-    f.render_widget(canvas, rects[0]);
+        .x_bounds(CANVAS_BOUNDS)
+        .y_bounds(CANVAS_BOUNDS);
+    f.render_widget(canvas, areas.board_canvas);
+    if let Some(analysis) = app.analysis.as_ref() {
+        draw_eval_bar(f, areas.eval_bar, analysis.evaluation, app.flipped);
+    }
+    draw_material(f, areas.material, &shown, &app.theme);
+    draw_move_list(f, areas.moves, &shown, app.move_list_scroll);
+    if app.chess_match.get_clock().is_some() {
+        draw_clocks(f, areas.clock, &app.chess_match);
+    }
+    if let Some(text) = app.move_entry.as_ref() {
+        draw_move_entry(f, areas.move_entry, text, app.move_entry_error.as_deref());
+    }
+    let activity = match (&app.annotating, &app.thinking) {
+        (Some(annotating), _) => Some(("Annotating the game", annotating.started.elapsed())),
+        (None, Some(thinking)) => Some(("Computer is thinking", thinking.started.elapsed())),
+        (None, None) => None,
+    };
+    draw_status(
+        f,
+        areas.status,
+        &shown,
+        activity,
+        app.status_message.as_deref(),
+    );
 
     let size = f.size();
 
-    if app.show_saved_popup {
-        let block = Block::default().title("Popup").borders(Borders::ALL);
+    if let Some(message) = app.saved_message.as_ref() {
+        let block = Block::default().title("Saved").borders(Borders::ALL);
         let area = centered_rect(60, 20, size);
         let text = Paragraph::new(Span::styled(
-            "Match state saved successfully.",
+            message.as_str(),
             Style::default().fg(Color::LightGreen),
         ))
         .alignment(Alignment::Center);
@@ -278,11 +1463,11 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
         f.render_widget(text, area);
     }
 
-    if app.game_over_text.is_some() {
-        let block = Block::default().title("Popup").borders(Borders::ALL);
+    if app.chess_match.get_pending_promotion().is_some() {
+        let block = Block::default().title("Promotion").borders(Borders::ALL);
         let area = centered_rect(60, 20, size);
         let text = Paragraph::new(Span::styled(
-            app.game_over_text.as_ref().unwrap().as_str(),
+            "Promote to: (q)ueen, (r)ook, (b)ishop or k(n)ight. Esc to cancel.",
             Style::default().fg(Color::LightGreen),
         ))
         .alignment(Alignment::Center);
@@ -290,39 +1475,432 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
         f.render_widget(block, area);
         f.render_widget(text, area);
     }
+
+    if let Some(saved) = app.saved_games.as_ref() {
+        draw_saved_games(f, centered_rect(70, 60, size), saved);
+    }
+
+    if let Some(dialog) = app.file_dialog.as_ref() {
+        draw_file_dialog(f, centered_rect(70, 60, size), dialog);
+    }
+
+    if app.game_over_text.is_some() && app.replay.is_none() {
+        let (title, color) = match app.chess_match.result() {
+            MatchResult::Draw { .. } => ("Draw", Color::LightYellow),
+            _ => ("Game Over", Color::LightGreen),
+        };
+        let block = Block::default().title(title).borders(Borders::ALL);
+        let area = centered_rect(60, 20, size);
+        let text = Paragraph::new(Span::styled(
+            app.game_over_text.as_ref().unwrap().as_str(),
+            Style::default().fg(color),
+        ))
+        .alignment(Alignment::Center);
+        f.render_widget(Clear, area); //this clears out the background
+        f.render_widget(block, area);
+        f.render_widget(text, area);
+    }
+
+    if let Some(menu) = app.new_game_menu.as_ref() {
+        draw_new_game_menu(f, centered_rect(50, 40, size), menu);
+    }
 }
 
-fn draw_pieces(ctx: &mut Context, chess_match: &ChessMatch) {
-    let base_x = 2.1f64;
-    let base_y = 2.25f64;
-    let check_color = Color::Yellow;
+fn draw_new_game_menu<B: Backend>(f: &mut Frame<B>, area: Rect, menu: &NewGameMenu) {
+    let color = color_name(menu.color);
+    let opponent = match menu.opponent {
+        Opponent::Human => "Human".to_string(),
+        Opponent::Computer(difficulty) => format!("Computer ({})", difficulty.name()),
+    };
+    let items = vec![
+        ListItem::new(format!("Play as: {}", color)),
+        ListItem::new(format!("Opponent: {}", opponent)),
+        ListItem::new("Start new game"),
+        ListItem::new("Load a saved game"),
+    ];
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("New game: Enter to change or pick, Esc to close")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().fg(Color::LightGreen))
+        .highlight_symbol("> ");
+    let mut state = ListState::default();
+    state.select(Some(menu.selected));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
 
+fn draw_saved_games<B: Backend>(f: &mut Frame<B>, area: Rect, saved: &SavedGames) {
+    let items: Vec<ListItem> = if saved.games.is_empty() {
+        vec![ListItem::new("No saved games.")]
+    } else {
+        saved
+            .games
+            .iter()
+            .map(|game| {
+                ListItem::new(format!(
+                    "{}  {:>3} moves  {:?}",
+                    game.updated_at.format("%Y-%m-%d %H:%M"),
+                    game.move_count,
+                    game.result
+                ))
+            })
+            .collect()
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Saved games: Enter to resume, d to delete, Tab for files, Esc to close")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().fg(Color::LightGreen))
+        .highlight_symbol("> ");
+    let mut state = ListState::default();
+    if !saved.games.is_empty() {
+        state.select(Some(saved.selected));
+    }
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_file_dialog<B: Backend>(f: &mut Frame<B>, area: Rect, dialog: &FileDialog) {
+    let rects = Layout::default()
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+    let title = match dialog.mode {
+        FileMode::Save => "Save as: Enter to save, Esc to cancel",
+        FileMode::Load => "Load file: Enter to load, Tab for saved games, Esc to cancel",
+    };
+    let mut spans = vec![Span::raw(dialog.filename.as_str())];
+    if dialog.confirm_overwrite {
+        spans.push(Span::styled(
+            "  already exists, overwrite? (y/n)",
+            Style::default().fg(Color::LightYellow),
+        ));
+    } else if let Some(error) = dialog.error.as_ref() {
+        spans.push(Span::styled(
+            format!("  {}", error),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+    let input = Paragraph::new(Spans::from(spans))
+        .block(Block::default().borders(Borders::ALL).title(title));
+
+    let items: Vec<ListItem> = if dialog.files.is_empty() {
+        vec![ListItem::new("No saves in this directory.")]
+    } else {
+        dialog
+            .files
+            .iter()
+            .map(|file| ListItem::new(file.as_str()))
+            .collect()
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Saves: Up/Down to pick")
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().fg(Color::LightGreen))
+        .highlight_symbol("> ");
+    let mut state = ListState::default();
+    state.select(dialog.selected);
+
+    f.render_widget(Clear, area);
+    f.render_widget(input, rects[0]);
+    f.render_stateful_widget(list, rects[1], &mut state);
+}
+
+/// The pieces each side has taken, most valuable first, and who is ahead on material.
+fn draw_material<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    chess_match: &ChessMatch,
+    theme: &Theme,
+) {
+    let taken_by = |color: PieceColor| {
+        let mut captured = chess_match.captured_pieces(color.opposite());
+        captured.sort_by_key(|p| std::cmp::Reverse(p.points()));
+        captured.iter().map(|p| theme.glyph(p)).collect::<String>()
+    };
+    let balance = chess_match.material_balance();
+    let score = match balance.cmp(&0) {
+        std::cmp::Ordering::Greater => format!("White +{}", balance),
+        std::cmp::Ordering::Less => format!("Black +{}", -balance),
+        std::cmp::Ordering::Equal => "Even".to_string(),
+    };
+
+    let lines = vec![
+        Spans::from(format!("White: {}", taken_by(PieceColor::White))),
+        Spans::from(format!("Black: {}", taken_by(PieceColor::Black))),
+        Spans::from(Span::styled(score, Style::default().fg(Color::LightGreen))),
+    ];
+    let text =
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Captured"));
+    f.render_widget(text, area);
+}
+
+/// The numbered moves of the match, following the latest move unless scrolled back by
+/// `scroll` rows.
+fn draw_move_list<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    chess_match: &ChessMatch,
+    scroll: usize,
+) {
+    let rows = MovementLogger::get_move_rows(chess_match);
+    let height = area.height.saturating_sub(2) as usize;
+    let end = rows
+        .len()
+        .saturating_sub(scroll)
+        .max(height.min(rows.len()));
+    let start = end.saturating_sub(height);
+    let lines: Vec<Spans> = rows[start..end]
+        .iter()
+        .map(|row| Spans::from(row.as_str()))
+        .collect();
+
+    let title = if scroll > 0 {
+        "Moves (PgDn for latest)"
+    } else {
+        "Moves (PgUp/PgDn)"
+    };
+    let list = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(list, area);
+}
+
+fn draw_move_entry<B: Backend>(f: &mut Frame<B>, area: Rect, text: &str, error: Option<&str>) {
+    let mut spans = vec![Span::raw(format!("> {}", text))];
+    if let Some(error) = error {
+        spans.push(Span::raw("   "));
+        spans.push(Span::styled(error, Style::default().fg(Color::LightRed)));
+    }
+
+    let text = Paragraph::new(Spans::from(spans)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Move (SAN like Nf3 or coordinates like e2e4): Enter to play, Esc to cancel"),
+    );
+    f.render_widget(text, area);
+}
+
+fn draw_clocks<B: Backend>(f: &mut Frame<B>, area: Rect, chess_match: &ChessMatch) {
+    let (_, to_move) = chess_match.get_current_turn_and_color();
+    let clock = |color: PieceColor, name: &str| {
+        let time = format_time(chess_match.time_remaining(color).unwrap_or(0));
+        let style = if color == to_move && !chess_match.is_game_over() {
+            Style::default().fg(Color::LightGreen)
+        } else {
+            Style::default()
+        };
+        Span::styled(format!("{} {}", name, time), style)
+    };
+    let paused = chess_match.get_clock().is_some_and(|c| c.is_paused());
+    let mut spans = vec![
+        clock(PieceColor::White, "White"),
+        Span::raw("   "),
+        clock(PieceColor::Black, "Black"),
+    ];
+    if paused {
+        spans.push(Span::raw("   (paused)"));
+    }
+
+    let text = Paragraph::new(Spans::from(spans))
+        .block(Block::default().borders(Borders::ALL).title("Clock"))
+        .alignment(Alignment::Center);
+    f.render_widget(text, area);
+}
+
+/// A bar filled with White's share of the evaluation, from White's side of the board, and
+/// the evaluation itself as the title.
+fn draw_eval_bar<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    evaluation: Option<Evaluation>,
+    flipped: bool,
+) {
+    let title = evaluation.map_or_else(|| "...".to_string(), |e| e.to_string());
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    let share = evaluation.map_or(0.5, |e| e.white_share());
+    let white_rows = (share * inner.height as f64).round() as u16;
+
+    let lines: Vec<Spans> = (0..inner.height)
+        .map(|row| {
+            // rows counted from White's side
+            let from_white = if flipped { row } else { inner.height - 1 - row };
+            let color = if from_white < white_rows {
+                Color::White
+            } else {
+                Color::DarkGray
+            };
+            Spans::from(Span::styled(
+                "█".repeat(inner.width as usize),
+                Style::default().fg(color),
+            ))
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Whose turn it is, check, the move number, a spinner while the computer is thinking or
+/// the game is being annotated, and anything the player should know about their last action, over a line of key help.
+fn draw_status<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    chess_match: &ChessMatch,
+    activity: Option<(&str, Duration)>,
+    message: Option<&str>,
+) {
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+    let (_, to_move) = chess_match.get_current_turn_and_color();
+    let state = if chess_match.is_game_over() {
+        game_over_message(chess_match.result()).to_string()
+    } else {
+        format!("{} to move", color_name(to_move))
+    };
+    let mut spans = vec![
+        Span::styled(state, Style::default().fg(Color::LightGreen)),
+        Span::raw(format!("   Move {}", chess_match.fullmove_number())),
+    ];
+    for (color, king_state) in [
+        (PieceColor::White, chess_match.get_white_king_state()),
+        (PieceColor::Black, chess_match.get_black_king_state()),
+    ] {
+        if king_state == KingState::InCheck {
+            spans.push(Span::styled(
+                format!("   {} is in check", color_name(color)),
+                Style::default().fg(Color::LightYellow),
+            ));
+        }
+    }
+    if let Some((activity, elapsed)) = activity {
+        let frame = SPINNER[(elapsed.as_millis() / 250) as usize % SPINNER.len()];
+        spans.push(Span::raw(format!("   {} {}", activity, frame)));
+    }
+    if let Some(message) = message {
+        spans.push(Span::styled(
+            format!("   {}", message),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+
+    let help = Span::styled(
+        "arrows/click: move cursor  space: select  m: type a move  u: undo  h: hint  \
+         e: analysis  c: pause clock  s: save  o: load  n: new game  v: replay  f: flip  \
+         q: quit",
+        Style::default().fg(Color::DarkGray),
+    );
+    f.render_widget(
+        Paragraph::new(vec![Spans::from(spans), Spans::from(help)]),
+        area,
+    );
+}
+
+/// Where the square at `tile` is drawn, counting from the bottom left of the screen, or
+/// the other way round: with the board flipped, h8 is at the bottom left.
+fn screen_tile(tile: (i32, i32), flipped: bool) -> (i32, i32) {
+    if flipped {
+        (7 - tile.0, 7 - tile.1)
+    } else {
+        tile
+    }
+}
+
+/// The largest rect inside the border of `area` that shows the canvas without stretching
+/// it, centered. Terminal cells are about twice as tall as they are wide, so it is twice as
+/// many columns across as rows down.
+fn square_board(area: Rect) -> Rect {
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    let height = inner.height.min(inner.width / 2);
+    let width = height * 2;
+    Rect::new(
+        inner.x + (inner.width - width) / 2,
+        inner.y + (inner.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// The square drawn under the terminal cell at `column`, `row`, counting from the bottom
+/// left of the screen like `screen_tile`. Follows the canvas geometry of `draw_board`:
+/// `CANVAS_BOUNDS` across and up `canvas_area`, with a square every `SQUARE_PITCH` units.
+fn clicked_tile(canvas_area: Rect, column: u16, row: u16) -> Option<(i32, i32)> {
+    let [canvas_min, canvas_max] = CANVAS_BOUNDS;
+    let Rect {
+        x: left,
+        y: top,
+        width,
+        height,
+    } = canvas_area;
+    if width < 2 || height < 2 || column < left || row < top {
+        return None;
+    }
+    if column >= left + width || row >= top + height {
+        return None;
+    }
+
+    let scale = |offset: u16, cells: u16| {
+        canvas_min + offset as f64 * (canvas_max - canvas_min) / (cells - 1) as f64
+    };
+    let x = scale(column - left, width);
+    let y = scale(top + height - 1 - row, height);
+    if x < 0.0 || y < 0.0 {
+        // on the coordinate labels
+        return None;
+    }
+    let tile = ((x / SQUARE_PITCH) as i32, (y / SQUARE_PITCH) as i32);
+    (tile.0 <= 7 && tile.1 <= 7).then_some(tile)
+}
+
+/// Labels the files along the bottom of the board and the ranks up its left side.
+fn draw_coordinates(ctx: &mut Context, flipped: bool) {
+    let style = Style::default().fg(Color::Gray);
+    for i in 0..8 {
+        let (file, rank) = screen_tile((i, i), flipped);
+        let center = square_center(i);
+        ctx.print(
+            center,
+            -0.9,
+            Spans::from(Span::styled(FILES[file as usize], style)),
+        );
+        ctx.print(
+            -0.9,
+            center,
+            Spans::from(Span::styled((rank + 1).to_string(), style)),
+        );
+    }
+}
+
+/// Where the middle of the `index`th square from the left or bottom is on the canvas.
+fn square_center(index: i32) -> f64 {
+    index as f64 * SQUARE_PITCH + SQUARE_SIZE / 2.0
+}
+
+fn draw_pieces(ctx: &mut Context, chess_match: &ChessMatch, theme: &Theme, flipped: bool) {
     for piece in &chess_match.pieces {
         if piece.is_captured() {
             continue;
         }
-        let mut color = Color::White;
-        if piece.color == PieceColor::Black {
-            if piece.get_type() == PieceType::King
-                && chess_match.get_black_king_state() == KingState::InCheck
-            {
-                color = check_color;
-            } else {
-                color = Color::DarkGray;
-            }
+        let king_state = match piece.color {
+            PieceColor::White => chess_match.get_white_king_state(),
+            PieceColor::Black => chess_match.get_black_king_state(),
+        };
+        let color = if piece.get_type() == PieceType::King && king_state == KingState::InCheck {
+            theme.king_in_check
         } else {
-            if piece.get_type() == PieceType::King
-                && chess_match.get_white_king_state() == KingState::InCheck
-            {
-                color = check_color;
-            }
-        }
+            theme.piece_color(piece.color)
+        };
         let style = Style::default().fg(color);
-        let spans = Spans::from(Span::styled(piece.get_text(), style));
+        let text = theme.glyph(piece);
+        let spans = Spans::from(Span::styled(text, style));
         let location = piece.location.get_x_y();
-        let x = (location.0 * base_x) + 1.0;
-        let y = (location.1 * base_y) + 0.50;
-        ctx.print(x, y, spans.clone());
+        let (screen_x, screen_y) = screen_tile((location.0 as i32, location.1 as i32), flipped);
+        ctx.print(square_center(screen_x), square_center(screen_y), spans);
     }
 }
 
@@ -331,100 +1909,83 @@ fn draw_board(
     current_tile: &(i32, i32),
     selected_tile: &Option<(i32, i32)>,
     chess_match: &ChessMatch,
+    hint: Option<&Move>,
+    theme: &Theme,
+    flipped: bool,
 ) {
-    let mut color = Color::DarkGray;
-    let mut x_offset = 0f64;
-    let mut y_offset = 0f64;
-
-    let valid_moves: Vec<(i32, i32)> = if selected_tile.is_some() {
+    let targets: Vec<((i32, i32), TargetKind)> = if selected_tile.is_some() {
         let loc = selected_tile.unwrap();
         let piece = chess_match
             .get_piece_at_location(PieceLocation::new_from_x_y(loc.0, loc.1 + 1))
             .unwrap();
         piece
-            .get_valid_moves()
-            .iter()
-            .map(|m| {
+            .get_targets()
+            .into_iter()
+            .map(|(m, kind)| {
                 let xy = m.get_x_y();
-                (xy.0 as i32, xy.1 as i32)
+                ((xy.0 as i32, xy.1 as i32), kind)
             })
             .collect()
     } else {
         Vec::new()
     };
-
-    let valid_captures: Vec<(i32, i32)> = if selected_tile.is_some() {
-        let loc = selected_tile.unwrap();
-        let piece = chess_match
-            .get_piece_at_location(PieceLocation::new_from_x_y(loc.0, loc.1 + 1))
-            .unwrap();
-        piece
-            .get_valid_captures()
-            .iter()
-            .map(|m| {
-                let xy = m.get_x_y();
-                (xy.0 as i32, xy.1 as i32)
-            })
-            .collect()
-    } else {
-        Vec::new()
+    let tile_of = |location: &PieceLocation| {
+        let (x, y) = location.get_x_y();
+        (x as i32, y as i32)
     };
+    let last_move: Vec<(i32, i32)> = chess_match
+        .last_move_squares()
+        .map_or(Vec::new(), |(from, to)| vec![tile_of(&from), tile_of(&to)]);
+    let hint: Vec<(i32, i32)> = hint.map_or(Vec::new(), |m| vec![tile_of(&m.from), tile_of(&m.to)]);
 
     for y in 0..=7 {
-        if y % 2 == 0 {
-            color = Color::DarkGray;
-        } else {
-            color = Color::White;
-        }
         for x in 0..=7 {
-            let is_valid_move = valid_moves.contains(&(x, y));
-            let is_valid_capture = valid_captures.contains(&(x, y));
-            let is_current = x == current_tile.0 && y == current_tile.1;
-            let is_selected = if selected_tile.is_some() {
-                let s_tile = selected_tile.unwrap();
-                x == s_tile.0 && y == s_tile.1
+            let color = if (x + y) % 2 == 0 {
+                theme.dark_square
             } else {
-                false
+                theme.light_square
             };
-            if x > 0 {
-                x_offset = (x as f64) * 1.125f64;
+            // flipping turns the board half way round, so squares keep their colors
+            let tile = screen_tile((x, y), flipped);
+            let is_valid_move = targets.contains(&(tile, TargetKind::Move));
+            let is_valid_capture = targets.contains(&(tile, TargetKind::Capture));
+            let is_current = tile == *current_tile;
+            let is_selected = *selected_tile == Some(tile);
+            let color_to_use = if hint.contains(&tile) {
+                theme.hint
+            } else if last_move.contains(&tile) {
+                theme.last_move
             } else {
-                x_offset = 0f64;
-            }
-            if y > 0 {
-                y_offset = (y as f64) * 1.125f64;
+                color
+            };
+            let color_to_use = if is_selected {
+                theme.selected
             } else {
-                y_offset = 0f64;
-            }
-            let color_to_use = if is_selected { Color::Yellow } else { color };
+                color_to_use
+            };
             let color_to_use = if is_valid_move {
-                Color::LightMagenta
+                theme.move_target
             } else {
                 color_to_use
             };
             let color_to_use = if is_valid_capture {
-                Color::LightRed
+                theme.capture_target
             } else {
                 color_to_use
             };
             let color_to_use = if is_current {
-                Color::Green
+                theme.cursor
             } else {
                 color_to_use
             };
             let rect = Rectangle {
-                x: (x as f64) + x_offset,
-                y: (y as f64) + y_offset,
-                width: 2f64,
-                height: 2f64,
+                x: x as f64 * SQUARE_PITCH,
+                y: y as f64 * SQUARE_PITCH,
+                width: SQUARE_SIZE,
+                height: SQUARE_SIZE,
                 color: color_to_use,
             };
             ctx.draw(&rect);
-            if color == Color::DarkGray {
-                color = Color::White;
-            } else {
-                color = Color::DarkGray;
-            }
         }
     }
 }
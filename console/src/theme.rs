@@ -0,0 +1,200 @@
+use std::{error::Error, fs, path::Path};
+
+use chess_engine::piece_base::{ChessPiece, PieceColor};
+use serde::Deserialize;
+use tui::style::Color;
+
+/// How pieces are written on the board.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PieceGlyphs {
+    /// Chess symbols like ♔ and ♟.
+    Unicode,
+    /// FEN letters, upper case for white: K, Q, R, B, N and P.
+    Ascii,
+}
+
+/// The colors and piece set the board is drawn with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub light_square: Color,
+    pub dark_square: Color,
+    pub cursor: Color,
+    pub selected: Color,
+    pub move_target: Color,
+    pub capture_target: Color,
+    pub last_move: Color,
+    pub hint: Color,
+    pub white_piece: Color,
+    pub black_piece: Color,
+    pub king_in_check: Color,
+    pub glyphs: PieceGlyphs,
+}
+
+/// A theme file: the name of a built-in theme to start from, and whatever should differ from
+/// it. Colors are names like `light_green` or `#rrggbb`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    theme: Option<String>,
+    pieces: Option<PieceGlyphs>,
+    light_square: Option<String>,
+    dark_square: Option<String>,
+    cursor: Option<String>,
+    selected: Option<String>,
+    move_target: Option<String>,
+    capture_target: Option<String>,
+    last_move: Option<String>,
+    hint: Option<String>,
+    white_piece: Option<String>,
+    black_piece: Option<String>,
+    king_in_check: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            light_square: Color::White,
+            dark_square: Color::DarkGray,
+            cursor: Color::Green,
+            selected: Color::Yellow,
+            move_target: Color::LightMagenta,
+            capture_target: Color::LightRed,
+            last_move: Color::Cyan,
+            hint: Color::LightBlue,
+            white_piece: Color::White,
+            black_piece: Color::DarkGray,
+            king_in_check: Color::Yellow,
+            glyphs: PieceGlyphs::Unicode,
+        }
+    }
+}
+
+impl Theme {
+    pub const NAMES: [&'static str; 3] = ["classic", "contrast", "wood"];
+
+    /// One of the built-in themes in `NAMES`.
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "classic" => Some(Theme::default()),
+            // letters on plain squares, for terminals that draw the chess symbols badly
+            "contrast" => Some(Theme {
+                light_square: Color::Gray,
+                dark_square: Color::Black,
+                cursor: Color::LightGreen,
+                selected: Color::LightYellow,
+                move_target: Color::LightBlue,
+                capture_target: Color::LightRed,
+                last_move: Color::Blue,
+                hint: Color::LightCyan,
+                white_piece: Color::White,
+                black_piece: Color::LightRed,
+                king_in_check: Color::LightYellow,
+                glyphs: PieceGlyphs::Ascii,
+            }),
+            "wood" => Some(Theme {
+                light_square: Color::Rgb(240, 217, 181),
+                dark_square: Color::Rgb(181, 136, 99),
+                cursor: Color::Rgb(106, 168, 79),
+                selected: Color::Rgb(246, 246, 105),
+                move_target: Color::Rgb(130, 151, 105),
+                capture_target: Color::Rgb(204, 85, 85),
+                last_move: Color::Rgb(205, 210, 106),
+                hint: Color::Rgb(100, 150, 220),
+                white_piece: Color::White,
+                black_piece: Color::Black,
+                king_in_check: Color::Red,
+                glyphs: PieceGlyphs::Unicode,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Reads a JSON theme file, e.g. `{"theme": "wood", "pieces": "ascii"}`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Theme, Box<dyn Error>> {
+        let file: ThemeFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut theme = match file.theme.as_deref() {
+            Some(name) => Theme::named(name).ok_or_else(|| unknown_theme(name))?,
+            None => Theme::default(),
+        };
+        if let Some(glyphs) = file.pieces {
+            theme.glyphs = glyphs;
+        }
+
+        let overrides = [
+            (file.light_square, &mut theme.light_square),
+            (file.dark_square, &mut theme.dark_square),
+            (file.cursor, &mut theme.cursor),
+            (file.selected, &mut theme.selected),
+            (file.move_target, &mut theme.move_target),
+            (file.capture_target, &mut theme.capture_target),
+            (file.last_move, &mut theme.last_move),
+            (file.hint, &mut theme.hint),
+            (file.white_piece, &mut theme.white_piece),
+            (file.black_piece, &mut theme.black_piece),
+            (file.king_in_check, &mut theme.king_in_check),
+        ];
+        for (name, color) in overrides {
+            if let Some(name) = name {
+                *color = parse_color(&name).ok_or_else(|| format!("unknown color {}", name))?;
+            }
+        }
+
+        Ok(theme)
+    }
+
+    /// The text `piece` is drawn as.
+    pub fn glyph(&self, piece: &ChessPiece) -> String {
+        match self.glyphs {
+            PieceGlyphs::Unicode => piece.get_text(),
+            PieceGlyphs::Ascii => piece.get_ascii_text().to_string(),
+        }
+    }
+
+    pub fn piece_color(&self, color: PieceColor) -> Color {
+        match color {
+            PieceColor::White => self.white_piece,
+            PieceColor::Black => self.black_piece,
+        }
+    }
+}
+
+pub fn unknown_theme(name: &str) -> String {
+    format!(
+        "unknown theme {}, expected one of {}",
+        name,
+        Theme::NAMES.join(", ")
+    )
+}
+
+/// A terminal color name in snake case, e.g. `dark_gray`, or `#rrggbb`.
+fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+
+    let color = match name {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "dark_gray" => Color::DarkGray,
+        "light_red" => Color::LightRed,
+        "light_green" => Color::LightGreen,
+        "light_yellow" => Color::LightYellow,
+        "light_blue" => Color::LightBlue,
+        "light_magenta" => Color::LightMagenta,
+        "light_cyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    };
+    Some(color)
+}
@@ -0,0 +1,58 @@
+#ifndef CHESS_ENGINE_H
+#define CHESS_ENGINE_H
+
+#include <stdbool.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An opaque match, owned by the caller. */
+typedef struct ChessMatchHandle ChessMatchHandle;
+
+typedef enum ChessError {
+    CHESS_OK = 0,
+    CHESS_NULL_ARGUMENT = 1,
+    CHESS_INVALID_UTF8 = 2,
+    CHESS_INVALID_NOTATION = 3,
+    CHESS_ILLEGAL_MOVE = 4,
+    CHESS_PROMOTION_REQUIRED = 5,
+    CHESS_GAME_OVER = 6,
+    CHESS_INVALID_POSITION = 7,
+    /* The engine panicked; do not use the match again. See chess_last_error. */
+    CHESS_PANIC = 8,
+} ChessError;
+
+/* A new match from the starting position. Release it with chess_match_free. */
+ChessMatchHandle *chess_match_new(void);
+
+/* Sets up a match from a FEN string and stores it in out. On failure the reason is
+ * available from chess_last_error. */
+ChessError chess_match_from_fen(const char *fen, ChessMatchHandle **out);
+
+void chess_match_free(ChessMatchHandle *handle);
+
+/* Plays a move in SAN ("Nf3", "e8=Q") or coordinates ("g1f3"). */
+ChessError chess_match_apply_san(ChessMatchHandle *handle, const char *san);
+
+/* Strings returned by these belong to the caller; release them with chess_string_free. */
+char *chess_match_to_fen(const ChessMatchHandle *handle);
+char *chess_match_legal_moves_json(const ChessMatchHandle *handle);
+
+bool chess_match_is_game_over(const ChessMatchHandle *handle);
+
+/* The message of the last failed call on handle, or NULL. Owned by the match and valid
+ * until the next call on it. */
+const char *chess_match_last_error(const ChessMatchHandle *handle);
+
+/* The message of the last chess_match_from_fen failure or panic on this thread, or NULL.
+ * Valid until the next such call on the same thread. */
+const char *chess_last_error(void);
+
+void chess_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CHESS_ENGINE_H */
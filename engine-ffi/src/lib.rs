@@ -0,0 +1,398 @@
+//! C ABI for the engine, for embedding it in other languages. See `include/chess_engine.h`.
+//!
+//! A match is an opaque `ChessMatchHandle` owned by the caller, created with
+//! `chess_match_new` or `chess_match_from_fen` and released with `chess_match_free`.
+//! Strings returned by the library are released with `chess_string_free`.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use chess_engine::{chess_match::ChessMatch, chess_move::MoveError};
+use uuid::Uuid;
+
+/// Result codes returned by every fallible call. The message for anything but `Ok` is
+/// available from `chess_match_last_error`, or from `chess_last_error` for failures of
+/// `chess_match_from_fen` and for `Panic`.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChessError {
+    Ok = 0,
+    NullArgument = 1,
+    InvalidUtf8 = 2,
+    InvalidNotation = 3,
+    IllegalMove = 4,
+    PromotionRequired = 5,
+    GameOver = 6,
+    InvalidPosition = 7,
+    /// The engine panicked. The match it was called on should not be used again.
+    Panic = 8,
+}
+
+impl From<&MoveError> for ChessError {
+    fn from(e: &MoveError) -> Self {
+        match e {
+            MoveError::InvalidNotation(_) => ChessError::InvalidNotation,
+            MoveError::IllegalMove(_)
+            | MoveError::IllegalDestination(_)
+            | MoveError::NotAPlayer(_)
+            | MoveError::NotYourTurn(_)
+            | MoveError::PieceCaptured(_) => ChessError::IllegalMove,
+            MoveError::InvalidPosition(_) => ChessError::InvalidPosition,
+            MoveError::PromotionRequired(_) => ChessError::PromotionRequired,
+            MoveError::GameOver => ChessError::GameOver,
+        }
+    }
+}
+
+/// A match and the message of the last failed call on it.
+pub struct ChessMatchHandle {
+    chess_match: ChessMatch,
+    last_error: Option<CString>,
+}
+
+impl ChessMatchHandle {
+    fn new(chess_match: ChessMatch) -> *mut ChessMatchHandle {
+        Box::into_raw(Box::new(ChessMatchHandle {
+            chess_match,
+            last_error: None,
+        }))
+    }
+
+    fn fail(&mut self, code: ChessError, message: String) -> ChessError {
+        self.last_error = CString::new(message).ok();
+        code
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: Option<String>) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = message.and_then(|m| CString::new(m).ok()));
+}
+
+/// Runs `f`, turning a panic into `on_panic` so it never unwinds into the caller.
+fn guard<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|m| m.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        set_last_error(Some(format!("engine panicked: {}", message)));
+        on_panic
+    })
+}
+
+/// A new match from the starting position, or null if the engine panicked.
+#[no_mangle]
+pub extern "C" fn chess_match_new() -> *mut ChessMatchHandle {
+    guard(ptr::null_mut(), || {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        ChessMatchHandle::new(chess_match)
+    })
+}
+
+/// Sets up a match from `fen` and stores it in `out`. Nothing is stored on failure, and
+/// the reason is available from `chess_last_error`.
+///
+/// # Safety
+///
+/// `fen` must be a valid NUL-terminated string and `out` a valid pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn chess_match_from_fen(
+    fen: *const c_char,
+    out: *mut *mut ChessMatchHandle,
+) -> ChessError {
+    guard(ChessError::Panic, || {
+        if out.is_null() {
+            set_last_error(Some("out is null".to_string()));
+            return ChessError::NullArgument;
+        }
+        let fen = match read_str(fen) {
+            Ok(fen) => fen,
+            Err(code) => {
+                set_last_error(Some("FEN is null or not valid UTF-8".to_string()));
+                return code;
+            }
+        };
+
+        match ChessMatch::from_fen(fen) {
+            Ok(chess_match) => {
+                set_last_error(None);
+                *out = ChessMatchHandle::new(chess_match);
+                ChessError::Ok
+            }
+            Err(e) => {
+                set_last_error(Some(e.to_string()));
+                ChessError::InvalidPosition
+            }
+        }
+    })
+}
+
+/// Releases a match. Passing null does nothing.
+///
+/// # Safety
+///
+/// `handle` must come from this library and not be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chess_match_free(handle: *mut ChessMatchHandle) {
+    guard((), || {
+        if !handle.is_null() {
+            drop(Box::from_raw(handle));
+        }
+    })
+}
+
+/// Plays a move given in SAN, e.g. `Nf3` or `e8=Q`. Coordinates like `g1f3` work too.
+///
+/// # Safety
+///
+/// `handle` must be a live match from this library and `san` a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn chess_match_apply_san(
+    handle: *mut ChessMatchHandle,
+    san: *const c_char,
+) -> ChessError {
+    guard(ChessError::Panic, || {
+        let handle = match handle.as_mut() {
+            Some(handle) => handle,
+            None => return ChessError::NullArgument,
+        };
+        let san = match read_str(san) {
+            Ok(san) => san,
+            Err(code) => return handle.fail(code, "move is not valid UTF-8".to_string()),
+        };
+
+        match handle.chess_match.apply_san(san) {
+            Ok(_) => {
+                handle.last_error = None;
+                ChessError::Ok
+            }
+            Err(e) => handle.fail(ChessError::from(&e), e.to_string()),
+        }
+    })
+}
+
+/// The position in FEN, or null if `handle` is null or the engine panicked. Release it with `chess_string_free`.
+///
+/// # Safety
+///
+/// `handle` must be a live match from this library.
+#[no_mangle]
+pub unsafe extern "C" fn chess_match_to_fen(handle: *const ChessMatchHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || match handle.as_ref() {
+        Some(handle) => to_c_string(handle.chess_match.to_fen()),
+        None => ptr::null_mut(),
+    })
+}
+
+/// The legal moves of the side to move as a JSON array of moves, each with `from`, `to` and
+/// `promotion`, or null if `handle` is null or the engine panicked. Release it with `chess_string_free`.
+///
+/// # Safety
+///
+/// `handle` must be a live match from this library.
+#[no_mangle]
+pub unsafe extern "C" fn chess_match_legal_moves_json(
+    handle: *const ChessMatchHandle,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || match handle.as_ref() {
+        Some(handle) => match serde_json::to_string(&handle.chess_match.legal_moves()) {
+            Ok(json) => to_c_string(json),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    })
+}
+
+/// Whether the game has ended, by mate, stalemate or otherwise.
+///
+/// # Safety
+///
+/// `handle` must be a live match from this library or null.
+#[no_mangle]
+pub unsafe extern "C" fn chess_match_is_game_over(handle: *const ChessMatchHandle) -> bool {
+    guard(false, || {
+        handle
+            .as_ref()
+            .map(|handle| handle.chess_match.is_game_over())
+            .unwrap_or(false)
+    })
+}
+
+/// The message of the last failed call on `handle`, or null if the last call succeeded.
+/// The string belongs to the match and stays valid until the next call on it.
+///
+/// # Safety
+///
+/// `handle` must be a live match from this library.
+#[no_mangle]
+pub unsafe extern "C" fn chess_match_last_error(handle: *const ChessMatchHandle) -> *const c_char {
+    guard(ptr::null(), || {
+        match handle
+            .as_ref()
+            .and_then(|handle| handle.last_error.as_ref())
+        {
+            Some(message) => message.as_ptr(),
+            None => ptr::null(),
+        }
+    })
+}
+
+/// The message of the last `chess_match_from_fen` failure or panic on this thread, or null
+/// if there was none. The string stays valid until the next such call on this thread.
+#[no_mangle]
+pub extern "C" fn chess_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Releases a string returned by this library. Passing null does nothing.
+///
+/// # Safety
+///
+/// `s` must come from this library and not be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chess_string_free(s: *mut c_char) {
+    guard((), || {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    })
+}
+
+unsafe fn read_str<'a>(s: *const c_char) -> Result<&'a str, ChessError> {
+    if s.is_null() {
+        return Err(ChessError::NullArgument);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| ChessError::InvalidUtf8)
+}
+
+fn to_c_string(s: String) -> *mut c_char {
+    // engine output never contains NUL
+    CString::new(s).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take_string(s: *mut c_char) -> String {
+        assert!(!s.is_null());
+        unsafe {
+            let owned = CStr::from_ptr(s).to_str().unwrap().to_string();
+            chess_string_free(s);
+            owned
+        }
+    }
+
+    #[test]
+    fn test_play_moves_through_the_c_api() {
+        let handle = chess_match_new();
+        unsafe {
+            for san in ["f3", "e5", "g4", "Qh4#"] {
+                let san = CString::new(san).unwrap();
+                assert_eq!(ChessError::Ok, chess_match_apply_san(handle, san.as_ptr()));
+            }
+            assert!(chess_match_is_game_over(handle));
+            assert_eq!(
+                "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+                take_string(chess_match_to_fen(handle))
+            );
+            assert_eq!("[]", take_string(chess_match_legal_moves_json(handle)));
+            chess_match_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_errors_are_reported() {
+        unsafe {
+            let mut handle = ptr::null_mut();
+            let bad_fen = CString::new("not a fen").unwrap();
+            assert_eq!(
+                ChessError::InvalidPosition,
+                chess_match_from_fen(bad_fen.as_ptr(), &mut handle)
+            );
+            assert!(handle.is_null());
+            assert!(!chess_last_error().is_null());
+
+            let fen = CString::new("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+            assert_eq!(
+                ChessError::Ok,
+                chess_match_from_fen(fen.as_ptr(), &mut handle)
+            );
+            let moves = take_string(chess_match_legal_moves_json(handle));
+            assert!(moves.contains("\"to\""));
+
+            let illegal = CString::new("Ke3").unwrap();
+            assert_ne!(
+                ChessError::Ok,
+                chess_match_apply_san(handle, illegal.as_ptr())
+            );
+            assert!(!chess_match_last_error(handle).is_null());
+
+            let castle = CString::new("O-O").unwrap();
+            assert_eq!(
+                ChessError::Ok,
+                chess_match_apply_san(handle, castle.as_ptr())
+            );
+            assert!(chess_match_last_error(handle).is_null());
+
+            assert_eq!(
+                ChessError::NullArgument,
+                chess_match_apply_san(handle, ptr::null())
+            );
+            assert_eq!(
+                ChessError::NullArgument,
+                chess_match_apply_san(ptr::null_mut(), castle.as_ptr())
+            );
+            chess_match_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_fen_errors_keep_their_message() {
+        unsafe {
+            let mut handle = ptr::null_mut();
+            let fen = CString::new("4k3/8/8/8/8/8/8/4K2R x K - 0 1").unwrap();
+            assert_eq!(
+                ChessError::InvalidPosition,
+                chess_match_from_fen(fen.as_ptr(), &mut handle)
+            );
+            let message = CStr::from_ptr(chess_last_error()).to_str().unwrap();
+            assert_eq!("Invalid side to move: x", message);
+
+            let fen = CString::new("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+            assert_eq!(
+                ChessError::Ok,
+                chess_match_from_fen(fen.as_ptr(), &mut handle)
+            );
+            assert!(chess_last_error().is_null());
+            chess_match_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_panics_do_not_cross_the_c_boundary() {
+        assert_eq!(
+            ChessError::Panic,
+            guard(ChessError::Panic, || panic!("board on fire"))
+        );
+        let message = unsafe { CStr::from_ptr(chess_last_error()) };
+        assert_eq!("engine panicked: board on fire", message.to_str().unwrap());
+    }
+}
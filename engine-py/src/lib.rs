@@ -0,0 +1,230 @@
+//! Python bindings for the engine, built with maturin: `maturin develop` in this directory
+//! installs the `chess_engine_py` module into the current virtualenv.
+
+// false positive on the error conversion #[pymethods] generates for PyResult returns
+#![allow(clippy::useless_conversion)]
+
+use chess_engine::{
+    chess_match::ChessMatch,
+    chess_move,
+    piece_base::PieceColor,
+    piece_location,
+    search::{AiPlayer, SearchConfig},
+};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use uuid::Uuid;
+
+fn value_error(e: impl ToString) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// A square, e.g. `PieceLocation("e4")`.
+#[pyclass(name = "PieceLocation", eq, frozen, hash)]
+#[derive(Clone, PartialEq, Hash)]
+pub struct PyPieceLocation {
+    location: piece_location::PieceLocation,
+}
+
+#[pymethods]
+impl PyPieceLocation {
+    #[new]
+    fn new(square: &str) -> PyResult<Self> {
+        piece_location::PieceLocation::new_from_string(square)
+            .map(|location| PyPieceLocation { location })
+            .map_err(|e| value_error(format!("{}: {}", square, e)))
+    }
+
+    #[getter]
+    fn file(&self) -> String {
+        self.location.get_file()
+    }
+
+    #[getter]
+    fn rank(&self) -> u32 {
+        self.location.get_rank()
+    }
+
+    fn __str__(&self) -> String {
+        self.location.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("PieceLocation('{}')", self.location)
+    }
+}
+
+/// A legal move. `str(move)` gives it in UCI notation, e.g. `e7e8q`.
+#[pyclass(name = "Move", eq, frozen, hash)]
+#[derive(Clone, PartialEq, Hash)]
+pub struct PyMove {
+    chess_move: chess_move::Move,
+}
+
+#[pymethods]
+impl PyMove {
+    #[getter(from_square)]
+    fn origin(&self) -> PyPieceLocation {
+        PyPieceLocation {
+            location: self.chess_move.from.clone(),
+        }
+    }
+
+    #[getter(to_square)]
+    fn destination(&self) -> PyPieceLocation {
+        PyPieceLocation {
+            location: self.chess_move.to.clone(),
+        }
+    }
+
+    #[getter]
+    fn capture(&self) -> bool {
+        self.chess_move.capture
+    }
+
+    /// The piece a pawn promotes to, e.g. `"queen"`, or None.
+    #[getter]
+    fn promotion(&self) -> Option<String> {
+        self.chess_move.promotion.map(|p| p.get_name())
+    }
+
+    fn __str__(&self) -> String {
+        self.chess_move.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Move('{}')", self.chess_move)
+    }
+}
+
+/// A game of chess, starting from the initial position unless made with `from_fen`.
+#[pyclass(name = "ChessMatch")]
+pub struct PyChessMatch {
+    chess_match: ChessMatch,
+}
+
+#[pymethods]
+impl PyChessMatch {
+    #[new]
+    fn new() -> Self {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        PyChessMatch { chess_match }
+    }
+
+    #[staticmethod]
+    fn from_fen(fen: &str) -> PyResult<Self> {
+        ChessMatch::from_fen(fen)
+            .map(|chess_match| PyChessMatch { chess_match })
+            .map_err(value_error)
+    }
+
+    fn fen(&self) -> String {
+        self.chess_match.to_fen()
+    }
+
+    fn pgn(&self) -> String {
+        self.chess_match.to_pgn()
+    }
+
+    /// `"white"` or `"black"`.
+    fn side_to_move(&self) -> &'static str {
+        match self.chess_match.get_current_turn_and_color().1 {
+            PieceColor::White => "white",
+            PieceColor::Black => "black",
+        }
+    }
+
+    fn legal_moves(&self) -> Vec<PyMove> {
+        self.chess_match
+            .legal_moves_sorted()
+            .into_iter()
+            .map(|chess_move| PyMove { chess_move })
+            .collect()
+    }
+
+    /// Plays a move given in SAN (`Nf3`, `e8=Q`), long algebraic or UCI notation, or a
+    /// `Move` from `legal_moves`. Returns the move in SAN. Raises ValueError if it is illegal.
+    fn push(&mut self, chess_move: &Bound<'_, PyAny>) -> PyResult<String> {
+        let text = match chess_move.extract::<PyMove>() {
+            Ok(m) => m.chess_move.to_string(),
+            Err(_) => chess_move.extract::<String>()?,
+        };
+        self.chess_match
+            .apply_san(&text)
+            .map(|entry| entry.to_string())
+            .map_err(value_error)
+    }
+
+    /// Takes back the last move. Returns False if there was none, or if the game ended in a
+    /// resignation, timeout or agreed draw.
+    fn undo(&mut self) -> bool {
+        self.chess_match.undo_move().is_some()
+    }
+
+    fn is_game_over(&self) -> bool {
+        self.chess_match.is_game_over()
+    }
+
+    /// The result, e.g. `InProgress`, `WhiteWonCheckmate` or `Draw { reason: Stalemate }`.
+    fn result(&self) -> String {
+        format!("{:?}", self.chess_match.result())
+    }
+
+    /// The moves played so far in SAN.
+    fn move_history(&self) -> Vec<String> {
+        self.chess_match.snapshot().move_history
+    }
+
+    fn copy(&self) -> Self {
+        PyChessMatch {
+            chess_match: self.chess_match.copy(),
+        }
+    }
+
+    fn __str__(&self) -> String {
+        self.chess_match.board_string()
+    }
+}
+
+/// The search engine. `search` returns `(move, score, depth)`, with the score in centipawns
+/// from the side to move's point of view, or None when there is no legal move.
+#[pyclass(name = "AiPlayer", unsendable)]
+pub struct PyAiPlayer {
+    ai: AiPlayer,
+}
+
+#[pymethods]
+impl PyAiPlayer {
+    #[new]
+    #[pyo3(signature = (max_depth=3, time_limit_ms=None))]
+    fn new(max_depth: u32, time_limit_ms: Option<u64>) -> Self {
+        PyAiPlayer {
+            ai: AiPlayer::new(SearchConfig {
+                max_depth,
+                time_limit_ms,
+                ..SearchConfig::default()
+            }),
+        }
+    }
+
+    fn search(&mut self, chess_match: &PyChessMatch) -> Option<(PyMove, i32, u32)> {
+        self.ai.search(&chess_match.chess_match).map(|result| {
+            (
+                PyMove {
+                    chess_move: result.best_move,
+                },
+                result.score,
+                result.depth,
+            )
+        })
+    }
+}
+
+#[pymodule]
+fn chess_engine_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyPieceLocation>()?;
+    m.add_class::<PyMove>()?;
+    m.add_class::<PyChessMatch>()?;
+    m.add_class::<PyAiPlayer>()?;
+    Ok(())
+}
@@ -0,0 +1,49 @@
+"""Run with `maturin develop && python -m pytest tests` from the engine-py directory."""
+import pytest
+
+from chess_engine_py import AiPlayer, ChessMatch, Move, PieceLocation
+
+
+def test_fools_mate():
+    chess_match = ChessMatch()
+    for san in ["f3", "e5", "g4", "Qh4#"]:
+        chess_match.push(san)
+
+    assert chess_match.is_game_over()
+    assert chess_match.result() == "BlackWonCheckmate"
+    assert chess_match.move_history() == ["f3", "e5", "g4", "Qh4#"]
+    assert chess_match.legal_moves() == []
+
+
+def test_legal_moves_can_be_played():
+    chess_match = ChessMatch()
+    moves = chess_match.legal_moves()
+    assert len(moves) == 20
+
+    e4 = next(m for m in moves if str(m) == "e2e4")
+    assert isinstance(e4, Move)
+    assert e4.from_square == PieceLocation("e2")
+    assert e4.to_square.rank == 4
+    assert chess_match.push(e4) == "e4"
+    assert chess_match.side_to_move() == "black"
+
+
+def test_fen_round_trip_and_errors():
+    fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1"
+    assert ChessMatch.from_fen(fen).fen() == fen
+
+    with pytest.raises(ValueError):
+        ChessMatch.from_fen("not a fen")
+    with pytest.raises(ValueError):
+        ChessMatch().push("Ke2")
+    with pytest.raises(ValueError):
+        PieceLocation("z9")
+
+
+def test_search_finds_mate_in_one():
+    chess_match = ChessMatch.from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")
+    best, score, depth = AiPlayer(max_depth=2).search(chess_match)
+
+    assert str(best) == "a1a8"
+    assert depth >= 1
+    assert score > 0
@@ -0,0 +1,174 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    chess_match::{ChessMatch, MatchResult},
+    chess_move::Move,
+    piece_base::PieceColor,
+};
+
+/// Chooses moves for the computer player. All randomness goes through a single seedable
+/// generator, so two engines created with the same seed pick the same moves from the same
+/// positions.
+pub struct Engine {
+    rng: StdRng,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine {
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn with_seed(seed: u64) -> Engine {
+        Engine {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Picks one of the side to move's legal moves at random, or `None` if there are none.
+    pub fn random_move(&mut self, chess_match: &ChessMatch) -> Option<Move> {
+        let mut moves = chess_match.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+
+        let index = self.rng.gen_range(0..moves.len());
+        Some(moves.swap_remove(index))
+    }
+
+    /// Picks a move for the side to move: a mate in one when there is one, otherwise a
+    /// random move. A side that is ahead on material never picks a move that stalemates the
+    /// opponent, unless nothing else is legal.
+    pub fn choose_move(&mut self, chess_match: &ChessMatch) -> Option<Move> {
+        let (_, color) = chess_match.get_current_turn_and_color();
+        let mate = match color {
+            PieceColor::White => MatchResult::WhiteWonCheckmate,
+            PieceColor::Black => MatchResult::BlackWonCheckmate,
+        };
+        let moves = chess_match.legal_moves();
+        if let Some(m) = moves.iter().find(|m| {
+            let mut match_copy = chess_match.copy();
+            match_copy.apply_move(m);
+            match_copy.result() == mate
+        }) {
+            return Some(m.clone());
+        }
+
+        let balance = match color {
+            PieceColor::White => chess_match.material_balance(),
+            PieceColor::Black => -chess_match.material_balance(),
+        };
+        let mut candidates = moves.clone();
+        if balance > 0 {
+            candidates.retain(|m| !chess_match.is_stalemate_trap(m));
+        }
+        if candidates.is_empty() {
+            candidates = moves;
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let index = self.rng.gen_range(0..candidates.len());
+        Some(candidates.swap_remove(index))
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+/// Something that can pick the move it thinks best for the side to move, which is all
+/// `ChessMatch::suggest_move` needs to coach with it.
+pub trait MoveChooser {
+    /// The move to play, or `None` if the side to move has no legal moves.
+    fn best_move(&mut self, chess_match: &ChessMatch) -> Option<Move>;
+}
+
+impl MoveChooser for Engine {
+    /// A mate in one when there is one, otherwise the capture winning the most material,
+    /// otherwise whatever `choose_move` picks.
+    fn best_move(&mut self, chess_match: &ChessMatch) -> Option<Move> {
+        let chosen = self.choose_move(chess_match)?;
+        if chess_match.move_mates(&chosen) {
+            return Some(chosen);
+        }
+
+        Some(
+            chess_match
+                .winning_captures()
+                .into_iter()
+                .next()
+                .unwrap_or(chosen),
+        )
+    }
+}
+
+/// A move recommended to the player, with a short explanation of what it does.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Suggestion {
+    pub chess_move: Move,
+    pub explanation: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        piece_base::{ChessPiece, PieceType},
+        piece_location::PieceLocation,
+    };
+    use uuid::Uuid;
+
+    fn play_random_game(seed: u64, plies: usize) -> Vec<String> {
+        let mut engine = Engine::with_seed(seed);
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+
+        let mut played = Vec::new();
+        for _ in 0..plies {
+            let chess_move = match engine.random_move(&chess_match) {
+                Some(m) => m,
+                None => break,
+            };
+            played.push(chess_move.to_string());
+            chess_match.apply_move(&chess_move);
+        }
+
+        played
+    }
+
+    #[test]
+    fn test_same_seed_plays_same_game() {
+        assert_eq!(play_random_game(7, 6), play_random_game(7, 6));
+    }
+
+    #[test]
+    fn test_choose_move_mates_instead_of_stalemating() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("b6"), 0),
+            ChessPiece::new(PieceType::Queen, PieceColor::White, loc("c5"), 9),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("a8"), 0),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        let queen_to = |to: &str| {
+            chess_match
+                .legal_moves()
+                .into_iter()
+                .find(|m| m.from == loc("c5") && m.to == loc(to))
+                .unwrap()
+        };
+        assert!(chess_match.is_stalemate_trap(&queen_to("c7")));
+        assert!(!chess_match.is_stalemate_trap(&queen_to("c8")));
+
+        let chosen = Engine::with_seed(1).choose_move(&chess_match).unwrap();
+        chess_match.apply_move(&chosen);
+        assert_eq!(MatchResult::WhiteWonCheckmate, chess_match.result());
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chess_match::ChessMatch,
+    chess_move::Move,
+    search::{AiPlayer, SearchConfig, MATE_SCORE},
+};
+
+/// Scores are capped at this many centipawns either way, so missing a mate counts as a big
+/// loss rather than a million centipawns.
+const SCORE_CAP: i32 = 10_000;
+
+/// A verdict on a move, written after it in PGN, e.g. `Qxf7#!` or `Nf6??`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Annotation {
+    /// The only move that didn't give much away.
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Annotation {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Annotation::Good => "!",
+            Annotation::Inaccuracy => "?!",
+            Annotation::Mistake => "?",
+            Annotation::Blunder => "??",
+        }
+    }
+
+    /// The annotation for a move that lost `centipawn_loss` against the best move, where the
+    /// next best alternative to the best move lost `runner_up_loss`.
+    fn for_loss(centipawn_loss: i32, runner_up_loss: Option<i32>) -> Option<Annotation> {
+        match centipawn_loss {
+            300.. => Some(Annotation::Blunder),
+            100.. => Some(Annotation::Mistake),
+            50.. => Some(Annotation::Inaccuracy),
+            0 if runner_up_loss.is_some_and(|loss| loss >= 100) => Some(Annotation::Good),
+            _ => None,
+        }
+    }
+}
+
+/// Looks back over a match, scoring every move against the best move in its position.
+pub struct Analyzer {
+    ai: AiPlayer,
+}
+
+impl Analyzer {
+    /// Scores moves by searching the position after each of them with `config`, including
+    /// its evaluation parameters.
+    pub fn new(config: SearchConfig) -> Analyzer {
+        Analyzer {
+            ai: AiPlayer::new(config),
+        }
+    }
+
+    /// Tags each logged move with its centipawn loss and, when it stands out, an
+    /// `Annotation`. Moves whose position can't be rebuilt, as in matches saved without their
+    /// move history, are left alone.
+    pub fn annotate(&mut self, chess_match: &mut ChessMatch) {
+        for ply in 0..chess_match.get_log_entries().len() {
+            let (position, entry) = match (
+                chess_match.position_at_move(ply),
+                chess_match.get_log_entries().get(ply).cloned(),
+            ) {
+                (Some(position), Some(entry)) => (position, entry),
+                _ => continue,
+            };
+            let mut scores: Vec<(Move, i32)> = position
+                .legal_moves()
+                .into_iter()
+                .map(|m| {
+                    let score = self.score_move(&position, &m);
+                    (m, score)
+                })
+                .collect();
+            scores.sort_by_key(|(_, score)| -score);
+
+            let played = scores.iter().find(|(m, _)| {
+                m.from == entry.get_start_location()
+                    && m.to == entry.get_end_location()
+                    && m.promotion == entry.get_promoted_to()
+            });
+            let (best, played) = match (scores.first(), played) {
+                (Some((_, best)), Some((_, played))) => (*best, *played),
+                _ => continue,
+            };
+            let runner_up_loss = scores.get(1).map(|(_, score)| best - score);
+            let loss = best - played;
+
+            if let Some(entry) = chess_match.log_entry_mut(ply) {
+                entry.annotation(Annotation::for_loss(loss, runner_up_loss), loss);
+            }
+        }
+    }
+
+    /// How good `chess_move` is for the side playing it, in centipawns.
+    fn score_move(&mut self, position: &ChessMatch, chess_move: &Move) -> i32 {
+        let mut child = position.copy();
+        child.apply_move(chess_move);
+        let score = match self.ai.search(&child) {
+            Some(result) => -result.score,
+            None if child.checking_pieces().is_empty() => 0,
+            // no reply and in check: it's mate
+            None => MATE_SCORE,
+        };
+        score.clamp(-SCORE_CAP, SCORE_CAP)
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Analyzer::new(SearchConfig {
+            max_depth: 1,
+            time_limit_ms: None,
+            ..SearchConfig::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annotates_blunder_and_punishment() {
+        // after 1. e4 e5 2. Qh5 Nc6 3. Bc4
+        let mut chess_match = ChessMatch::from_fen(
+            "r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 3 3",
+        )
+        .unwrap();
+        for san in ["Nf6", "Qxf7#"] {
+            chess_match.apply_san(san).unwrap();
+        }
+
+        Analyzer::default().annotate(&mut chess_match);
+
+        let entries = chess_match.get_log_entries();
+        assert_eq!(Some(Annotation::Blunder), entries[0].get_annotation());
+        assert!(entries[0].get_centipawn_loss().unwrap() >= 300);
+        assert_eq!(Some(Annotation::Good), entries[1].get_annotation());
+        assert_eq!(Some(0), entries[1].get_centipawn_loss());
+        assert_eq!("Nf6??", entries[0].get_annotated_notation());
+        assert!(chess_match.to_pgn().contains("3... Nf6?? 4. Qxf7#!"));
+    }
+
+    #[test]
+    fn test_annotation_thresholds() {
+        assert_eq!(None, Annotation::for_loss(0, Some(20)));
+        assert_eq!(None, Annotation::for_loss(49, None));
+        assert_eq!(Some(Annotation::Inaccuracy), Annotation::for_loss(50, None));
+        assert_eq!(Some(Annotation::Mistake), Annotation::for_loss(150, None));
+        assert_eq!(Some(Annotation::Blunder), Annotation::for_loss(300, None));
+        assert_eq!(Some(Annotation::Good), Annotation::for_loss(0, Some(100)));
+    }
+}
@@ -0,0 +1,479 @@
+use crate::{
+    piece_base::{ChessPiece, PieceColor, PieceType},
+    piece_location::PieceLocation,
+};
+
+/// Every piece type, in the order bitboards and Zobrist keys are indexed by.
+pub(crate) const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Rook,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+const STRAIGHT_STEPS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const DIAGONAL_STEPS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+const KNIGHT_ATTACKS: [u64; 64] = step_attacks(&[
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+]);
+const KING_ATTACKS: [u64; 64] = step_attacks(&[
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+]);
+const WHITE_PAWN_ATTACKS: [u64; 64] = step_attacks(&[(-1, 1), (1, 1)]);
+const BLACK_PAWN_ATTACKS: [u64; 64] = step_attacks(&[(-1, -1), (1, -1)]);
+
+/// For every square, the squares one step of `offsets` away that are still on the board.
+const fn step_attacks(offsets: &[(i32, i32)]) -> [u64; 64] {
+    let mut table = [0u64; 64];
+    let mut square = 0;
+    while square < 64 {
+        let x = (square % 8) as i32;
+        let y = (square / 8) as i32;
+        let mut i = 0;
+        while i < offsets.len() {
+            let (dx, dy) = offsets[i];
+            if x + dx >= 0 && x + dx < 8 && y + dy >= 0 && y + dy < 8 {
+                table[square] |= 1 << ((y + dy) * 8 + x + dx);
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+
+    table
+}
+
+/// What `Board::make_move` changed, so `Board::unmake_move` can put it back.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BoardUndo {
+    from: usize,
+    to: usize,
+    moved: Option<(PieceType, PieceColor)>,
+    captured: Option<(usize, PieceType, PieceColor)>,
+}
+
+/// A bitboard view of the pieces on the board, one `u64` per piece type and color with bit
+/// `PieceLocation::index` set for every square holding such a piece. Move generation and
+/// attack detection use it for occupancy instead of searching the piece list.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Board {
+    bitboards: [[u64; 6]; 2],
+}
+
+impl Board {
+    /// Builds the board from the pieces still in play, skipping captured ones.
+    pub fn from_pieces<'a>(pieces: impl IntoIterator<Item = &'a ChessPiece>) -> Board {
+        let mut board = Board::default();
+        for piece in pieces.into_iter().filter(|p| !p.is_captured()) {
+            board.put(
+                piece.get_type(),
+                piece.get_color(),
+                Board::square(&piece.location),
+            );
+        }
+
+        board
+    }
+
+    pub fn square(location: &PieceLocation) -> usize {
+        location.index()
+    }
+
+    pub fn location(square: usize) -> PieceLocation {
+        PieceLocation::new_from_x_y((square % 8) as i32, (square / 8) as i32 + 1)
+    }
+
+    /// The squares set in `bitboard`, from a1 to h8.
+    pub fn squares(mut bitboard: u64) -> impl Iterator<Item = usize> {
+        std::iter::from_fn(move || {
+            if bitboard == 0 {
+                return None;
+            }
+            let square = bitboard.trailing_zeros() as usize;
+            bitboard &= bitboard - 1;
+            Some(square)
+        })
+    }
+
+    pub fn pieces(&self, piece_type: PieceType, color: PieceColor) -> u64 {
+        self.bitboards[color_index(color)][type_index(piece_type)]
+    }
+
+    pub fn occupied_by(&self, color: PieceColor) -> u64 {
+        self.bitboards[color_index(color)]
+            .iter()
+            .fold(0, |all, bitboard| all | bitboard)
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.occupied_by(PieceColor::White) | self.occupied_by(PieceColor::Black)
+    }
+
+    pub fn is_occupied(&self, square: usize) -> bool {
+        self.occupied() & (1 << square) != 0
+    }
+
+    pub fn piece_at(&self, square: usize) -> Option<(PieceType, PieceColor)> {
+        for color in [PieceColor::White, PieceColor::Black] {
+            for piece_type in PIECE_TYPES {
+                if self.pieces(piece_type, color) & (1 << square) != 0 {
+                    return Some((piece_type, color));
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn put(&mut self, piece_type: PieceType, color: PieceColor, square: usize) {
+        self.bitboards[color_index(color)][type_index(piece_type)] |= 1 << square;
+    }
+
+    pub fn remove(&mut self, square: usize) {
+        self.bitboards
+            .iter_mut()
+            .flatten()
+            .for_each(|bitboard| *bitboard &= !(1 << square));
+    }
+
+    /// Moves the piece on `from` to `to`, removing whatever stood on `to` or on `captured`,
+    /// the victim's square for an en passant capture. Returns what `unmake_move` needs to
+    /// undo it.
+    pub fn make_move(&mut self, from: usize, to: usize, captured: Option<usize>) -> BoardUndo {
+        let captured_square = captured.unwrap_or(to);
+        let undo = BoardUndo {
+            from,
+            to,
+            moved: self.piece_at(from),
+            captured: self
+                .piece_at(captured_square)
+                .map(|(piece_type, color)| (captured_square, piece_type, color)),
+        };
+
+        self.remove(captured_square);
+        if let Some((piece_type, color)) = undo.moved {
+            self.remove(from);
+            self.put(piece_type, color, to);
+        }
+
+        undo
+    }
+
+    /// Takes back a move played with `make_move`. Moves have to be taken back in the
+    /// reverse order they were made.
+    pub fn unmake_move(&mut self, undo: BoardUndo) {
+        if let Some((piece_type, color)) = undo.moved {
+            self.remove(undo.to);
+            self.put(piece_type, color, undo.from);
+        }
+        if let Some((square, piece_type, color)) = undo.captured {
+            self.put(piece_type, color, square);
+        }
+    }
+
+    /// The board after the piece on `from` moves to `to`, leaving this one as it is.
+    pub fn after_move(&self, from: usize, to: usize, captured: Option<usize>) -> Board {
+        let mut board = *self;
+        board.make_move(from, to, captured);
+        board
+    }
+
+    /// The squares a `piece_type` of `color` on `square` attacks, stopping sliding pieces at
+    /// the first occupied square in each direction. Pawns attack diagonally forward only.
+    pub fn attacks(&self, piece_type: PieceType, color: PieceColor, square: usize) -> u64 {
+        match piece_type {
+            PieceType::Pawn => match color {
+                PieceColor::White => WHITE_PAWN_ATTACKS[square],
+                PieceColor::Black => BLACK_PAWN_ATTACKS[square],
+            },
+            PieceType::Knight => KNIGHT_ATTACKS[square],
+            PieceType::King => KING_ATTACKS[square],
+            PieceType::Rook => self.slide(square, &STRAIGHT_STEPS),
+            PieceType::Bishop => self.slide(square, &DIAGONAL_STEPS),
+            PieceType::Queen => {
+                self.slide(square, &STRAIGHT_STEPS) | self.slide(square, &DIAGONAL_STEPS)
+            }
+        }
+    }
+
+    /// Every piece of `attacking_color` that attacks `square`.
+    pub fn attackers_of(&self, square: usize, attacking_color: PieceColor) -> u64 {
+        let pieces = |piece_type| self.pieces(piece_type, attacking_color);
+        // pawns attack diagonally forward, so look diagonally backward from the target
+        let pawn_attacks = self.attacks(PieceType::Pawn, attacking_color.opposite(), square);
+        let straight = self.slide(square, &STRAIGHT_STEPS);
+        let diagonal = self.slide(square, &DIAGONAL_STEPS);
+
+        (pawn_attacks & pieces(PieceType::Pawn))
+            | (KNIGHT_ATTACKS[square] & pieces(PieceType::Knight))
+            | (KING_ATTACKS[square] & pieces(PieceType::King))
+            | (straight & (pieces(PieceType::Rook) | pieces(PieceType::Queen)))
+            | (diagonal & (pieces(PieceType::Bishop) | pieces(PieceType::Queen)))
+    }
+
+    pub fn is_attacked_by(&self, square: usize, attacking_color: PieceColor) -> bool {
+        self.attackers_of(square, attacking_color) != 0
+    }
+
+    /// The squares strictly between `from` and `to` when they share a rank, file or
+    /// diagonal, or none when they don't.
+    pub fn between(from: usize, to: usize) -> u64 {
+        let (x1, y1) = ((from % 8) as i32, (from / 8) as i32);
+        let (x2, y2) = ((to % 8) as i32, (to / 8) as i32);
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        if from == to || (dx != 0 && dy != 0 && dx.abs() != dy.abs()) {
+            return 0;
+        }
+
+        let (step_x, step_y) = (dx.signum(), dy.signum());
+        let (mut x, mut y) = (x1 + step_x, y1 + step_y);
+        let mut squares = 0;
+        while (x, y) != (x2, y2) {
+            squares |= 1 << (y * 8 + x);
+            x += step_x;
+            y += step_y;
+        }
+
+        squares
+    }
+
+    /// The pieces of `color` pinned to their king on `king_square`, each with the squares it
+    /// can still go to without exposing the king: the line from the king up to and
+    /// including the pinning piece.
+    pub fn pins(&self, king_square: usize, color: PieceColor) -> Vec<(usize, u64)> {
+        let occupied = self.occupied();
+        let own = self.occupied_by(color);
+        let opponent = color.opposite();
+        let mut pins = Vec::new();
+        for (steps, slider) in [
+            (STRAIGHT_STEPS, PieceType::Rook),
+            (DIAGONAL_STEPS, PieceType::Bishop),
+        ] {
+            let pinners = self.pieces(slider, opponent) | self.pieces(PieceType::Queen, opponent);
+            for (dx, dy) in steps {
+                let mut x = (king_square % 8) as i32 + dx;
+                let mut y = (king_square / 8) as i32 + dy;
+                let mut ray = 0;
+                let mut pinned = None;
+                while (0..8).contains(&x) && (0..8).contains(&y) {
+                    let square = (y * 8 + x) as usize;
+                    ray |= 1 << square;
+                    if occupied & (1 << square) != 0 {
+                        match pinned {
+                            None if own & (1 << square) != 0 => pinned = Some(square),
+                            Some(pinned) if pinners & (1 << square) != 0 => {
+                                pins.push((pinned, ray));
+                                break;
+                            }
+                            _ => break,
+                        }
+                    }
+                    x += dx;
+                    y += dy;
+                }
+            }
+        }
+
+        pins
+    }
+
+    fn slide(&self, square: usize, steps: &[(i32, i32)]) -> u64 {
+        let occupied = self.occupied();
+        let mut attacks = 0;
+        for (dx, dy) in steps {
+            let mut x = (square % 8) as i32 + dx;
+            let mut y = (square / 8) as i32 + dy;
+            while (0..8).contains(&x) && (0..8).contains(&y) {
+                let bit = 1 << (y * 8 + x);
+                attacks |= bit;
+                if occupied & bit != 0 {
+                    break;
+                }
+                x += dx;
+                y += dy;
+            }
+        }
+
+        attacks
+    }
+}
+
+fn color_index(color: PieceColor) -> usize {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => 1,
+    }
+}
+
+fn type_index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Rook => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(l: &str) -> usize {
+        Board::square(&PieceLocation::new_from_string(l).unwrap())
+    }
+
+    fn squares(bitboard: u64) -> Vec<String> {
+        Board::squares(bitboard)
+            .map(|s| Board::location(s).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_attacks_stop_at_blockers() {
+        let mut board = Board::default();
+        board.put(PieceType::Rook, PieceColor::White, square("a1"));
+        board.put(PieceType::Pawn, PieceColor::White, square("a3"));
+        board.put(PieceType::Knight, PieceColor::Black, square("c1"));
+
+        assert_eq!(
+            vec!["b1", "c1", "a2", "a3"],
+            squares(board.attacks(PieceType::Rook, PieceColor::White, square("a1")))
+        );
+        assert_eq!(
+            vec!["a2", "e2", "b3", "d3"],
+            squares(board.attacks(PieceType::Knight, PieceColor::Black, square("c1")))
+        );
+    }
+
+    #[test]
+    fn test_attackers_of() {
+        let mut board = Board::default();
+        board.put(PieceType::King, PieceColor::White, square("e1"));
+        board.put(PieceType::Queen, PieceColor::Black, square("e8"));
+        board.put(PieceType::Pawn, PieceColor::Black, square("f2"));
+        board.put(PieceType::Bishop, PieceColor::Black, square("a5"));
+        board.put(PieceType::Knight, PieceColor::White, square("c3"));
+
+        assert_eq!(
+            vec!["f2", "e8"],
+            squares(board.attackers_of(square("e1"), PieceColor::Black))
+        );
+        assert!(!board.is_attacked_by(square("e1"), PieceColor::White));
+
+        // moving the knight out of the way opens the bishop's diagonal
+        let moved = board.after_move(square("c3"), square("b5"), None);
+        assert_eq!(
+            vec!["f2", "a5", "e8"],
+            squares(moved.attackers_of(square("e1"), PieceColor::Black))
+        );
+        assert_eq!(
+            Some((PieceType::Knight, PieceColor::White)),
+            moved.piece_at(square("b5"))
+        );
+        assert_eq!(None, moved.piece_at(square("c3")));
+    }
+
+    #[test]
+    fn test_unmake_move_restores_board() {
+        let mut board = Board::default();
+        board.put(PieceType::Pawn, PieceColor::White, square("e5"));
+        board.put(PieceType::Pawn, PieceColor::Black, square("d5"));
+        board.put(PieceType::Rook, PieceColor::Black, square("e6"));
+        let before = board;
+
+        // en passant takes the pawn beside the capturing one
+        let undo = board.make_move(square("e5"), square("d6"), Some(square("d5")));
+        assert_eq!(None, board.piece_at(square("d5")));
+        assert_eq!(
+            Some((PieceType::Pawn, PieceColor::White)),
+            board.piece_at(square("d6"))
+        );
+        let undo_capture = board.make_move(square("e6"), square("d6"), None);
+        assert_eq!(
+            Some((PieceType::Rook, PieceColor::Black)),
+            board.piece_at(square("d6"))
+        );
+
+        board.unmake_move(undo_capture);
+        board.unmake_move(undo);
+        assert_eq!(before, board);
+    }
+
+    #[test]
+    fn test_pins() {
+        let mut board = Board::default();
+        board.put(PieceType::King, PieceColor::White, square("e1"));
+        board.put(PieceType::Knight, PieceColor::White, square("e3"));
+        board.put(PieceType::Rook, PieceColor::Black, square("e7"));
+        board.put(PieceType::Bishop, PieceColor::White, square("c3"));
+        board.put(PieceType::Queen, PieceColor::Black, square("a5"));
+        // two pieces between the king and the rook on h1 means neither is pinned
+        board.put(PieceType::Pawn, PieceColor::White, square("f1"));
+        board.put(PieceType::Pawn, PieceColor::White, square("g1"));
+        board.put(PieceType::Rook, PieceColor::Black, square("h1"));
+
+        let pins: Vec<(String, Vec<String>)> = board
+            .pins(square("e1"), PieceColor::White)
+            .into_iter()
+            .map(|(pinned, ray)| (Board::location(pinned).to_string(), squares(ray)))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "e3".to_string(),
+                    squares(
+                        (1 << square("e2"))
+                            | (1 << square("e3"))
+                            | (1 << square("e4"))
+                            | (1 << square("e5"))
+                            | (1 << square("e6"))
+                            | (1 << square("e7"))
+                    )
+                ),
+                (
+                    "c3".to_string(),
+                    squares(
+                        (1 << square("d2"))
+                            | (1 << square("c3"))
+                            | (1 << square("b4"))
+                            | (1 << square("a5"))
+                    )
+                ),
+            ],
+            pins
+        );
+    }
+
+    #[test]
+    fn test_between() {
+        assert_eq!(
+            squares((1 << square("c3")) | (1 << square("d4"))),
+            squares(Board::between(square("b2"), square("e5")))
+        );
+        assert_eq!(
+            squares((1 << square("e2")) | (1 << square("e3"))),
+            squares(Board::between(square("e4"), square("e1")))
+        );
+        assert_eq!(0, Board::between(square("e1"), square("e2")));
+        assert_eq!(0, Board::between(square("g1"), square("f3")));
+    }
+}
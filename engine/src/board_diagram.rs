@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{match_snapshot::SnapshotPiece, piece_location::PieceLocation};
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum ArrowKind {
+    LastMove,
+    Attack,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DiagramArrow {
+    pub from: PieceLocation,
+    pub to: PieceLocation,
+    pub kind: ArrowKind,
+}
+
+/// A renderer-agnostic picture of the position, meant to be turned into an SVG or image by
+/// whatever draws it.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BoardDiagram {
+    pub match_id: Uuid,
+    /// Ranks from 8 down to 1, each holding files a to h.
+    pub squares: Vec<Vec<Option<SnapshotPiece>>>,
+    /// The squares of the last move, plus the king's square when it is in check.
+    pub highlights: Vec<PieceLocation>,
+    /// The last move, followed by every attack on a piece of the side to move.
+    pub arrows: Vec<DiagramArrow>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chess_match::ChessMatch, piece_base::PieceType};
+
+    #[test]
+    fn test_diagram_after_check() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match
+            .set_position_uci("startpos moves e2e4 f7f6 d1h5")
+            .unwrap();
+
+        let diagram = chess_match.to_diagram();
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+
+        assert_eq!(
+            DiagramArrow {
+                from: loc("d1"),
+                to: loc("h5"),
+                kind: ArrowKind::LastMove
+            },
+            diagram.arrows[0]
+        );
+        assert!(diagram.arrows.contains(&DiagramArrow {
+            from: loc("h5"),
+            to: loc("e8"),
+            kind: ArrowKind::Attack
+        }));
+        assert_eq!(vec![loc("d1"), loc("h5"), loc("e8")], diagram.highlights);
+        // h5 is on the fourth row from the top
+        let h5 = diagram.squares[3][7].as_ref().unwrap();
+        assert_eq!(PieceType::Queen, h5.piece_type);
+        assert!(serde_json::to_string(&diagram).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cell::Cell, collections::HashMap};
+use std::collections::HashMap;
 
 use chrono::{DateTime, Utc};
 use log::{debug, info};
@@ -7,19 +7,34 @@ use strum::IntoEnumIterator;
 use uuid::Uuid;
 
 use crate::{
-    move_resolver::MoveResolver,
+    ai::{MoveChooser, Suggestion},
+    board::Board,
+    board_diagram::{ArrowKind, BoardDiagram, DiagramArrow},
+    chess_move::{Move, MoveError},
+    clock::{MatchClock, TimeControl},
+    eco,
+    evaluation::Evaluation,
+    fen::{self, FenError},
+    match_helpers::MatchHelpers,
+    match_snapshot::{MatchSnapshot, SnapshotPiece},
+    move_resolver::{MoveResolver, SimulateType},
     movement_log::{MovementLogEntry, MovementLogger},
+    opening_book::OpeningBook,
     piece_base::{ChessPiece, PieceColor, PieceType},
     piece_location::{PieceLocation, FILES},
+    position_builder::PositionBuilder,
+    save_format::{self, SaveError},
+    search::{AiPlayer, BestLine, SearchConfig, SearchLimit, MATE_SCORE},
+    zobrist,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CastleSide {
     KingSide,
     QueenSide,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct KingCastleData {
     pub king_id: Uuid,
     pub king_target_location: PieceLocation,
@@ -36,23 +51,270 @@ pub enum KingState {
     NotInCheckMate,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum DrawReason {
+    Stalemate,
+    MoveLimit,
+    FiftyMoveRule,
+    Agreement,
+    ThreefoldRepetition,
+    /// Neither side has the pieces left to checkmate.
+    InsufficientMaterial,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum MatchResult {
+    InProgress,
+    WhiteWonCheckmate,
+    BlackWonCheckmate,
+    WhiteWonResignation,
+    BlackWonResignation,
+    WhiteWonTimeout,
+    BlackWonTimeout,
+    Draw {
+        reason: DrawReason,
+    },
+    /// Ended without a result, e.g. abandoned before it got going.
+    Aborted,
+}
+
+/// Whether a match is still being played.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Default)]
+pub enum MatchStatus {
+    #[default]
+    InProgress,
+    Completed,
+}
+
+impl MatchResult {
+    /// The result as written at the end of a PGN game.
+    pub fn pgn_marker(&self) -> &'static str {
+        match self.winner() {
+            Some(PieceColor::White) => "1-0",
+            Some(PieceColor::Black) => "0-1",
+            None if matches!(self, MatchResult::Draw { .. }) => "1/2-1/2",
+            None => "*",
+        }
+    }
+
+    /// Whether the result was recorded, like a resignation or an agreed draw, rather than
+    /// read off the board like a checkmate. Taking back a move can't change a recorded
+    /// result.
+    pub fn is_recorded(&self) -> bool {
+        !matches!(
+            self,
+            MatchResult::InProgress
+                | MatchResult::WhiteWonCheckmate
+                | MatchResult::BlackWonCheckmate
+                | MatchResult::Draw {
+                    reason: DrawReason::Stalemate
+                        | DrawReason::FiftyMoveRule
+                        | DrawReason::InsufficientMaterial
+                }
+        )
+    }
+
+    /// The color that won the match, if it was decided.
+    pub fn winner(&self) -> Option<PieceColor> {
+        match self {
+            MatchResult::WhiteWonCheckmate
+            | MatchResult::WhiteWonResignation
+            | MatchResult::WhiteWonTimeout => Some(PieceColor::White),
+            MatchResult::BlackWonCheckmate
+            | MatchResult::BlackWonResignation
+            | MatchResult::BlackWonTimeout => Some(PieceColor::Black),
+            MatchResult::InProgress | MatchResult::Draw { .. } | MatchResult::Aborted => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+/// What happens when a pawn reaches the last rank without the caller naming a piece.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Default)]
+pub enum PromotionPolicy {
+    /// Promote to a queen without asking.
+    #[default]
+    AlwaysQueen,
+    /// Refuse the move with `MoveError::PromotionRequired` until the caller picks a piece
+    /// through `move_piece_with_promotion`.
+    Prompt,
+}
+
+/// How a legal move gets the side to move out of check.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum EvasionKind {
+    Block,
+    CaptureChecker,
+    KingMove,
+}
+
+/// A pawn move to the last rank that is waiting for the player to pick the piece it
+/// promotes to, see `ChessMatch::promote_pawn`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PendingPromotion {
+    pub piece_id: Uuid,
+    pub from: PieceLocation,
+    pub to: PieceLocation,
+}
+
+/// Which pawns stand on a rook's file.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum FileStatus {
+    /// No pawns at all.
+    Open,
+    /// Only the opponent's pawns.
+    SemiOpen,
+    /// At least one of the rook's own pawns.
+    Closed,
+}
+
+/// Everything needed to take a move back: the state of every piece the move touched (the
+/// mover, a captured piece, a castling rook) and the en passant target before the move.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MoveRecord {
+    pieces_before: Vec<ChessPiece>,
+    en_passant_target: Option<PieceLocation>,
+    /// The zobrist key of the position before the move, for spotting repetitions.
+    #[serde(default)]
+    key_before: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ChessMatch {
     id: Uuid,
     white_player: Uuid,
     black_player: Uuid,
-    status: u32,
-    result: u32,
+    status: MatchStatus,
+    /// Set once the match is decided; `result()` works the outcome out from the board
+    /// while this is still `InProgress`.
+    result: MatchResult,
     winner: Option<Uuid>,
     started: Option<DateTime<Utc>>,
     completed: Option<DateTime<Utc>>,
-    current_turn: Cell<u32>,
+    current_turn: PieceColor,
     pub pieces: Vec<ChessPiece>,
     white_king_state: KingState,
     black_king_state: KingState,
     pub white_king_castle: Vec<KingCastleData>,
     pub black_king_castle: Vec<KingCastleData>,
     movement_log: Vec<MovementLogEntry>,
+    #[serde(default)]
+    en_passant_target: Option<PieceLocation>,
+    #[serde(default)]
+    move_history: Vec<MoveRecord>,
+    #[serde(default)]
+    pending_takeback: Option<PieceColor>,
+    #[serde(default)]
+    pending_draw_offer: Option<PieceColor>,
+    /// Moves taken back with `undo_move`, the most recently undone last.
+    #[serde(default)]
+    redo_stack: Vec<MovementLogEntry>,
+    #[serde(default)]
+    clock: Option<MatchClock>,
+    #[serde(default)]
+    promotion_policy: PromotionPolicy,
+    #[serde(default)]
+    pending_promotion: Option<PendingPromotion>,
+    /// Plies played before the first logged move, for matches set up from a FEN.
+    #[serde(default)]
+    start_ply: u32,
+    /// The fifty-move rule count the match was set up with.
+    #[serde(default)]
+    start_halfmove_clock: u32,
+    /// The position the match was set up from, when it didn't start from the usual one.
+    #[serde(default)]
+    start_fen: Option<String>,
+}
+
+/// The moves and captures calculated for `piece`, before checking whether they leave its
+/// own king in check. A pawn move to the last rank is listed once for each promotion piece.
+fn pseudo_legal_moves_for_piece(piece: &ChessPiece) -> impl Iterator<Item = Move> + '_ {
+    let moves = piece.get_valid_moves().into_iter().map(|l| (l, false));
+    let captures = piece.get_valid_captures().into_iter().map(|l| (l, true));
+    moves_to_targets(piece, moves.chain(captures))
+}
+
+/// Like `pseudo_legal_moves_for_piece`, but only the captures and promotions.
+fn pseudo_legal_captures_for_piece(piece: &ChessPiece) -> impl Iterator<Item = Move> + '_ {
+    let last_rank = last_rank(piece.get_color());
+    let promotions = piece
+        .get_valid_moves()
+        .into_iter()
+        .filter(move |l| piece.get_type() == PieceType::Pawn && l.get_rank() == last_rank)
+        .map(|l| (l, false));
+    let captures = piece.get_valid_captures().into_iter().map(|l| (l, true));
+    moves_to_targets(piece, promotions.chain(captures))
+}
+
+fn last_rank(color: PieceColor) -> u32 {
+    match color {
+        PieceColor::White => 8,
+        PieceColor::Black => 1,
+    }
+}
+
+/// Turns a piece's target squares into moves, marking castles and giving a pawn reaching the
+/// last rank one move per piece it can promote to.
+fn moves_to_targets<'a>(
+    piece: &'a ChessPiece,
+    targets: impl Iterator<Item = (PieceLocation, bool)> + 'a,
+) -> impl Iterator<Item = Move> + 'a {
+    let last_rank = last_rank(piece.get_color());
+    targets.flat_map(move |(location, capture)| {
+        let mut chess_move = Move::new(piece.id, piece.location.clone(), location, capture);
+        if piece.get_type() == PieceType::King
+            && piece.location.chebyshev_distance(&chess_move.to) == 2
+        {
+            chess_move.castle = Some(if chess_move.to.get_file() == "g" {
+                CastleSide::KingSide
+            } else {
+                CastleSide::QueenSide
+            });
+        }
+
+        if piece.get_type() != PieceType::Pawn || chess_move.to.get_rank() != last_rank {
+            return vec![chess_move];
+        }
+        [
+            PieceType::Queen,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Knight,
+        ]
+        .into_iter()
+        .map(|piece_type| Move {
+            promotion: Some(piece_type),
+            ..chess_move.clone()
+        })
+        .collect()
+    })
+}
+
+/// The piece a promotion letter names, in either case.
+fn promotion_piece(c: char) -> Option<PieceType> {
+    match c.to_ascii_uppercase() {
+        'Q' => Some(PieceType::Queen),
+        'R' => Some(PieceType::Rook),
+        'B' => Some(PieceType::Bishop),
+        'N' => Some(PieceType::Knight),
+        _ => None,
+    }
+}
+
+/// Joins phrases into a list for a sentence: "a", "a and b", "a, b and c".
+fn join_phrases(phrases: &[String]) -> String {
+    match phrases.split_last() {
+        None => String::new(),
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+    }
 }
 
 impl ChessMatch {
@@ -63,18 +325,29 @@ impl ChessMatch {
             id: Uuid::new_v4(),
             white_player,
             black_player,
-            status: 0,
-            result: 0,
+            status: MatchStatus::InProgress,
+            result: MatchResult::InProgress,
             winner: None,
             started: None,
             completed: None,
-            current_turn: Cell::new(0),
+            current_turn: PieceColor::White,
             pieces,
             white_king_state: KingState::NotInCheck,
             black_king_state: KingState::NotInCheck,
             white_king_castle: Vec::new(),
             black_king_castle: Vec::new(),
             movement_log: Vec::new(),
+            en_passant_target: None,
+            move_history: Vec::new(),
+            pending_takeback: None,
+            pending_draw_offer: None,
+            redo_stack: Vec::new(),
+            clock: None,
+            promotion_policy: PromotionPolicy::AlwaysQueen,
+            pending_promotion: None,
+            start_ply: 0,
+            start_halfmove_clock: 0,
+            start_fen: None,
         }
     }
 
@@ -88,18 +361,67 @@ impl ChessMatch {
             winner: self.winner,
             started: self.started,
             completed: self.completed,
-            current_turn: self.current_turn.clone(),
+            current_turn: self.current_turn,
             pieces: self.pieces.clone(),
             white_king_state: self.white_king_state.clone(),
             black_king_state: self.black_king_state.clone(),
             white_king_castle: self.white_king_castle.clone(),
             black_king_castle: self.black_king_castle.clone(),
             movement_log: self.movement_log.clone(),
+            en_passant_target: self.en_passant_target.clone(),
+            move_history: self.move_history.clone(),
+            pending_takeback: self.pending_takeback,
+            pending_draw_offer: self.pending_draw_offer,
+            redo_stack: self.redo_stack.clone(),
+            clock: self.clock.clone(),
+            promotion_policy: self.promotion_policy,
+            pending_promotion: self.pending_promotion.clone(),
+            start_ply: self.start_ply,
+            start_halfmove_clock: self.start_halfmove_clock,
+            start_fen: self.start_fen.clone(),
         }
     }
 
+    /// Starts a fresh match between the same two players with their colors swapped.
+    pub fn rematch(&self) -> ChessMatch {
+        let mut rematch = ChessMatch::new(self.black_player, self.white_player);
+        rematch.clock = self
+            .clock
+            .as_ref()
+            .map(|clock| MatchClock::new(clock.get_time_control()));
+        rematch
+    }
+
+    /// Like `from_json`, but panics if the save can't be read.
     pub fn new_from_json(data: String) -> ChessMatch {
-        serde_json::from_str(data.as_str()).expect("Error reading JSON match data")
+        ChessMatch::from_json(&data).expect("Error reading JSON match data")
+    }
+
+    /// Loads a match saved with `get_json_string`, upgrading saves from older versions.
+    pub fn from_json(json: &str) -> Result<ChessMatch, SaveError> {
+        save_format::from_json(json)
+    }
+
+    /// The match in the compact binary save format, see `save_format::to_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        save_format::to_bytes(self)
+    }
+
+    /// Loads a match saved with `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ChessMatch, SaveError> {
+        save_format::from_bytes(bytes)
+    }
+
+    /// The FEN the match was set up from, or `None` for the standard starting position.
+    pub(crate) fn get_start_fen(&self) -> Option<&str> {
+        self.start_fen.as_deref()
+    }
+
+    /// Gives a match set up from scratch the ids of the match it is restoring.
+    pub(crate) fn set_ids(&mut self, match_id: Uuid, white_player: Uuid, black_player: Uuid) {
+        self.id = match_id;
+        self.white_player = white_player;
+        self.black_player = black_player;
     }
 
     pub fn get_match_id(&self) -> Uuid {
@@ -114,19 +436,18 @@ impl ChessMatch {
         self.black_player
     }
 
+    /// The match as JSON, tagged with the save format version.
     pub fn get_json_string(&self) -> String {
-        serde_json::to_string(self).expect("Error generating JSON output")
+        save_format::to_json(self)
     }
 
     pub fn get_current_turn_and_color(&self) -> (u32, PieceColor) {
-        (
-            self.current_turn.get(),
-            if self.current_turn.get() == 0 {
-                PieceColor::White
-            } else {
-                PieceColor::Black
-            },
-        )
+        let turn = match self.current_turn {
+            PieceColor::White => 0,
+            PieceColor::Black => 1,
+        };
+
+        (turn, self.current_turn)
     }
 
     pub fn get_white_king_castle_data(&mut self) -> Vec<KingCastleData> {
@@ -137,6 +458,155 @@ impl ChessMatch {
         self.black_king_castle.clone()
     }
 
+    /// The square a pawn would move to when capturing en passant, i.e. the square skipped
+    /// over by the pawn that just advanced two ranks. Only set for the move directly after.
+    pub fn get_en_passant_target(&self) -> Option<PieceLocation> {
+        self.en_passant_target.clone()
+    }
+
+    /// The pawn that would be removed by an en passant capture this move. This is not the
+    /// piece on the capture destination (`get_en_passant_target`), which is always empty,
+    /// but the pawn that just advanced two ranks and sits directly behind that square.
+    pub fn en_passant_victim(&self) -> Option<ChessPiece> {
+        let target = self.en_passant_target.as_ref()?;
+        let rank_offset = if target.get_rank() == 3 { 1 } else { -1 };
+        let victim_location = target.offset(0, rank_offset)?;
+
+        self.get_piece_at_location(victim_location)
+            .filter(|p| p.get_type() == PieceType::Pawn)
+    }
+
+    /// Determines whether `piece` moving to `location` is an en passant capture.
+    pub fn is_en_passant_capture(&self, piece: &ChessPiece, location: &PieceLocation) -> bool {
+        piece.get_type() == PieceType::Pawn
+            && self.en_passant_target.as_ref() == Some(location)
+            && self.get_piece_at_location(location.clone()).is_none()
+    }
+
+    /// Whether each side still has the right to castle, i.e. neither the king nor the rook
+    /// on that side has moved. This says nothing about whether castling is playable this
+    /// move, which also depends on the squares in between.
+    pub fn castling_rights(&self) -> CastlingRights {
+        let has_right = |color: PieceColor, rook_file: &str| {
+            let king_unmoved = self
+                .get_player_pieces_by_type(&color, &PieceType::King)
+                .iter()
+                .any(|k| k.is_first_move());
+            let rook_unmoved = self
+                .get_player_pieces_by_type(&color, &PieceType::Rook)
+                .iter()
+                .any(|r| r.is_first_move() && r.location.get_file() == rook_file);
+            king_unmoved && rook_unmoved
+        };
+
+        CastlingRights {
+            white_king_side: has_right(PieceColor::White, "h"),
+            white_queen_side: has_right(PieceColor::White, "a"),
+            black_king_side: has_right(PieceColor::Black, "h"),
+            black_queen_side: has_right(PieceColor::Black, "a"),
+        }
+    }
+
+    /// Whether `color` could castle on `side` right now, as opposed to merely still having
+    /// the right to: the squares must be empty, and the king must not be in check or pass
+    /// through or land on an attacked square. Relies on the castle data from the last
+    /// `calculate_valid_moves`.
+    pub fn can_castle_now(&self, color: PieceColor, side: CastleSide) -> bool {
+        let castle_data = match color {
+            PieceColor::White => &self.white_king_castle,
+            PieceColor::Black => &self.black_king_castle,
+        };
+        let resolver = MoveResolver {};
+        castle_data.iter().filter(|c| c.side == side).any(|c| {
+            let king = self.get_piece_by_id_copy(&c.king_id);
+            king.get_valid_moves().contains(&c.king_target_location)
+                && resolver.is_move_legal(
+                    self,
+                    &Move::new(
+                        king.id,
+                        king.location.clone(),
+                        c.king_target_location.clone(),
+                        false,
+                    ),
+                )
+        })
+    }
+
+    /// Material difference in piece points, positive when white is ahead.
+    pub fn material_balance(&self) -> i32 {
+        self.get_pieces_in_play()
+            .iter()
+            .map(|p| match p.get_color() {
+                PieceColor::White => p.points() as i32,
+                PieceColor::Black => -(p.points() as i32),
+            })
+            .sum()
+    }
+
+    /// The pieces of `color` that have been captured, in the order they were taken. Only
+    /// moves that can still be undone are looked at, as captured pieces are kept nowhere else.
+    pub fn captured_pieces(&self, color: PieceColor) -> Vec<ChessPiece> {
+        let mut captured: Vec<ChessPiece> = self
+            .movement_log
+            .iter()
+            .rev()
+            .zip(self.move_history.iter().rev())
+            .filter_map(|(entry, record)| {
+                let captured_id = entry.get_captured_piece_id()?;
+                record.pieces_before.iter().find(|p| p.id == captured_id)
+            })
+            .filter(|p| p.get_color() == color)
+            .cloned()
+            .collect();
+        captured.reverse();
+
+        captured
+    }
+
+    /// Classifies the file a rook stands on by the pawns on it.
+    pub fn rook_file_status(&self, rook_id: &Uuid) -> FileStatus {
+        let rook = self.get_piece_by_id_copy(rook_id);
+        let file = rook.location.get_file();
+        let pawns_on_file: Vec<ChessPiece> = self
+            .get_pieces_in_play()
+            .into_iter()
+            .filter(|p| p.get_type() == PieceType::Pawn && p.location.get_file() == file)
+            .collect();
+
+        if pawns_on_file.is_empty() {
+            FileStatus::Open
+        } else if pawns_on_file
+            .iter()
+            .all(|p| p.get_color() != rook.get_color())
+        {
+            FileStatus::SemiOpen
+        } else {
+            FileStatus::Closed
+        }
+    }
+
+    /// Counts the pieces `color` has in play, grouped by type.
+    pub fn material_breakdown(&self, color: PieceColor) -> HashMap<PieceType, usize> {
+        let mut breakdown: HashMap<PieceType, usize> = HashMap::new();
+        for piece in self
+            .get_pieces_in_play()
+            .iter()
+            .filter(|p| p.get_color() == color)
+        {
+            *breakdown.entry(piece.get_type()).or_insert(0) += 1;
+        }
+
+        breakdown
+    }
+
+    /// Whether `color` has at least two bishops. A bishop gained by promotion counts even
+    /// when it runs on the same square color as the other one.
+    pub fn has_bishop_pair(&self, color: PieceColor) -> bool {
+        self.material_breakdown(color)
+            .get(&PieceType::Bishop)
+            .is_some_and(|count| *count >= 2)
+    }
+
     pub fn get_white_king_state(&self) -> KingState {
         self.white_king_state
     }
@@ -166,12 +636,17 @@ impl ChessMatch {
 
     pub fn get_pieces_in_play(&self) -> Vec<ChessPiece> {
         self.pieces
-            .clone()
-            .into_iter()
+            .iter()
             .filter(|p| !p.is_captured())
+            .cloned()
             .collect()
     }
 
+    /// A bitboard view of the pieces in play.
+    pub fn get_board(&self) -> Board {
+        Board::from_pieces(&self.pieces)
+    }
+
     pub fn get_pieces_in_play_mut(&mut self) -> Vec<&mut ChessPiece> {
         self.pieces
             .iter_mut()
@@ -180,10 +655,10 @@ impl ChessMatch {
     }
 
     pub fn get_player_pieces_in_play(&self, player: &PieceColor) -> Vec<ChessPiece> {
-        let pieces_in_play = self.get_pieces_in_play();
-        pieces_in_play
-            .into_iter()
-            .filter(|p| p.color == *player)
+        self.pieces
+            .iter()
+            .filter(|p| !p.is_captured() && p.color == *player)
+            .cloned()
             .collect()
     }
 
@@ -212,21 +687,20 @@ impl ChessMatch {
     }
 
     pub fn get_piece_at_location(&self, location: PieceLocation) -> Option<ChessPiece> {
-        let pieces = self.get_pieces_in_play();
-        let piece_at_location: Vec<&ChessPiece> =
-            pieces.iter().filter(|p| p.location == location).collect();
-        if piece_at_location.is_empty() {
-            None
-        } else {
-            Some(piece_at_location[0].clone())
-        }
+        self.pieces
+            .iter()
+            .find(|p| p.location == location && !p.is_captured())
+            .cloned()
     }
 
     pub fn get_piece_at_location_mut(
         &mut self,
         location: PieceLocation,
     ) -> Option<&mut ChessPiece> {
-        let piece = self.pieces.iter_mut().find(|p| p.location == location);
+        let piece = self
+            .pieces
+            .iter_mut()
+            .find(|p| p.location == location && !p.is_captured());
         if piece.is_some() {
             Some(piece.unwrap())
         } else {
@@ -261,9 +735,22 @@ impl ChessMatch {
     }
 
     pub fn calculate_valid_moves(&mut self) {
-        let resolver = MoveResolver {};
+        MoveResolver {}.calculate_valid_moves(self);
+        self.update_king_states();
+    }
 
-        resolver.calculate_valid_moves(self);
+    /// Recalculates the valid moves after a move like `calculate_valid_moves`, but only for
+    /// the pieces the move can have affected: those in `piece_ids` and those reaching one of
+    /// the `changed_squares`. See `MoveResolver::recalculate_pieces`.
+    fn recalculate_valid_moves(&mut self, piece_ids: &[Uuid], changed_squares: &[PieceLocation]) {
+        MoveResolver {}.recalculate_pieces(self, piece_ids, changed_squares);
+        self.update_king_states();
+    }
+
+    /// Works out whether each king is in check, stalemated or mated, limiting the moves of a
+    /// side in check to those that get it out of check.
+    fn update_king_states(&mut self) {
+        let resolver = MoveResolver {};
         let kings = self.get_kings();
         for king in kings {
             let color = king.get_color();
@@ -294,249 +781,4150 @@ impl ChessMatch {
         }
     }
 
-    pub fn get_kings(&self) -> Vec<ChessPiece> {
-        let kings = self
-            .get_pieces_in_play()
-            .into_iter()
-            .filter(|p| p.get_type() == PieceType::King)
-            .collect();
-        kings
-    }
+    /// Checks whether the side to move has been checkmated or stalemated, based on the king
+    /// states from the last `calculate_valid_moves`.
+    pub fn is_game_over(&self) -> bool {
+        if self.status == MatchStatus::Completed {
+            return true;
+        }
 
-    pub fn get_piece_by_id(&mut self, piece_id: &Uuid) -> &mut ChessPiece {
-        let piece = self.pieces.iter_mut().find(|p| p.id == *piece_id).unwrap();
-        piece
-    }
+        let (_, color) = self.get_current_turn_and_color();
+        let king_state = match color {
+            PieceColor::White => self.get_white_king_state(),
+            PieceColor::Black => self.get_black_king_state(),
+        };
 
-    pub fn get_piece_by_id_copy(&self, piece_id: &Uuid) -> ChessPiece {
-        let piece = self
-            .pieces
-            .iter()
-            .find(|p| p.id == *piece_id)
-            .expect(format!("Could not find piece with id: {}", piece_id).as_str());
-        piece.to_owned()
+        king_state == KingState::InCheckMate
+            || king_state == KingState::InStaleMate
+            || self.is_fifty_move_draw()
+            || self.has_insufficient_material()
     }
 
-    pub fn get_pieces_by_type(&self, piece_type: PieceType) -> Vec<ChessPiece> {
-        self.pieces
-            .clone()
-            .into_iter()
-            .filter(|p| p.get_type() == piece_type)
-            .collect()
+    /// Whether fifty moves by each side have gone by without a capture or a pawn move.
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock() >= 100
     }
 
-    pub fn handle_king_castle(
-        &mut self,
-        piece_id: &Uuid,
-        target_location: &PieceLocation,
-        movement_entry: &mut MovementLogEntry,
-    ) {
-        let piece = self.get_piece_by_id(piece_id);
-        let color = piece.get_color();
-
-        match color {
-            PieceColor::White => {
-                if self.has_king_castle_data(color) {
-                    for wkc in self.get_white_king_castle_data() {
-                        debug!("found king castle data: {:?}", wkc);
-                        if wkc.king_target_location == *target_location {
-                            // piece still moves to target location,
-                            // we just also move the rook to its target location
-                            let rook = self.get_piece_by_id(&wkc.rook_id);
-                            rook.set_moved(wkc.rook_target_location);
-                            match wkc.side {
-                                CastleSide::KingSide => movement_entry.castled_king_side(),
-                                CastleSide::QueenSide => movement_entry.castled_queen_side(),
-                            };
-                        }
-                    }
-                }
-            }
-            PieceColor::Black => {
-                if self.has_king_castle_data(color) {
-                    for bkc in self.get_black_king_castle_data() {
-                        if bkc.king_target_location == *target_location {
-                            let rook = self.get_piece_by_id(&bkc.rook_id);
-                            rook.set_moved(bkc.rook_target_location);
-                            match bkc.side {
-                                CastleSide::KingSide => movement_entry.castled_king_side(),
-                                CastleSide::QueenSide => movement_entry.castled_queen_side(),
-                            };
-                        }
-                    }
-                }
+    /// Checks that the pieces make up a position the engine can play from: one king per
+    /// side, and the two kings never next to each other.
+    pub fn validate_position(&self) -> Result<(), MoveError> {
+        let kings = self.get_kings();
+        for color in PieceColor::iter() {
+            let count = kings.iter().filter(|k| k.get_color() == color).count();
+            if count != 1 {
+                return Err(MoveError::InvalidPosition(format!(
+                    "{:?} has {} kings",
+                    color, count
+                )));
             }
         }
-    }
-
-    pub fn move_piece(&mut self, piece_id: &Uuid, location: &PieceLocation) {
-        debug!("move_piece called with {:?} at {:?}", piece_id, location);
-        let piece = self.get_piece_by_id_copy(piece_id);
-        debug!("valid moves: {:?}", piece.get_valid_moves());
 
-        let player_id = if piece.get_color() == PieceColor::White {
-            self.get_white_player_id()
-        } else {
-            self.get_black_player_id()
-        };
-        let mut movement_entry = MovementLogEntry::new(
-            player_id,
-            piece_id.clone(),
-            piece.location.clone(),
-            location.clone(),
-        );
-        let can_move = piece.get_valid_moves().contains(location);
-        let can_capture = piece.get_valid_captures().contains(location);
-        let is_king = piece.get_type() == PieceType::King;
-        if can_capture {
-            self.handle_capture(location.clone(), &mut movement_entry);
+        let (x1, y1) = kings[0].location.get_x_y();
+        let (x2, y2) = kings[1].location.get_x_y();
+        if (x1 - x2).abs() <= 1.0 && (y1 - y2).abs() <= 1.0 {
+            return Err(MoveError::InvalidPosition(format!(
+                "kings are adjacent on {} and {}",
+                kings[0].location, kings[1].location
+            )));
         }
 
-        if can_move || can_capture {
-            self.handle_move(&piece.id, location.clone());
-        }
+        Ok(())
+    }
 
-        if is_king {
-            self.handle_king_castle(piece_id, &location.clone(), &mut movement_entry);
-        }
+    /// Returns every legal move for the side to move. Relies on the valid moves and captures
+    /// populated by `calculate_valid_moves`, dropping any that would leave the mover's king
+    /// under attack.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let (_, color) = self.get_current_turn_and_color();
+        self.legal_moves_for_color(&color)
+    }
 
-        self.change_turn();
-        self.calculate_valid_moves();
+    /// Same as `legal_moves`, but ordered by origin square and then destination square so
+    /// the output doesn't depend on the order the pieces are stored in.
+    pub fn legal_moves_sorted(&self) -> Vec<Move> {
+        let mut moves = self.legal_moves();
+        moves.sort_by_key(|m| (m.from.index(), m.to.index()));
+        moves
+    }
 
-        if (piece.get_color() == PieceColor::Black
-            && self.get_white_king_state() == KingState::InCheck)
-            || (piece.get_color() == PieceColor::White
-                && self.get_black_king_state() == KingState::InCheck)
-        {
-            movement_entry.opponent_king_in_check();
-        }
+    /// Same as `legal_moves`, but for either side regardless of whose turn it is.
+    pub fn legal_moves_for_color(&self, color: &PieceColor) -> Vec<Move> {
+        self.legal_moves_for_color_iter(color).collect()
+    }
 
-        let final_entry = MovementLogger::add_entry_to_match(self, movement_entry);
-        info!("Entry logged: {}", final_entry);
+    /// Yields the legal moves of the side to move one at a time, only checking each move's
+    /// legality when it is asked for, so callers that stop early skip the rest of the work.
+    pub fn legal_moves_iter(&self) -> impl Iterator<Item = Move> + '_ {
+        let (_, color) = self.get_current_turn_and_color();
+        self.legal_moves_for_color_iter(&color)
     }
 
-    fn handle_capture(&mut self, location: PieceLocation, movement_entry: &mut MovementLogEntry) {
-        let piece = self.get_piece_at_location_mut(location).unwrap();
-        piece.set_captured();
-        movement_entry.captured(piece.id.clone());
+    /// Checks whether the side to move has at least one legal move.
+    pub fn has_legal_move(&self) -> bool {
+        self.legal_moves_iter().next().is_some()
     }
 
-    fn handle_move(&mut self, piece_id: &Uuid, location: PieceLocation) {
-        let piece = self.get_piece_by_id(piece_id);
-        piece.set_moved(location);
+    /// Lists the pseudo-legal moves of the side to move next to the legal ones, both sorted
+    /// like `legal_moves_sorted`, so a move generation bug at a single node can be found by
+    /// diffing them against a reference engine.
+    pub fn debug_moves(&self) -> (Vec<Move>, Vec<Move>) {
+        let (_, color) = self.get_current_turn_and_color();
+        let mut pseudo: Vec<Move> = self.pseudo_legal_moves_for_color_iter(&color).collect();
+        pseudo.sort_by_key(|m| (m.from.index(), m.to.index()));
+
+        (pseudo, self.legal_moves_sorted())
     }
 
-    pub fn change_turn(&mut self) -> u32 {
-        if self.current_turn.get() == 0 {
-            self.current_turn.set(1);
-        } else {
-            self.current_turn.set(0);
+    /// Counts the leaf nodes of the legal move tree `depth` plies deep, counting each piece a
+    /// pawn can promote to as a separate move. Comparing the counts against published perft
+    /// results is the standard way to check move generation.
+    pub fn perft(&self, depth: u32) -> u64 {
+        match depth {
+            0 => 1,
+            // the last ply only needs counting, not playing
+            1 => self.legal_moves().len() as u64,
+            _ => self
+                .perft_children()
+                .iter()
+                .map(|(_, child)| child.perft(depth - 1))
+                .sum(),
         }
+    }
 
-        debug!("changed turn to: {:?}", self.current_turn);
+    /// Splits `perft` by root move, giving each move in UCI notation with the number of leaf
+    /// nodes below it, sorted by move. Diffing this against a reference engine's divide
+    /// output narrows a wrong count down to the move that causes it.
+    pub fn perft_divide(&self, depth: u32) -> Vec<(String, u64)> {
+        let mut divide: Vec<(String, u64)> = self
+            .perft_children()
+            .iter()
+            .map(|(uci_move, child)| (uci_move.clone(), child.perft(depth.saturating_sub(1))))
+            .collect();
+        divide.sort();
+        divide
+    }
 
-        self.current_turn.get()
+    /// Every position one legal move away, along with the move in UCI notation.
+    fn perft_children(&self) -> Vec<(String, ChessMatch)> {
+        self.legal_moves()
+            .into_iter()
+            .map(|m| {
+                let mut child = self.copy();
+                child.apply_move(&m);
+                (m.to_string(), child)
+            })
+            .collect()
     }
 
-    pub fn add_log_entry(&mut self, entry: MovementLogEntry) {
-        self.movement_log.push(entry);
+    fn legal_moves_for_color_iter(&self, color: &PieceColor) -> impl Iterator<Item = Move> + '_ {
+        self.pseudo_legal_moves_for_color_iter(color)
+            .filter(move |m| MoveResolver {}.is_move_legal(self, m))
     }
 
-    pub fn get_log_entries(&self) -> Vec<MovementLogEntry> {
+    /// The legal captures and promotions of the side to move. Quiet moves are never generated,
+    /// which makes this much cheaper than filtering `legal_moves`.
+    pub fn capture_moves(&self) -> Vec<Move> {
+        let (_, color) = self.get_current_turn_and_color();
+        self.pieces
+            .iter()
+            .filter(|p| !p.is_captured() && p.get_color() == color)
+            .flat_map(pseudo_legal_captures_for_piece)
+            .filter(|m| MoveResolver {}.is_move_legal(self, m))
+            .collect()
+    }
+
+    fn pseudo_legal_moves_for_color_iter(
+        &self,
+        color: &PieceColor,
+    ) -> impl Iterator<Item = Move> + '_ {
+        let color = *color;
+        self.pieces
+            .iter()
+            .filter(move |p| !p.is_captured() && p.get_color() == color)
+            .flat_map(pseudo_legal_moves_for_piece)
+    }
+
+    /// Returns the legal moves of a single piece, checking only that piece's moves for pins
+    /// and check instead of every move on the board. Empty when the piece isn't in play.
+    pub fn legal_moves_for_piece(&self, piece_id: &Uuid) -> Vec<Move> {
+        match self
+            .pieces
+            .iter()
+            .find(|p| p.id == *piece_id && !p.is_captured())
+        {
+            Some(piece) => pseudo_legal_moves_for_piece(piece)
+                .filter(|m| MoveResolver {}.is_move_legal(self, m))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Whether `book` has a move for the current position, i.e. the game is still in book.
+    pub fn is_in_opening_book(&self, book: &OpeningBook) -> bool {
+        !self.book_moves(book).is_empty()
+    }
+
+    /// The moves `book` gives for the current position with their weights, heaviest first.
+    pub fn book_moves(&self, book: &OpeningBook) -> Vec<(Move, u32)> {
+        let legal_moves = self.legal_moves();
+        book.book_moves(self)
+            .into_iter()
+            .filter_map(|book_move| {
+                // two positions can share a key, so only moves legal here count
+                legal_moves
+                    .iter()
+                    .find(|m| m.to_string() == book_move.uci_move)
+                    .map(|m| (m.clone(), book_move.weight))
+            })
+            .collect()
+    }
+
+    pub fn apply_move(&mut self, chess_move: &Move) {
+        if let Err(e) = self.play_legal_move(chess_move) {
+            debug!("ignoring move {}: {}", chess_move, e);
+        }
+    }
+
+    /// Plays a move from `legal_moves`, promoting to the piece it names.
+    fn play_legal_move(&mut self, chess_move: &Move) -> Result<MovementLogEntry, MoveError> {
+        match chess_move.promotion {
+            Some(piece_type) => {
+                self.move_piece_with_promotion(&chess_move.piece_id, &chess_move.to, piece_type)
+            }
+            None => self.move_piece(&chess_move.piece_id, &chess_move.to),
+        }
+    }
+
+    /// Plays a move given in UCI coordinate notation, e.g. `e2e4` or `e7e8q`.
+    pub fn apply_uci_move(&mut self, uci_move: &str) -> Result<Move, MoveError> {
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+        let invalid = || MoveError::InvalidNotation(uci_move.to_string());
+        if !uci_move.is_ascii() || !(4..=5).contains(&uci_move.len()) {
+            return Err(invalid());
+        }
+
+        let from = PieceLocation::new_from_string(&uci_move[0..2]).map_err(|_| invalid())?;
+        let to = PieceLocation::new_from_string(&uci_move[2..4]).map_err(|_| invalid())?;
+        let promote_to = match uci_move.chars().nth(4) {
+            Some(c) if c.is_ascii_lowercase() => Some(promotion_piece(c).ok_or_else(invalid)?),
+            Some(_) => return Err(invalid()),
+            None => None,
+        };
+
+        // without a promotion letter the queen, listed first, is picked
+        let chess_move = self
+            .legal_moves()
+            .into_iter()
+            .find(|m| {
+                m.from == from && m.to == to && (promote_to.is_none() || m.promotion == promote_to)
+            })
+            .ok_or_else(|| MoveError::IllegalMove(uci_move.to_string()))?;
+        self.play_legal_move(&chess_move)?;
+
+        Ok(chess_move)
+    }
+
+    /// Turns whatever move text a user typed into the matching legal move. Accepts SAN
+    /// (`Nf3`, `exd5`, `O-O`, `e8=Q`), long algebraic (`Ng1-f3`, `e2xd3`) and coordinate
+    /// notation (`g1f3`, `e7e8q`); check marks and annotations are ignored.
+    pub fn parse_move(&self, input: &str) -> Result<Move, MoveError> {
+        self.parse_move_with_promotion(input).map(|(m, _)| m)
+    }
+
+    /// Plays a move given in standard algebraic notation, e.g. `Nf3`, `exd5`, `O-O` or
+    /// `e8=Q+`, and returns its movement log entry. Anything `parse_move` understands is
+    /// accepted.
+    pub fn apply_san(&mut self, san: &str) -> Result<MovementLogEntry, MoveError> {
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+
+        let (chess_move, promote_to) = self.parse_move_with_promotion(san)?;
+        match promote_to {
+            Some(piece_type) => {
+                self.move_piece_with_promotion(&chess_move.piece_id, &chess_move.to, piece_type)
+            }
+            None => self.move_piece(&chess_move.piece_id, &chess_move.to),
+        }
+    }
+
+    /// Same as `parse_move`, also returning the piece named for a promotion, if any.
+    fn parse_move_with_promotion(
+        &self,
+        input: &str,
+    ) -> Result<(Move, Option<PieceType>), MoveError> {
+        let invalid = || {
+            MoveError::InvalidNotation(format!(
+                "{} (expected SAN like Nf3, long algebraic like Ng1-f3 or coordinates like g1f3)",
+                input
+            ))
+        };
+        let text = input
+            .trim()
+            .trim_end_matches(['+', '#', '!', '?'])
+            .replace('0', "O");
+        if text.is_empty() || !text.is_ascii() {
+            return Err(invalid());
+        }
+
+        let legal_moves = self.legal_moves();
+
+        if text == "O-O" || text == "O-O-O" {
+            let side = if text == "O-O" {
+                CastleSide::KingSide
+            } else {
+                CastleSide::QueenSide
+            };
+            return legal_moves
+                .into_iter()
+                .find(|m| m.castle == Some(side.clone()))
+                .map(|m| (m, None))
+                .ok_or_else(|| MoveError::IllegalMove(input.to_string()));
+        }
+
+        let mut text = text.replace(['-', 'x', ':', '='], "");
+        // a promotion piece follows the rank of the target square: e8Q, e8=Q or e7e8q
+        let mut chars = text.chars().rev();
+        let promote_to = match (chars.next(), chars.next()) {
+            (Some(last), Some(before)) if before.is_ascii_digit() => promotion_piece(last),
+            _ => None,
+        };
+        if promote_to.is_some() {
+            text.pop();
+        }
+
+        let piece_type = match text.chars().next() {
+            Some('N') => Some(PieceType::Knight),
+            Some('B') => Some(PieceType::Bishop),
+            Some('R') => Some(PieceType::Rook),
+            Some('Q') => Some(PieceType::Queen),
+            Some('K') => Some(PieceType::King),
+            _ => None,
+        };
+        if piece_type.is_some() {
+            text.remove(0);
+        }
+        if text.len() < 2 || text.len() > 4 {
+            return Err(invalid());
+        }
+
+        let (from_hint, target) = text.split_at(text.len() - 2);
+        let target = PieceLocation::new_from_string(target).map_err(|_| invalid())?;
+        // a full origin square without a piece letter is coordinate notation, which may
+        // move any piece; otherwise a missing letter means a pawn
+        let any_piece = piece_type.is_none() && from_hint.len() == 2;
+        let piece_type = piece_type.unwrap_or(PieceType::Pawn);
+
+        let candidates: Vec<Move> = legal_moves
+            .into_iter()
+            .filter(|m| m.to == target)
+            .filter(|m| {
+                any_piece || self.get_piece_by_id_copy(&m.piece_id).get_type() == piece_type
+            })
+            .filter(|m| {
+                from_hint.chars().all(|c| {
+                    m.from.get_file() == c.to_string()
+                        || m.from.get_rank().to_string() == c.to_string()
+                })
+            })
+            // a promotion without a piece named is taken to be a queen
+            .filter(|m| {
+                m.promotion.is_none() || m.promotion == promote_to.or(Some(PieceType::Queen))
+            })
+            .collect();
+
+        match candidates.len() {
+            0 => Err(MoveError::IllegalMove(input.to_string())),
+            1 => Ok((candidates.into_iter().next().unwrap(), promote_to)),
+            _ => Err(MoveError::InvalidNotation(format!(
+                "{} is ambiguous, add the origin file or rank",
+                input
+            ))),
+        }
+    }
+
+    /// Sets up the board the way a UCI `position` command does: `startpos` or `fen <fen>`,
+    /// optionally followed by `moves` and a list of coordinate moves to play from there.
+    pub fn set_position_uci(&mut self, command: &str) -> Result<(), MoveError> {
+        let mut tokens = command.split_whitespace().peekable();
+        if tokens.peek() == Some(&"position") {
+            tokens.next();
+        }
+
+        match tokens.next() {
+            Some("startpos") => {
+                self.reset();
+                match tokens.next() {
+                    None => return Ok(()),
+                    Some("moves") => {}
+                    Some(_) => return Err(MoveError::InvalidPosition(command.to_string())),
+                }
+            }
+            Some("fen") => {
+                // the FEN runs up to the "moves" keyword, which is consumed along with it
+                let fields: Vec<&str> = tokens.by_ref().take_while(|t| *t != "moves").collect();
+                let position = ChessMatch::from_fen(&fields.join(" "))
+                    .map_err(|e| MoveError::InvalidPosition(e.to_string()))?;
+                *self = ChessMatch {
+                    id: self.id,
+                    white_player: self.white_player,
+                    black_player: self.black_player,
+                    ..position
+                };
+            }
+            _ => return Err(MoveError::InvalidPosition(command.to_string())),
+        }
+
+        for uci_move in tokens {
+            self.apply_uci_move(uci_move)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets up a match from a FEN string. The clock fields may be left off, in which case
+    /// the halfmove clock starts at 0 and the fullmove number at 1. The match gets new
+    /// player ids.
+    pub fn from_fen(fen: &str) -> Result<ChessMatch, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::WrongFieldCount(fields.len()));
+        }
+
+        let pieces = ChessMatch::parse_fen_placement(fields[0])?;
+
+        let side_to_move = match fields[1] {
+            "w" => PieceColor::White,
+            "b" => PieceColor::Black,
+            side => return Err(FenError::InvalidSideToMove(side.to_string())),
+        };
+
+        let castling = fields[2];
+        if castling != "-" && (castling.is_empty() || !castling.chars().all(|c| "KQkq".contains(c)))
+        {
+            return Err(FenError::InvalidCastling(castling.to_string()));
+        }
+
+        let en_passant_target = match fields[3] {
+            "-" => None,
+            square => match PieceLocation::new_from_string(square) {
+                Ok(location) if location.get_rank() == 3 || location.get_rank() == 6 => {
+                    Some(location)
+                }
+                _ => return Err(FenError::InvalidEnPassant(square.to_string())),
+            },
+        };
+        let mut chess_match = ChessMatch::set_up(pieces, side_to_move, castling, en_passant_target);
+
+        if fields.len() == 6 {
+            let clock = |field: &str| {
+                field
+                    .parse::<u32>()
+                    .map_err(|_| FenError::InvalidClock(field.to_string()))
+            };
+            chess_match.start_halfmove_clock = clock(fields[4])?;
+            let fullmove_number = clock(fields[5])?;
+            if fullmove_number == 0 {
+                return Err(FenError::InvalidClock(fields[5].to_string()));
+            }
+            chess_match.start_ply += (fullmove_number - 1) * 2;
+        }
+
+        chess_match.finish_set_up().map_err(|e| match e {
+            MoveError::InvalidPosition(reason) => FenError::InvalidPosition(reason),
+            e => FenError::InvalidPosition(e.to_string()),
+        })
+    }
+
+    /// Starts building an arbitrary position piece by piece, with white to move and no
+    /// castling or en passant rights unless set otherwise.
+    pub fn from_position() -> PositionBuilder {
+        PositionBuilder::new()
+    }
+
+    /// A match holding `pieces` with `side_to_move` to move, before the position has been
+    /// validated. Kings and rooks not covered by the `castling` rights (in FEN letters)
+    /// count as moved.
+    pub(crate) fn set_up(
+        pieces: Vec<ChessPiece>,
+        side_to_move: PieceColor,
+        castling: &str,
+        en_passant_target: Option<PieceLocation>,
+    ) -> ChessMatch {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.pieces = pieces;
+        chess_match.current_turn = side_to_move;
+        chess_match.mark_moved_pieces(castling);
+        chess_match.en_passant_target = en_passant_target;
+        if side_to_move == PieceColor::Black {
+            chess_match.start_ply = 1;
+        }
+
+        chess_match
+    }
+
+    /// Validates a match made by `set_up`, works out its moves and remembers its FEN as the
+    /// starting position.
+    pub(crate) fn finish_set_up(mut self) -> Result<ChessMatch, MoveError> {
+        self.validate_position()?;
+        self.calculate_valid_moves();
+        self.start_fen = Some(self.to_fen());
+
+        Ok(self)
+    }
+
+    /// Describes the current position as a FEN string.
+    pub fn to_fen(&self) -> String {
+        let mut ranks: Vec<String> = Vec::new();
+        for rank in (1..=8).rev() {
+            let mut rank_text = String::new();
+            let mut empty = 0;
+            for file in FILES {
+                match self.get_piece_at_location(PieceLocation::new(file.to_string(), rank)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            rank_text.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        rank_text.push(fen::piece_char(piece.get_type(), piece.get_color()));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                rank_text.push_str(&empty.to_string());
+            }
+            ranks.push(rank_text);
+        }
+
+        let side = match self.current_turn {
+            PieceColor::White => "w",
+            PieceColor::Black => "b",
+        };
+
+        let rights = self.castling_rights();
+        let mut castling: String = [
+            (rights.white_king_side, 'K'),
+            (rights.white_queen_side, 'Q'),
+            (rights.black_king_side, 'k'),
+            (rights.black_queen_side, 'q'),
+        ]
+        .iter()
+        .filter(|(has_right, _)| *has_right)
+        .map(|(_, c)| *c)
+        .collect();
+        if castling.is_empty() {
+            castling = "-".to_string();
+        }
+
+        let en_passant = self
+            .en_passant_target
+            .as_ref()
+            .map_or("-".to_string(), |l| l.to_string());
+
+        format!(
+            "{} {} {} {} {} {}",
+            ranks.join("/"),
+            side,
+            castling,
+            en_passant,
+            self.halfmove_clock(),
+            self.fullmove_number()
+        )
+    }
+
+    /// Writes the match as PGN: the Seven Tag Roster, plus SetUp and FEN tags for a match
+    /// that didn't start from the usual position, followed by the moves and the result. A
+    /// match set up from the starting FEN is written like any other.
+    pub fn to_pgn(&self) -> String {
+        let date = self.started.map_or("????.??.??".to_string(), |d| {
+            d.format("%Y.%m.%d").to_string()
+        });
+        let result = self.result().pgn_marker();
+        let mut tags = vec![
+            ("Event", "Casual Game".to_string()),
+            ("Site", "?".to_string()),
+            ("Date", date),
+            ("Round", "-".to_string()),
+            ("White", self.white_player.to_string()),
+            ("Black", self.black_player.to_string()),
+            ("Result", result.to_string()),
+        ];
+        let start_fen = self
+            .start_fen
+            .as_ref()
+            .filter(|start_fen| start_fen.as_str() != fen::STARTING_FEN);
+        if let Some(start_fen) = start_fen {
+            tags.push(("SetUp", "1".to_string()));
+            tags.push(("FEN", start_fen.clone()));
+        }
+
+        let mut pgn: String = tags
+            .iter()
+            .map(|(name, value)| format!("[{} \"{}\"]\n", name, value))
+            .collect();
+        pgn.push('\n');
+        pgn.push_str(&MovementLogger::get_pgn_movetext(self, result));
+        pgn.push('\n');
+
+        pgn
+    }
+
+    /// Moves made by either side since the last capture or pawn move, for the fifty-move
+    /// rule.
+    pub fn halfmove_clock(&self) -> u32 {
+        let since_reset = self
+            .movement_log
+            .iter()
+            .rev()
+            .take_while(|e| !e.resets_halfmove_clock())
+            .count() as u32;
+        if since_reset == self.movement_log.len() as u32 {
+            self.start_halfmove_clock + since_reset
+        } else {
+            since_reset
+        }
+    }
+
+    /// The number of the current full move, starting at 1 and going up after black moves.
+    pub fn fullmove_number(&self) -> u32 {
+        (self.start_ply + self.movement_log.len() as u32) / 2 + 1
+    }
+
+    fn parse_fen_placement(placement: &str) -> Result<Vec<ChessPiece>, FenError> {
+        let invalid = || FenError::InvalidPlacement(placement.to_string());
+        let rows: Vec<&str> = placement.split('/').collect();
+        if rows.len() != 8 {
+            return Err(invalid());
+        }
+
+        let mut pieces = Vec::new();
+        for (row, rank) in rows.iter().zip((1..=8).rev()) {
+            let mut file_index = 0;
+            for c in row.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    file_index += skip as usize;
+                    continue;
+                }
+
+                let (piece_type, color) = fen::char_piece(c).ok_or_else(invalid)?;
+                let file = FILES.get(file_index).ok_or_else(invalid)?;
+                pieces.push(ChessPiece::new(
+                    piece_type,
+                    color,
+                    PieceLocation::new(file.to_string(), rank),
+                    piece_type.get_points(),
+                ));
+                file_index += 1;
+            }
+            if file_index != 8 {
+                return Err(invalid());
+            }
+        }
+
+        Ok(pieces)
+    }
+
+    /// Pieces set up from a FEN all start out unmoved. Marks the ones that must have moved:
+    /// pawns off their starting rank, and kings and rooks that the castling rights rule out.
+    fn mark_moved_pieces(&mut self, castling: &str) {
+        for piece in self.pieces.iter_mut() {
+            let (home_rank, pawn_rank, king_side, queen_side) = match piece.get_color() {
+                PieceColor::White => (1, 2, 'K', 'Q'),
+                PieceColor::Black => (8, 7, 'k', 'q'),
+            };
+            let file = piece.location.get_file();
+            let on_home_rank = piece.location.get_rank() == home_rank;
+            let unmoved = match piece.get_type() {
+                PieceType::Pawn => piece.location.get_rank() == pawn_rank,
+                PieceType::King => {
+                    on_home_rank
+                        && file == "e"
+                        && (castling.contains(king_side) || castling.contains(queen_side))
+                }
+                PieceType::Rook => {
+                    on_home_rank
+                        && ((file == "h" && castling.contains(king_side))
+                            || (file == "a" && castling.contains(queen_side)))
+                }
+                _ => true,
+            };
+            if !unmoved {
+                piece.set_moved(piece.location.clone());
+            }
+        }
+    }
+
+    /// Puts every piece back in its starting position and clears the movement log, keeping
+    /// the match id and players.
+    pub fn reset(&mut self) {
+        *self = ChessMatch {
+            id: self.id,
+            ..ChessMatch::new(self.white_player, self.black_player)
+        };
+        self.calculate_valid_moves();
+    }
+
+    /// Finds the moves for `color` after which the moved piece attacks two or more enemy
+    /// pieces. Results are grouped by the moving piece, listing the squares it can fork from,
+    /// and ordered so the forks winning the most material (by piece points, with the king
+    /// outranking everything) come first.
+    pub fn forks_for(&self, color: PieceColor) -> Vec<(Uuid, Vec<PieceLocation>)> {
+        const KING_FORK_VALUE: u32 = 100;
+        let mut forks: Vec<(Uuid, PieceLocation, u32)> = Vec::new();
+
+        for m in self.legal_moves_for_color(&color) {
+            let attacked = self.attacked_after(&m);
+            if attacked.len() < 2 {
+                continue;
+            }
+
+            let value = attacked
+                .iter()
+                .map(|p| match p.get_type() {
+                    PieceType::King => KING_FORK_VALUE,
+                    _ => p.points(),
+                })
+                .sum();
+            forks.push((m.piece_id, m.to, value));
+        }
+
+        forks.sort_by_key(|f| std::cmp::Reverse(f.2));
+        let mut result: Vec<(Uuid, Vec<PieceLocation>)> = Vec::new();
+        for (piece_id, location, _) in forks {
+            match result.iter_mut().find(|(id, _)| *id == piece_id) {
+                Some((_, locations)) => locations.push(location),
+                None => result.push((piece_id, vec![location])),
+            }
+        }
+
+        result
+    }
+
+    /// The enemy pieces the moved piece attacks once `chess_move` has been played.
+    fn attacked_after(&self, chess_move: &Move) -> Vec<ChessPiece> {
+        let resolver = MoveResolver {};
+        let piece = self.get_piece_by_id_copy(&chess_move.piece_id);
+        let sim_type = if chess_move.capture {
+            SimulateType::Capture
+        } else {
+            SimulateType::Move
+        };
+        let mut sim_result =
+            resolver.simulate_move_or_capture(sim_type, self, &piece, chess_move.to.clone());
+        resolver.calculate_valid_moves(&mut sim_result);
+
+        sim_result
+            .get_piece_by_id_copy(&chess_move.piece_id)
+            .get_valid_captures()
+            .into_iter()
+            .filter_map(|loc| sim_result.get_piece_at_location(loc))
+            .collect()
+    }
+
+    /// How the position stands from White's point of view, e.g. for an evaluation bar,
+    /// from a short search so hanging pieces and mates a few moves away are seen.
+    pub fn evaluate_current_position(&self) -> Evaluation {
+        match self.result() {
+            MatchResult::WhiteWonCheckmate | MatchResult::BlackWonCheckmate => {
+                return Evaluation::Mate {
+                    winner: self.current_turn.opposite(),
+                    moves: 0,
+                }
+            }
+            MatchResult::Draw { .. } => return Evaluation::Centipawns(0),
+            _ => {}
+        }
+        let mut ai = AiPlayer::new(SearchConfig {
+            max_depth: 2,
+            time_limit_ms: None,
+            hash_size_mb: 1,
+            ..SearchConfig::default()
+        });
+        let score = match ai.search(self) {
+            Some(result) => result.score,
+            None => return Evaluation::Centipawns(0),
+        };
+
+        // mate scores count down from MATE_SCORE by the plies to the mate
+        let plies = MATE_SCORE - score.abs();
+        if plies < 1000 {
+            let winner = if score > 0 {
+                self.current_turn
+            } else {
+                self.current_turn.opposite()
+            };
+            return Evaluation::Mate {
+                winner,
+                moves: (plies as u32).div_ceil(2),
+            };
+        }
+        match self.current_turn {
+            PieceColor::White => Evaluation::Centipawns(score),
+            PieceColor::Black => Evaluation::Centipawns(-score),
+        }
+    }
+
+    /// Searches for the best move of the side to move within `limit`, along with its score, the
+    /// depth reached and the line the search expects to follow, in SAN. Returns `None` when
+    /// the game is over.
+    pub fn best_line(&self, limit: SearchLimit) -> Option<BestLine> {
+        let result = AiPlayer::new(limit.config()).search(self)?;
+        let pv = self
+            .line_to_san(&result.pv)
+            .expect("the principal variation is playable");
+        Some(BestLine {
+            best_move: result.best_move,
+            score: result.score,
+            depth: result.depth,
+            pv,
+        })
+    }
+
+    /// Asks `engine` for the best move of the side to move and explains it, for coaching.
+    /// Returns `None` when the game is over.
+    pub fn suggest_move(&self, engine: &mut impl MoveChooser) -> Option<Suggestion> {
+        let chess_move = engine.best_move(self)?;
+        let explanation = self.explain_move(&chess_move);
+
+        Some(Suggestion {
+            chess_move,
+            explanation,
+        })
+    }
+
+    /// Describes a legal move of the side to move in a few words, e.g. "develops a piece and
+    /// attacks the knight", built from what the move captures, threatens and checks.
+    pub fn explain_move(&self, chess_move: &Move) -> String {
+        if self.move_mates(chess_move) {
+            return "delivers checkmate".to_string();
+        }
+
+        let piece = self.get_piece_by_id_copy(&chess_move.piece_id);
+        let mut phrases: Vec<String> = Vec::new();
+
+        if chess_move.capture {
+            // nothing stands on the target square of an en passant capture
+            let captured = self
+                .get_piece_at_location(chess_move.to.clone())
+                .map_or(PieceType::Pawn, |p| p.get_type());
+            let gain = self.see(chess_move);
+            phrases.push(if gain >= captured.get_points() as i32 {
+                format!("wins a {}", captured.get_name())
+            } else if gain > 0 {
+                "wins material".to_string()
+            } else if gain == 0 {
+                format!("trades for the {}", captured.get_name())
+            } else {
+                format!("gives up material for the {}", captured.get_name())
+            });
+        }
+
+        let back_rank = match piece.get_color() {
+            PieceColor::White => 1,
+            PieceColor::Black => 8,
+        };
+        if matches!(piece.get_type(), PieceType::Knight | PieceType::Bishop)
+            && piece.location.get_rank() == back_rank
+        {
+            phrases.push("develops a piece".to_string());
+        }
+
+        let last_rank = match piece.get_color() {
+            PieceColor::White => 8,
+            PieceColor::Black => 1,
+        };
+        if piece.get_type() == PieceType::Pawn && chess_move.to.get_rank() == last_rank {
+            let promotion = chess_move.promotion.unwrap_or(PieceType::Queen);
+            phrases.push(format!("promotes to a {}", promotion.get_name()));
+        }
+
+        if self.move_gives_check(chess_move) {
+            phrases.push("gives check".to_string());
+        }
+
+        // pawns and the king are left out here: threats to pawns are rarely worth a
+        // mention, and attacking the king is already reported as a check
+        let mut targets: Vec<PieceType> = self
+            .attacked_after(chess_move)
+            .iter()
+            .map(|p| p.get_type())
+            .filter(|t| !matches!(t, PieceType::Pawn | PieceType::King))
+            .collect();
+        targets.sort_by_key(|t| std::cmp::Reverse(t.get_points()));
+        let target_names: Vec<String> = targets
+            .iter()
+            .map(|t| format!("the {}", t.get_name()))
+            .collect();
+        match target_names.len() {
+            0 => {}
+            1 => phrases.push(format!("attacks {}", target_names[0])),
+            _ => phrases.push(format!("forks {}", join_phrases(&target_names))),
+        }
+
+        if phrases.is_empty() {
+            return "is a quiet move".to_string();
+        }
+
+        join_phrases(&phrases)
+    }
+
+    /// Whether `chess_move` checkmates the opponent.
+    pub(crate) fn move_mates(&self, chess_move: &Move) -> bool {
+        let (_, color) = self.get_current_turn_and_color();
+        let mut match_copy = self.copy();
+        match_copy.apply_move(chess_move);
+
+        matches!(
+            (match_copy.result(), color),
+            (MatchResult::WhiteWonCheckmate, PieceColor::White)
+                | (MatchResult::BlackWonCheckmate, PieceColor::Black)
+        )
+    }
+
+    /// Returns the opponent pieces currently giving check to the side to move's king.
+    pub fn checking_pieces(&self) -> Vec<ChessPiece> {
+        let (_, color) = self.get_current_turn_and_color();
+        let king = match self
+            .get_kings()
+            .into_iter()
+            .find(|k| k.get_color() == color)
+        {
+            Some(king) => king,
+            None => return Vec::new(),
+        };
+
+        MatchHelpers::get_attackers_of_location(self, &king.location, &color.opposite())
+    }
+
+    /// Classifies how `chess_move` resolves the check against the side to move. Returns
+    /// `None` when the side to move is not in check or the move is not legal.
+    pub fn evasion_kind(&self, chess_move: &Move) -> Option<EvasionKind> {
+        let resolver = MoveResolver {};
+        let checkers = self.checking_pieces();
+        if checkers.is_empty() || !resolver.is_move_legal(self, chess_move) {
+            return None;
+        }
+
+        let piece = self.get_piece_by_id_copy(&chess_move.piece_id);
+        if piece.get_type() == PieceType::King {
+            return Some(EvasionKind::KingMove);
+        }
+
+        // with two checkers only a king move is legal, so a single checker remains here
+        let checker = &checkers[0];
+        let captured_location = match self.en_passant_victim() {
+            Some(victim) if self.is_en_passant_capture(&piece, &chess_move.to) => victim.location,
+            _ => chess_move.to.clone(),
+        };
+        if captured_location == checker.location {
+            return Some(EvasionKind::CaptureChecker);
+        }
+
+        let king = self
+            .get_kings()
+            .into_iter()
+            .find(|k| k.get_color() == piece.get_color())?;
+        if king
+            .location
+            .squares_between(&checker.location)
+            .contains(&chess_move.to)
+        {
+            return Some(EvasionKind::Block);
+        }
+
+        None
+    }
+
+    /// Checks whether playing `chess_move` leaves the opponent stalemated.
+    pub fn is_stalemate_trap(&self, chess_move: &Move) -> bool {
+        let mut match_copy = self.copy();
+        match_copy.apply_move(chess_move);
+
+        match_copy.result()
+            == MatchResult::Draw {
+                reason: DrawReason::Stalemate,
+            }
+    }
+
+    /// Returns the pieces of color `by` that attack `square`.
+    pub fn attackers_of(&self, square: &PieceLocation, by: PieceColor) -> Vec<ChessPiece> {
+        MatchHelpers::get_attackers_of_location(self, square, &by)
+    }
+
+    /// Returns the pieces of color `by` that could recapture on `square`, including x-ray
+    /// defenders lined up behind another defender, in the order they would get to recapture.
+    pub fn defenders_of(&self, square: &PieceLocation, by: PieceColor) -> Vec<ChessPiece> {
+        let mut board = self.copy();
+        let mut defenders: Vec<ChessPiece> = Vec::new();
+        loop {
+            let found = MatchHelpers::get_attackers_of_location(&board, square, &by);
+            if found.is_empty() {
+                break;
+            }
+            // taking the direct defenders off the board uncovers any pieces behind them
+            for piece in found {
+                board.get_piece_by_id(&piece.id).set_captured();
+                defenders.push(piece);
+            }
+        }
+
+        defenders
+    }
+
+    /// Works out which single legal move turns this position into `other`, comparing the
+    /// pieces on the board and the side to move. Returns `None` if no single move does.
+    pub fn move_between(&self, other: &ChessMatch) -> Option<Move> {
+        let target = other.position_key();
+        self.legal_moves().into_iter().find(|m| {
+            let mut match_copy = self.copy();
+            match_copy.apply_move(m);
+            match_copy.position_key() == target
+        })
+    }
+
+    /// The Zobrist hash of the current position, covering the pieces, side to move, castling
+    /// rights and en passant square. Used to look positions up in an opening book.
+    pub fn zobrist_key(&self) -> u64 {
+        let (_, color) = self.get_current_turn_and_color();
+        let en_passant_file = self
+            .en_passant_target
+            .as_ref()
+            .map(|l| Board::square(l) % 8);
+        zobrist::key(
+            &self.get_board(),
+            color,
+            self.castling_rights(),
+            en_passant_file,
+        )
+    }
+
+    /// The side to move and every piece in play by square, enough to tell two positions
+    /// apart regardless of piece ids.
+    fn position_key(&self) -> (PieceColor, Vec<(usize, PieceType, PieceColor)>) {
+        let mut pieces: Vec<(usize, PieceType, PieceColor)> = self
+            .get_pieces_in_play()
+            .iter()
+            .map(|p| (p.location.index(), p.get_type(), p.get_color()))
+            .collect();
+        pieces.sort_by_key(|(index, _, _)| *index);
+        let (_, color) = self.get_current_turn_and_color();
+
+        (color, pieces)
+    }
+
+    /// Checks whether `chess_move` would put the opponent's king in check, counting
+    /// discovered checks, checks from a promoted queen and checks from a castling rook.
+    pub fn move_gives_check(&self, chess_move: &Move) -> bool {
+        let resolver = MoveResolver {};
+        let piece = self.get_piece_by_id_copy(&chess_move.piece_id);
+        let sim_type = if chess_move.capture {
+            SimulateType::Capture
+        } else {
+            SimulateType::Move
+        };
+        let mut sim_result =
+            resolver.simulate_move_or_capture(sim_type, self, &piece, chess_move.to.clone());
+
+        let moved = sim_result.get_piece_by_id(&piece.id);
+        if moved.can_be_promoted() {
+            moved.promote(chess_move.promotion.unwrap_or(PieceType::Queen));
+        }
+        let castle_data = match piece.get_color() {
+            PieceColor::White => &self.white_king_castle,
+            PieceColor::Black => &self.black_king_castle,
+        };
+        if let Some(castle) = castle_data
+            .iter()
+            .find(|c| c.king_id == piece.id && c.king_target_location == chess_move.to)
+        {
+            sim_result.get_piece_by_id(&castle.rook_id).location =
+                castle.rook_target_location.clone();
+        }
+
+        let opponent = piece.get_color().opposite();
+        sim_result
+            .get_kings()
+            .iter()
+            .filter(|k| k.get_color() == opponent)
+            .any(|k| {
+                MatchHelpers::is_location_attacked_by(&sim_result, &k.location, &piece.get_color())
+            })
+    }
+
+    /// Returns the legal moves of the side to move that give check.
+    pub fn checking_moves(&self) -> Vec<Move> {
+        self.legal_moves()
+            .into_iter()
+            .filter(|m| self.move_gives_check(m))
+            .collect()
+    }
+
+    /// Applies the rule of the square to the pawn with `pawn_id`: whether the enemy king is
+    /// close enough to catch it before it promotes, taking into account whose move it is.
+    /// Only the two kings and the pawn are considered, so other pieces that could stop the
+    /// pawn are ignored.
+    pub fn pawn_can_be_stopped(&self, pawn_id: &Uuid) -> bool {
+        let pawn = self.get_piece_by_id_copy(pawn_id);
+        let (promotion_rank, start_rank) = match pawn.get_color() {
+            PieceColor::White => (8, 2),
+            PieceColor::Black => (1, 7),
+        };
+        let promotion_square = PieceLocation::new(pawn.location.get_file(), promotion_rank);
+        let mut pawn_distance = pawn.location.get_rank().abs_diff(promotion_rank);
+        // the double step from the starting rank saves the pawn a move
+        if pawn.location.get_rank() == start_rank {
+            pawn_distance -= 1;
+        }
+
+        let defender = pawn.get_color().opposite();
+        let king = match self
+            .get_kings()
+            .into_iter()
+            .find(|k| k.get_color() == defender)
+        {
+            Some(king) => king,
+            None => return false,
+        };
+        let king_distance = king.location.chebyshev_distance(&promotion_square);
+
+        // the king is in time if it can step inside the pawn's square, from where it reaches
+        // the promotion square first or captures the new queen
+        let (_, side_to_move) = self.get_current_turn_and_color();
+        if side_to_move == defender {
+            king_distance <= pawn_distance + 1
+        } else {
+            king_distance <= pawn_distance
+        }
+    }
+
+    /// Measures the pressure on `color`'s king as (enemy attackers, friendly defenders). The
+    /// king zone is the king's square and the squares next to it; a piece counts once if it
+    /// attacks any square of the zone. The king itself is not counted as a defender.
+    pub fn king_attack_balance(&self, color: PieceColor) -> (usize, usize) {
+        let king = match self
+            .get_kings()
+            .into_iter()
+            .find(|k| k.get_color() == color)
+        {
+            Some(king) => king,
+            None => return (0, 0),
+        };
+        let zone: Vec<PieceLocation> = (-1..=1)
+            .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+            .filter_map(|(dx, dy)| king.location.offset(dx, dy))
+            .collect();
+
+        let count_pieces = |by: PieceColor| {
+            let mut ids: Vec<Uuid> = zone
+                .iter()
+                .flat_map(|square| self.attackers_of(square, by))
+                .filter(|p| p.id != king.id)
+                .map(|p| p.id)
+                .collect();
+            ids.sort();
+            ids.dedup();
+            ids.len()
+        };
+
+        (count_pieces(color.opposite()), count_pieces(color))
+    }
+
+    /// Counts the legal moves available to `color`, grouped by the type of the moving piece.
+    /// Types with no legal moves are left out.
+    pub fn mobility_by_type(&self, color: PieceColor) -> HashMap<PieceType, usize> {
+        let mut mobility: HashMap<PieceType, usize> = HashMap::new();
+        for m in self.legal_moves_for_color(&color) {
+            let piece_type = self.get_piece_by_id_copy(&m.piece_id).get_type();
+            *mobility.entry(piece_type).or_insert(0) += 1;
+        }
+
+        mobility
+    }
+
+    /// Returns the legal captures for the side to move.
+    pub fn legal_captures(&self) -> Vec<Move> {
+        self.legal_moves()
+            .into_iter()
+            .filter(|m| m.capture)
+            .collect()
+    }
+
+    /// Static exchange evaluation of a capture: the material the mover comes out with, in
+    /// piece points, if both sides keep recapturing on the target square with their least
+    /// valuable attacker for as long as it pays off.
+    pub fn see(&self, chess_move: &Move) -> i32 {
+        const KING_VALUE: i32 = 100;
+        let value = |p: &ChessPiece| match p.get_type() {
+            PieceType::King => KING_VALUE,
+            piece_type => piece_type.get_points() as i32,
+        };
+
+        let mut board = self.copy();
+        let target = chess_move.to.clone();
+        let mover = board.get_piece_by_id_copy(&chess_move.piece_id);
+        let victim = match self.en_passant_victim() {
+            Some(victim) if self.is_en_passant_capture(&mover, &target) => Some(victim),
+            _ => self.get_piece_at_location(target.clone()),
+        };
+        let victim = match victim {
+            Some(victim) => victim,
+            None => return 0,
+        };
+
+        let mut gains = vec![value(&victim)];
+        board.get_piece_by_id(&victim.id).set_captured();
+        board.get_piece_by_id(&mover.id).location = target.clone();
+        let mut piece_on_target = mover;
+        let mut side = piece_on_target.get_color().opposite();
+
+        loop {
+            let attacker = MatchHelpers::get_attackers_of_location(&board, &target, &side)
+                .into_iter()
+                .min_by_key(|p| value(p));
+            let attacker = match attacker {
+                Some(attacker) => attacker,
+                None => break,
+            };
+
+            gains.push(value(&piece_on_target) - gains[gains.len() - 1]);
+            board.get_piece_by_id(&piece_on_target.id).set_captured();
+            board.get_piece_by_id(&attacker.id).location = target.clone();
+            piece_on_target = attacker;
+            side = side.opposite();
+        }
+
+        // each side may stop recapturing once continuing would lose material
+        while gains.len() > 1 {
+            let last = gains.pop().unwrap();
+            let previous = gains.last_mut().unwrap();
+            *previous = -std::cmp::max(-*previous, last);
+        }
+
+        gains[0]
+    }
+
+    /// Captures that win material outright for the side to move, best first.
+    pub fn winning_captures(&self) -> Vec<Move> {
+        let mut captures: Vec<(Move, i32)> = self
+            .legal_captures()
+            .into_iter()
+            .map(|m| {
+                let gain = self.see(&m);
+                (m, gain)
+            })
+            .filter(|(_, gain)| *gain > 0)
+            .collect();
+        captures.sort_by_key(|(_, gain)| std::cmp::Reverse(*gain));
+
+        captures.into_iter().map(|(m, _)| m).collect()
+    }
+
+    /// Determines the outcome of the match from the current position: the game is over once
+    /// the side to move has no legal moves, either checkmated or stalemated, or once the
+    /// fifty-move rule applies or neither side can mate, unless the last move was mate.
+    /// Repetitions only have to be claimed, so they are left to the caller, see
+    /// `is_threefold_repetition`. A result that
+    /// was recorded on the match, such as a resignation, is reported as is.
+    pub fn result(&self) -> MatchResult {
+        if self.result != MatchResult::InProgress {
+            return self.result;
+        }
+
+        if self.has_legal_move() {
+            if self.is_fifty_move_draw() {
+                return MatchResult::Draw {
+                    reason: DrawReason::FiftyMoveRule,
+                };
+            }
+            if self.has_insufficient_material() {
+                return MatchResult::Draw {
+                    reason: DrawReason::InsufficientMaterial,
+                };
+            }
+            return MatchResult::InProgress;
+        }
+
+        let (_, color) = self.get_current_turn_and_color();
+        let in_check = self
+            .get_kings()
+            .iter()
+            .filter(|k| k.get_color() == color)
+            .any(|k| MatchHelpers::is_location_attacked_by(self, &k.location, &color.opposite()));
+
+        match (in_check, color) {
+            (true, PieceColor::White) => MatchResult::BlackWonCheckmate,
+            (true, PieceColor::Black) => MatchResult::WhiteWonCheckmate,
+            (false, _) => MatchResult::Draw {
+                reason: DrawReason::Stalemate,
+            },
+        }
+    }
+
+    /// Records a result that can't be read off the board, such as a resignation. It is
+    /// saved with the match and `result()` reports it instead of recomputing one.
+    /// Recording `InProgress` reopens the match.
+    pub fn record_result(&mut self, result: MatchResult) {
+        self.result = result;
+        self.winner = result.winner().map(|color| match color {
+            PieceColor::White => self.white_player,
+            PieceColor::Black => self.black_player,
+        });
+        if result == MatchResult::InProgress {
+            self.status = MatchStatus::InProgress;
+            self.completed = None;
+        } else {
+            self.status = MatchStatus::Completed;
+            self.completed = Some(Utc::now());
+        }
+    }
+
+    /// Works the result out from the board and records it, ending the match once it is
+    /// decided. Called after every move.
+    fn update_result(&mut self) {
+        self.record_result(MatchResult::InProgress);
+        let result = self.result();
+        if result != MatchResult::InProgress {
+            self.record_result(result);
+        }
+    }
+
+    pub fn get_status(&self) -> MatchStatus {
+        self.status
+    }
+
+    /// The id of the player who won, once the match is decided and not drawn.
+    pub fn get_winner(&self) -> Option<Uuid> {
+        self.winner
+    }
+
+    pub fn get_kings(&self) -> Vec<ChessPiece> {
+        let kings = self
+            .get_pieces_in_play()
+            .into_iter()
+            .filter(|p| p.get_type() == PieceType::King)
+            .collect();
+        kings
+    }
+
+    pub fn get_piece_by_id(&mut self, piece_id: &Uuid) -> &mut ChessPiece {
+        let piece = self.pieces.iter_mut().find(|p| p.id == *piece_id).unwrap();
+        piece
+    }
+
+    /// The piece with `piece_id`, or why it can't be moved: it was captured or never existed.
+    /// Use this rather than `get_piece_by_id_copy` for ids that come from outside the engine.
+    pub fn find_piece(&self, piece_id: &Uuid) -> Result<ChessPiece, MoveError> {
+        match self.pieces.iter().find(|p| p.id == *piece_id) {
+            Some(piece) if !piece.is_captured() => Ok(piece.clone()),
+            Some(_) => Err(MoveError::PieceCaptured(*piece_id)),
+            // captured pieces are dropped from the board, only the log remembers them
+            None if self
+                .movement_log
+                .iter()
+                .any(|e| e.get_captured_piece_id() == Some(*piece_id)) =>
+            {
+                Err(MoveError::PieceCaptured(*piece_id))
+            }
+            None => Err(MoveError::IllegalMove(format!(
+                "no piece with id {}",
+                piece_id
+            ))),
+        }
+    }
+
+    /// Panics if there is no piece with `piece_id`.
+    pub fn get_piece_by_id_copy(&self, piece_id: &Uuid) -> ChessPiece {
+        let piece = self
+            .pieces
+            .iter()
+            .find(|p| p.id == *piece_id)
+            .expect(format!("Could not find piece with id: {}", piece_id).as_str());
+        piece.to_owned()
+    }
+
+    /// Every square the piece with `piece_id` can move to or capture on.
+    pub fn targets_of(&self, piece_id: &Uuid) -> Result<Vec<PieceLocation>, MoveError> {
+        Ok(self.find_piece(piece_id)?.get_all_targets())
+    }
+
+    pub fn get_pieces_by_type(&self, piece_type: PieceType) -> Vec<ChessPiece> {
+        self.pieces
+            .clone()
+            .into_iter()
+            .filter(|p| p.get_type() == piece_type)
+            .collect()
+    }
+
+    pub fn handle_king_castle(
+        &mut self,
+        piece_id: &Uuid,
+        target_location: &PieceLocation,
+        movement_entry: &mut MovementLogEntry,
+    ) {
+        let piece = self.get_piece_by_id(piece_id);
+        let color = piece.get_color();
+
+        match color {
+            PieceColor::White => {
+                if self.has_king_castle_data(color) {
+                    for wkc in self.get_white_king_castle_data() {
+                        debug!("found king castle data: {:?}", wkc);
+                        if wkc.king_target_location == *target_location {
+                            // piece still moves to target location,
+                            // we just also move the rook to its target location
+                            let rook = self.get_piece_by_id(&wkc.rook_id);
+                            rook.set_moved(wkc.rook_target_location);
+                            match wkc.side {
+                                CastleSide::KingSide => movement_entry.castled_king_side(),
+                                CastleSide::QueenSide => movement_entry.castled_queen_side(),
+                            };
+                        }
+                    }
+                }
+            }
+            PieceColor::Black => {
+                if self.has_king_castle_data(color) {
+                    for bkc in self.get_black_king_castle_data() {
+                        if bkc.king_target_location == *target_location {
+                            let rook = self.get_piece_by_id(&bkc.rook_id);
+                            rook.set_moved(bkc.rook_target_location);
+                            match bkc.side {
+                                CastleSide::KingSide => movement_entry.castled_king_side(),
+                                CastleSide::QueenSide => movement_entry.castled_queen_side(),
+                            };
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get_promotion_policy(&self) -> PromotionPolicy {
+        self.promotion_policy
+    }
+
+    pub fn set_promotion_policy(&mut self, policy: PromotionPolicy) {
+        self.promotion_policy = policy;
+    }
+
+    /// Plays the match on a clock from now on. Both sides start with the full time, and the
+    /// clock starts running with the first move.
+    pub fn set_time_control(&mut self, time_control: TimeControl) {
+        self.clock = Some(MatchClock::new(time_control));
+    }
+
+    pub fn get_clock(&self) -> Option<&MatchClock> {
+        self.clock.as_ref()
+    }
+
+    /// How many milliseconds `color` has left, or `None` when the match isn't timed. The
+    /// clocks stand still once the match is over.
+    pub fn time_remaining(&self, color: PieceColor) -> Option<u64> {
+        let now = self.completed.unwrap_or_else(Utc::now);
+        self.clock
+            .as_ref()
+            .map(|clock| clock.time_remaining(color, now))
+    }
+
+    /// Suspends a timed match, e.g. when a player disconnects, without the time until
+    /// `resume_clock` counting against either side. Returns false if the clock wasn't
+    /// running.
+    pub fn pause_clock(&mut self) -> bool {
+        if self.is_game_over() {
+            return false;
+        }
+        self.clock
+            .as_mut()
+            .is_some_and(|clock| clock.pause(Utc::now()))
+    }
+
+    /// Restarts the clock after `pause_clock`. Playing a move resumes it as well.
+    pub fn resume_clock(&mut self) -> bool {
+        self.clock
+            .as_mut()
+            .is_some_and(|clock| clock.resume(Utc::now()))
+    }
+
+    /// The pawn move refused by `move_piece` under `PromotionPolicy::Prompt`, if it is
+    /// still waiting for a piece to be picked.
+    pub fn get_pending_promotion(&self) -> Option<PendingPromotion> {
+        self.pending_promotion.clone()
+    }
+
+    /// Completes the pending promotion of `piece_id`, playing the pawn's move and turning it
+    /// into `promote_to`.
+    pub fn promote_pawn(
+        &mut self,
+        piece_id: &Uuid,
+        promote_to: PieceType,
+    ) -> Result<MovementLogEntry, MoveError> {
+        let pending = match &self.pending_promotion {
+            Some(pending) if pending.piece_id == *piece_id => pending.clone(),
+            _ => {
+                return Err(MoveError::IllegalMove(format!(
+                    "no promotion pending for {}",
+                    piece_id
+                )))
+            }
+        };
+
+        self.move_piece_with_promotion(piece_id, &pending.to, promote_to)
+    }
+
+    /// Drops the pending promotion, leaving the pawn where it was.
+    pub fn cancel_promotion(&mut self) {
+        self.pending_promotion = None;
+    }
+
+    /// Moves a pawn to the last rank and promotes it to `promote_to`.
+    pub fn move_piece_with_promotion(
+        &mut self,
+        piece_id: &Uuid,
+        location: &PieceLocation,
+        promote_to: PieceType,
+    ) -> Result<MovementLogEntry, MoveError> {
+        if promote_to == PieceType::Pawn || promote_to == PieceType::King {
+            return Err(MoveError::IllegalMove(format!(
+                "cannot promote to {:?}",
+                promote_to
+            )));
+        }
+
+        self.play_move(piece_id, location, promote_to)
+    }
+
+    /// Moves a piece and returns the move's log entry. A pawn reaching the last rank is
+    /// promoted as the `PromotionPolicy` says: to a queen, or not at all, refusing the move
+    /// with `MoveError::PromotionRequired` until the piece is picked through
+    /// `move_piece_with_promotion`. A refused move leaves the match untouched, including
+    /// whose turn it is.
+    pub fn move_piece(
+        &mut self,
+        piece_id: &Uuid,
+        location: &PieceLocation,
+    ) -> Result<MovementLogEntry, MoveError> {
+        self.validate_move(piece_id, location)?;
+        let piece = self.find_piece(piece_id)?;
+        let last_rank = match piece.get_color() {
+            PieceColor::White => 8,
+            PieceColor::Black => 1,
+        };
+        if self.promotion_policy == PromotionPolicy::Prompt
+            && piece.get_type() == PieceType::Pawn
+            && location.get_rank() == last_rank
+        {
+            self.pending_promotion = Some(PendingPromotion {
+                piece_id: *piece_id,
+                from: piece.location.clone(),
+                to: location.clone(),
+            });
+            return Err(MoveError::PromotionRequired(format!(
+                "{}{}",
+                piece.location, location
+            )));
+        }
+
+        self.play_move(piece_id, location, PieceType::Queen)
+    }
+
+    /// How many times the current position has come up, counting this time. Positions are
+    /// the same when the same pieces stand on the same squares with the same side to move and
+    /// the same castling and en passant rights.
+    pub fn repetition_count(&self) -> usize {
+        let key = self.zobrist_key();
+        // a capture or pawn move means no earlier position can come back
+        let reversible = self.halfmove_clock() as usize;
+        1 + self
+            .move_history
+            .iter()
+            .rev()
+            .take(reversible)
+            .filter(|record| record.key_before == Some(key))
+            .count()
+    }
+
+    /// Whether the current position has come up three times, letting either player claim
+    /// a draw.
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+
+    /// Whether neither side has the pieces left to checkmate: bare kings, a single minor piece,
+    /// or only bishops that all run on squares of one color.
+    pub fn has_insufficient_material(&self) -> bool {
+        let pieces: Vec<ChessPiece> = self
+            .get_pieces_in_play()
+            .into_iter()
+            .filter(|p| p.get_type() != PieceType::King)
+            .collect();
+
+        match pieces.as_slice() {
+            [] => true,
+            [piece] => matches!(piece.get_type(), PieceType::Knight | PieceType::Bishop),
+            _ => {
+                let square_color = |p: &ChessPiece| {
+                    let (x, y) = p.location.get_x_y();
+                    (x as u32 + y as u32) % 2
+                };
+                pieces.iter().all(|p| p.get_type() == PieceType::Bishop)
+                    && pieces
+                        .iter()
+                        .all(|p| square_color(p) == square_color(&pieces[0]))
+            }
+        }
+    }
+
+    /// Checks that `piece_id` belongs to the side to move, is still on the board and can
+    /// reach `location`.
+    fn validate_move(&self, piece_id: &Uuid, location: &PieceLocation) -> Result<(), MoveError> {
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+        let piece = self.find_piece(piece_id)?;
+        if piece.get_color() != self.current_turn {
+            return Err(MoveError::NotYourTurn(piece.get_color()));
+        }
+        if !piece.get_valid_moves().contains(location)
+            && !piece.get_valid_captures().contains(location)
+        {
+            return Err(MoveError::IllegalDestination(format!(
+                "{}{}",
+                piece.location, location
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn play_move(
+        &mut self,
+        piece_id: &Uuid,
+        location: &PieceLocation,
+        promote_to: PieceType,
+    ) -> Result<MovementLogEntry, MoveError> {
+        debug!("move_piece called with {:?} at {:?}", piece_id, location);
+        // a move made after the flag fell is too late
+        self.check_flag();
+        self.validate_move(piece_id, location)?;
+        self.pending_promotion = None;
+        self.redo_stack.clear();
+        if self.started.is_none() {
+            self.started = Some(Utc::now());
+        }
+        let piece = self.get_piece_by_id_copy(piece_id);
+        debug!("valid moves: {:?}", piece.get_valid_moves());
+
+        let player_id = if piece.get_color() == PieceColor::White {
+            self.get_white_player_id()
+        } else {
+            self.get_black_player_id()
+        };
+        let mut movement_entry = MovementLogEntry::new(
+            player_id,
+            piece_id.clone(),
+            piece.location.clone(),
+            location.clone(),
+        );
+        movement_entry.disambiguation(self.get_disambiguation(&piece, location));
+        let pieces_before = self.pieces.clone();
+        let en_passant_before = self.en_passant_target.clone();
+        let key_before = self.zobrist_key();
+        let can_move = piece.get_valid_moves().contains(location);
+        let can_capture = piece.get_valid_captures().contains(location);
+        let is_king = piece.get_type() == PieceType::King;
+        let was_in_check = match piece.get_color() {
+            PieceColor::White => self.get_white_king_state(),
+            PieceColor::Black => self.get_black_king_state(),
+        } == KingState::InCheck;
+        if can_capture {
+            let capture_location = if self.is_en_passant_capture(&piece, location) {
+                movement_entry.en_passant();
+                self.en_passant_victim().unwrap().location
+            } else {
+                location.clone()
+            };
+            self.handle_capture(capture_location, &mut movement_entry);
+        }
+
+        self.en_passant_target = if piece.get_type() == PieceType::Pawn
+            && (can_move || can_capture)
+            && piece.location.get_rank().abs_diff(location.get_rank()) == 2
+        {
+            let rank_offset = if piece.get_color() == PieceColor::White {
+                1
+            } else {
+                -1
+            };
+            piece.location.offset(0, rank_offset)
+        } else {
+            None
+        };
+
+        if can_move || can_capture {
+            self.handle_move(&piece.id, location.clone());
+            self.handle_promotion(&piece.id, promote_to, &mut movement_entry);
+        }
+
+        if is_king {
+            self.handle_king_castle(piece_id, &location.clone(), &mut movement_entry);
+        }
+
+        let changed_pieces: Vec<ChessPiece> = pieces_before
+            .into_iter()
+            .filter(|before| !self.pieces.contains(before))
+            .collect();
+        // the squares pieces left or landed on, including a castling rook's, and the en
+        // passant squares, since pawns next to them gain or lose a capture
+        let mut changed_squares: Vec<PieceLocation> = en_passant_before
+            .iter()
+            .chain(&self.en_passant_target)
+            .cloned()
+            .collect();
+        for before in &changed_pieces {
+            changed_squares.push(before.location.clone());
+            changed_squares.push(self.get_piece_by_id_copy(&before.id).location);
+        }
+        let changed_ids: Vec<Uuid> = changed_pieces.iter().map(|p| p.id).collect();
+
+        self.move_history.push(MoveRecord {
+            pieces_before: changed_pieces,
+            en_passant_target: en_passant_before,
+            key_before: Some(key_before),
+        });
+        self.pending_takeback = None;
+        // moving instead of answering a draw offer declines it
+        if self.pending_draw_offer == Some(piece.get_color().opposite()) {
+            self.pending_draw_offer = None;
+        }
+        self.change_turn();
+        if was_in_check {
+            // the moves of every piece were cut down to answering the check, so they all
+            // need working out again
+            self.calculate_valid_moves();
+        } else {
+            self.recalculate_valid_moves(&changed_ids, &changed_squares);
+        }
+
+        let opponent_king_state = match piece.get_color() {
+            PieceColor::White => self.get_black_king_state(),
+            PieceColor::Black => self.get_white_king_state(),
+        };
+        match opponent_king_state {
+            KingState::InCheck => {
+                movement_entry.opponent_king_in_check();
+            }
+            KingState::InCheckMate => {
+                movement_entry.opponent_king_in_checkmate();
+            }
+            _ => {}
+        }
+
+        // a timed match charges the time to the clock, otherwise the move took as long as
+        // it has been since the last one
+        let now = Utc::now();
+        let spent = match self.clock.as_mut() {
+            Some(clock) => clock.press(piece.get_color(), now),
+            None => self
+                .movement_log
+                .last()
+                .and_then(|e| e.get_timestamp())
+                .or(self.started)
+                .map_or(0, |since| (now - since).num_milliseconds().max(0) as u64),
+        };
+        movement_entry.timestamp(now).time_span(spent as u32);
+
+        let final_entry = MovementLogger::add_entry_to_match(self, movement_entry);
+        info!("Entry logged: {}", final_entry);
+        self.update_result();
+
+        Ok(final_entry)
+    }
+
+    /// Works out the start square hint SAN needs when another piece of the same type and
+    /// color could also legally move to `location`: the file if that tells them apart,
+    /// otherwise the rank, otherwise both.
+    fn get_disambiguation(&self, piece: &ChessPiece, location: &PieceLocation) -> String {
+        if piece.get_type() == PieceType::Pawn || piece.get_type() == PieceType::King {
+            return "".to_string();
+        }
+
+        let others: Vec<PieceLocation> = self
+            .legal_moves_for_color(&piece.get_color())
+            .into_iter()
+            .filter(|m| m.to == *location && m.piece_id != piece.id)
+            .map(|m| self.get_piece_by_id_copy(&m.piece_id))
+            .filter(|p| p.get_type() == piece.get_type())
+            .map(|p| p.location)
+            .collect();
+        if others.is_empty() {
+            return "".to_string();
+        }
+
+        let file = piece.location.get_file();
+        let rank = piece.location.get_rank();
+        if others.iter().all(|l| l.get_file() != file) {
+            file
+        } else if others.iter().all(|l| l.get_rank() != rank) {
+            rank.to_string()
+        } else {
+            piece.location.to_string()
+        }
+    }
+
+    /// Returns the SAN for a legal move without playing it, including disambiguation and
+    /// the check or mate suffix, by playing it on a copy of the match.
+    pub fn san_of(&self, chess_move: &Move) -> Result<String, MoveError> {
+        if !self.legal_moves().contains(chess_move) {
+            return Err(MoveError::IllegalMove(chess_move.to_string()));
+        }
+
+        let mut match_copy = self.copy();
+        match_copy.apply_move(chess_move);
+        let entry = match_copy.movement_log.last().unwrap();
+
+        Ok(entry.get_notation())
+    }
+
+    /// Returns the SAN of each move in a line starting from the current position, such as the
+    /// principal variation of a `SearchResult`.
+    pub fn line_to_san(&self, moves: &[Move]) -> Result<Vec<String>, MoveError> {
+        let mut match_copy = self.copy();
+        let mut line = Vec::with_capacity(moves.len());
+        for chess_move in moves {
+            line.push(match_copy.san_of(chess_move)?);
+            match_copy.apply_move(chess_move);
+        }
+
+        Ok(line)
+    }
+
+    /// Assembles the full state of the match into a single serializable payload for clients.
+    pub fn snapshot(&self) -> MatchSnapshot {
+        let board = self.snapshot_board();
+        let (_, side_to_move) = self.get_current_turn_and_color();
+
+        MatchSnapshot {
+            match_id: self.get_match_id(),
+            board,
+            side_to_move,
+            castling_rights: self.castling_rights(),
+            en_passant_target: self.get_en_passant_target(),
+            legal_moves: self.legal_moves_sorted(),
+            white_king_state: self.get_white_king_state(),
+            black_king_state: self.get_black_king_state(),
+            result: self.result(),
+            move_history: self
+                .get_log_entries()
+                .iter()
+                .map(|e| e.get_notation())
+                .collect(),
+            material_balance: self.material_balance(),
+        }
+    }
+
+    /// Describes the position for drawing a diagram: the pieces, the last move and every
+    /// attack on the side to move's pieces.
+    pub fn to_diagram(&self) -> BoardDiagram {
+        let mut highlights = Vec::new();
+        let mut arrows = Vec::new();
+        if let Some(last) = self.movement_log.last() {
+            highlights.push(last.get_start_location());
+            highlights.push(last.get_end_location());
+            arrows.push(DiagramArrow {
+                from: last.get_start_location(),
+                to: last.get_end_location(),
+                kind: ArrowKind::LastMove,
+            });
+        }
+
+        let (_, color) = self.get_current_turn_and_color();
+        for piece in self.get_player_pieces_in_play(&color) {
+            let attackers =
+                MatchHelpers::get_attackers_of_location(self, &piece.location, &color.opposite());
+            if piece.get_type() == PieceType::King && !attackers.is_empty() {
+                highlights.push(piece.location.clone());
+            }
+            for attacker in attackers {
+                arrows.push(DiagramArrow {
+                    from: attacker.location,
+                    to: piece.location.clone(),
+                    kind: ArrowKind::Attack,
+                });
+            }
+        }
+
+        BoardDiagram {
+            match_id: self.get_match_id(),
+            squares: self.snapshot_board(),
+            highlights,
+            arrows,
+        }
+    }
+
+    /// The board as a 64 character string for callers that can't use the Rust types. Squares
+    /// run from a8 to h8, then a7 to h7 and so on down to h1. Each piece is its letter,
+    /// uppercase for white and lowercase for black, and empty squares are `.`.
+    pub fn board_string(&self) -> String {
+        (1..=8u32)
+            .rev()
+            .flat_map(|rank| {
+                FILES.iter().map(move |file| {
+                    self.get_piece_at_location(PieceLocation::new(file.to_string(), rank))
+                        .map(|p| p.get_ascii_text())
+                        .unwrap_or('.')
+                })
+            })
+            .collect()
+    }
+
+    /// The board as rows of pieces, ranks from 8 down to 1 and files a to h.
+    fn snapshot_board(&self) -> Vec<Vec<Option<SnapshotPiece>>> {
+        (1..=8u32)
+            .rev()
+            .map(|rank| {
+                FILES
+                    .iter()
+                    .map(|file| {
+                        self.get_piece_at_location(PieceLocation::new(file.to_string(), rank))
+                            .map(|p| SnapshotPiece {
+                                id: p.id,
+                                piece_type: p.get_type(),
+                                color: p.get_color(),
+                            })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Takes back the most recently played move, restoring every piece it touched, and
+    /// returns its log entry. Returns `None` when no moves have been played, or when the
+    /// match ended with a recorded result such as a resignation, which taking back a move
+    /// would wipe out.
+    pub fn undo_last_move(&mut self) -> Option<MovementLogEntry> {
+        if self.result.is_recorded() {
+            return None;
+        }
+        let record = self.move_history.pop()?;
+        for before in record.pieces_before {
+            // captured pieces are dropped from the board on recalculation, so they may
+            // need to be put back rather than replaced
+            match self.pieces.iter_mut().find(|p| p.id == before.id) {
+                Some(piece) => *piece = before,
+                None => self.pieces.push(before),
+            }
+        }
+        self.en_passant_target = record.en_passant_target;
+        self.change_turn();
+        self.calculate_valid_moves();
+
+        let entry = self.movement_log.pop();
+        self.update_result();
+        entry
+    }
+
+    /// Takes back the last move like `undo_last_move`, keeping it so `redo_move` can play it
+    /// again.
+    pub fn undo_move(&mut self) -> Option<MovementLogEntry> {
+        let entry = self.undo_last_move()?;
+        self.redo_stack.push(entry.clone());
+        Some(entry)
+    }
+
+    /// Plays the most recently undone move again and returns its new log entry. Returns
+    /// `None` when nothing was undone, or when a different move was played since.
+    pub fn redo_move(&mut self) -> Option<MovementLogEntry> {
+        let entry = self.redo_stack.pop()?;
+        let remaining = std::mem::take(&mut self.redo_stack);
+        let redone = self
+            .play_move(
+                &entry.get_piece_id(),
+                &entry.get_end_location(),
+                entry.get_promoted_to().unwrap_or(PieceType::Queen),
+            )
+            .ok();
+        self.redo_stack = remaining;
+
+        redone
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// The match as it stood after the first `ply` moves, e.g. 0 for the starting position,
+    /// for stepping through a game. Returns `None` when fewer moves were played, or when the
+    /// earlier positions can't be reconstructed because the save lacks the moves' history.
+    pub fn position_at_move(&self, ply: usize) -> Option<ChessMatch> {
+        if ply > self.movement_log.len() {
+            return None;
+        }
+        let mut position = self.copy();
+        // the earlier positions came before any resignation or agreed draw
+        if ply < self.movement_log.len() {
+            position.record_result(MatchResult::InProgress);
+        }
+        while position.movement_log.len() > ply {
+            position.undo_last_move()?;
+        }
+        position.redo_stack.clear();
+
+        Some(position)
+    }
+
+    /// The color `player_id` plays in this match.
+    fn player_color(&self, player_id: &Uuid) -> Result<PieceColor, MoveError> {
+        if *player_id == self.white_player {
+            Ok(PieceColor::White)
+        } else if *player_id == self.black_player {
+            Ok(PieceColor::Black)
+        } else {
+            Err(MoveError::NotAPlayer(*player_id))
+        }
+    }
+
+    /// Ends the match as lost on time if the side to move has run out of time. Returns
+    /// whether the flag has fallen.
+    pub fn check_flag(&mut self) -> bool {
+        if self.is_game_over() {
+            return false;
+        }
+        let flagged = self
+            .clock
+            .as_ref()
+            .and_then(|clock| clock.flagged(Utc::now()));
+        match flagged {
+            Some(color) => {
+                self.record_result(match color {
+                    PieceColor::White => MatchResult::BlackWonTimeout,
+                    PieceColor::Black => MatchResult::WhiteWonTimeout,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ends the match with `player_id` resigning, so the opponent wins.
+    pub fn resign(&mut self, player_id: &Uuid) -> Result<MatchResult, MoveError> {
+        let color = self.player_color(player_id)?;
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+
+        let result = match color {
+            PieceColor::White => MatchResult::BlackWonResignation,
+            PieceColor::Black => MatchResult::WhiteWonResignation,
+        };
+        self.pending_draw_offer = None;
+        self.record_result(result);
+        Ok(result)
+    }
+
+    /// Offers the opponent a draw. The offer stands until the opponent answers it through
+    /// `respond_to_draw` or plays a move instead.
+    pub fn offer_draw(&mut self, player_id: &Uuid) -> Result<(), MoveError> {
+        let color = self.player_color(player_id)?;
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+
+        self.pending_draw_offer = Some(color);
+        Ok(())
+    }
+
+    pub fn get_pending_draw_offer(&self) -> Option<PieceColor> {
+        self.pending_draw_offer
+    }
+
+    /// Answers the opponent's draw offer, ending the match in a draw on acceptance. Returns
+    /// whether the match was drawn.
+    pub fn respond_to_draw(&mut self, player_id: &Uuid, accept: bool) -> Result<bool, MoveError> {
+        let color = self.player_color(player_id)?;
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+        if self.pending_draw_offer != Some(color.opposite()) {
+            return Err(MoveError::IllegalMove(
+                "there is no draw offer to answer".to_string(),
+            ));
+        }
+
+        self.pending_draw_offer = None;
+        if accept {
+            self.record_result(MatchResult::Draw {
+                reason: DrawReason::Agreement,
+            });
+        }
+        Ok(accept)
+    }
+
+    /// Asks the opponent for permission to take back `by`'s last move. The takeback only
+    /// happens once the opponent accepts it through `respond_takeback`. Fails when the match
+    /// is over or `by` has no move that can be taken back.
+    pub fn request_takeback(&mut self, by: PieceColor) -> Result<(), MoveError> {
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+        if self.plies_to_take_back(by).is_none() {
+            return Err(MoveError::IllegalMove(
+                "there is no move to take back".to_string(),
+            ));
+        }
+
+        self.pending_takeback = Some(by);
+        Ok(())
+    }
+
+    pub fn get_pending_takeback(&self) -> Option<PieceColor> {
+        self.pending_takeback
+    }
+
+    /// Answers a pending takeback request. On acceptance moves are undone back to and
+    /// including the requester's last move, which takes back the opponent's reply too if one
+    /// was already played. Returns whether any move was taken back.
+    pub fn respond_takeback(&mut self, accept: bool) -> bool {
+        let requested_by = match self.pending_takeback.take() {
+            Some(color) => color,
+            None => return false,
+        };
+        if !accept || self.is_game_over() {
+            return false;
+        }
+        let plies = match self.plies_to_take_back(requested_by) {
+            Some(plies) => plies,
+            None => return false,
+        };
+
+        for _ in 0..plies {
+            self.undo_last_move();
+        }
+        true
+    }
+
+    /// How many moves have to be undone to take back `color`'s last move, or `None` when
+    /// `color` hasn't played a move that can be undone.
+    fn plies_to_take_back(&self, color: PieceColor) -> Option<usize> {
+        let (_, to_move) = self.get_current_turn_and_color();
+        let plies = if to_move == color { 2 } else { 1 };
+        if self.move_history.len() < plies {
+            return None;
+        }
+
+        Some(plies)
+    }
+
+    fn handle_capture(&mut self, location: PieceLocation, movement_entry: &mut MovementLogEntry) {
+        let piece = self.get_piece_at_location_mut(location).unwrap();
+        piece.set_captured();
+        movement_entry.captured(piece.id.clone());
+    }
+
+    fn handle_promotion(
+        &mut self,
+        piece_id: &Uuid,
+        promote_to: PieceType,
+        movement_entry: &mut MovementLogEntry,
+    ) {
+        // the piece type has to change before the valid moves are recalculated, so that
+        // the promoted piece's attacks count towards check and mate detection
+        let piece = self.get_piece_by_id(piece_id);
+        if piece.can_be_promoted() {
+            piece.promote(promote_to);
+            movement_entry.promoted(promote_to);
+        }
+    }
+
+    fn handle_move(&mut self, piece_id: &Uuid, location: PieceLocation) {
+        let piece = self.get_piece_by_id(piece_id);
+        piece.set_moved(location);
+    }
+
+    pub fn change_turn(&mut self) -> u32 {
+        self.current_turn = self.current_turn.opposite();
+
+        debug!("changed turn to: {:?}", self.current_turn);
+
+        let (turn, _) = self.get_current_turn_and_color();
+        turn
+    }
+
+    pub fn add_log_entry(&mut self, entry: MovementLogEntry) {
+        self.movement_log.push(entry);
+    }
+
+    pub fn get_log_entries(&self) -> Vec<MovementLogEntry> {
         self.movement_log.clone()
     }
 
-    fn generate_pieces() -> Vec<ChessPiece> {
-        let mut result = Vec::new();
-        let pawn_ranks: HashMap<PieceColor, u32> =
-            HashMap::from([(PieceColor::White, 2), (PieceColor::Black, 7)]);
-        let other_ranks: HashMap<PieceColor, u32> =
-            HashMap::from([(PieceColor::White, 1), (PieceColor::Black, 8)]);
+    pub(crate) fn log_entry_mut(&mut self, index: usize) -> Option<&mut MovementLogEntry> {
+        self.movement_log.get_mut(index)
+    }
+
+    /// The squares the last move went from and to, the king's for a castle.
+    pub fn last_move_squares(&self) -> Option<(PieceLocation, PieceLocation)> {
+        self.movement_log
+            .last()
+            .map(|entry| (entry.get_start_location(), entry.get_end_location()))
+    }
+
+    /// Names the opening played so far, as an ECO code and opening name, using the longest
+    /// matching line from the bundled opening table.
+    pub fn eco_classification(&self) -> Option<(String, String)> {
+        let moves: Vec<String> = self.movement_log.iter().map(|e| e.get_notation()).collect();
+        eco::classify(&moves)
+    }
+
+    /// Counts how many times each square received a piece over the course of the match,
+    /// based on the movement log. Castling counts for both the king's and the rook's squares.
+    pub fn square_visit_counts(&self) -> HashMap<PieceLocation, u32> {
+        let mut counts: HashMap<PieceLocation, u32> = HashMap::new();
+        for entry in &self.movement_log {
+            let end_location = entry.get_end_location();
+            let rook_file = match entry.get_castle_side() {
+                Some(CastleSide::KingSide) => Some("f"),
+                Some(CastleSide::QueenSide) => Some("d"),
+                None => None,
+            };
+            if let Some(file) = rook_file {
+                let rook_location = PieceLocation::new(file.to_string(), end_location.get_rank());
+                *counts.entry(rook_location).or_insert(0) += 1;
+            }
+            *counts.entry(end_location).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    fn generate_pieces() -> Vec<ChessPiece> {
+        let mut result = Vec::new();
+        let pawn_ranks: HashMap<PieceColor, u32> =
+            HashMap::from([(PieceColor::White, 2), (PieceColor::Black, 7)]);
+        let other_ranks: HashMap<PieceColor, u32> =
+            HashMap::from([(PieceColor::White, 1), (PieceColor::Black, 8)]);
+
+        fn get_location(file: usize, rank: u32) -> PieceLocation {
+            PieceLocation::new(FILES.get(file).unwrap().to_string(), rank)
+        }
+
+        for color in PieceColor::iter() {
+            // generate pawns
+            let mut rank = pawn_ranks.get(&color).unwrap();
+            for f in FILES {
+                let location =
+                    PieceLocation::new_from_string(format!("{}{}", f, rank).as_str()).unwrap();
+                let piece = ChessPiece::new(PieceType::Pawn, color.clone(), location, 1);
+                result.push(piece);
+            }
+
+            // generate rooks
+            rank = other_ranks.get(&color).unwrap();
+            let rook_positions = vec![0, 7];
+            for p in rook_positions {
+                let location = get_location(p, *rank);
+                let rook = ChessPiece::new(PieceType::Rook, color.clone(), location, 5);
+                result.push(rook);
+            }
+
+            // generate knights
+            let knight_positions = vec![1, 6];
+            for p in knight_positions {
+                let location = get_location(p, *rank);
+                let knight = ChessPiece::new(PieceType::Knight, color.clone(), location, 3);
+                result.push(knight);
+            }
+
+            // generate bishops
+            let bishop_positions = vec![2, 5];
+            for p in bishop_positions {
+                let location = get_location(p, *rank);
+                let bishop = ChessPiece::new(PieceType::Bishop, color.clone(), location, 3);
+                result.push(bishop);
+            }
+
+            // generate queen
+            let queen_position = 3;
+            let queen_location = get_location(queen_position, *rank);
+            let queen = ChessPiece::new(PieceType::Queen, color.clone(), queen_location, 9);
+
+            // generate king
+            let king_position = 4;
+            let king_location = get_location(king_position, *rank);
+            let king = ChessPiece::new(PieceType::King, color.clone(), king_location, 0);
+
+            result.push(queen);
+            result.push(king);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ai::Engine, movement_log::PlayerTimes, opening_book::BookLine};
+
+    /// Strips the version from a save, making it look like one from before versioning.
+    fn unversioned(json: String) -> String {
+        let mut save: serde_json::Value = serde_json::from_str(&json).unwrap();
+        save.as_object_mut().unwrap().remove("schema_version");
+        save.to_string()
+    }
+
+    #[test]
+    fn test_pieces_generate() {
+        let chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+
+        assert_eq!(32, chess_match.pieces.len());
+    }
+
+    #[test]
+    fn test_move_piece_and_update_valid_moves() {
+        env_logger::init();
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+
+        let piece = chess_match
+            .get_piece_at_location(PieceLocation::new_from_string("a2").unwrap())
+            .unwrap();
+        chess_match
+            .move_piece(&piece.id, &PieceLocation::new_from_string("a4").unwrap())
+            .unwrap();
+        let current_turn = chess_match.change_turn();
+        chess_match.calculate_valid_moves();
+        let piece = chess_match
+            .get_piece_at_location(PieceLocation::new_from_string("a4").unwrap())
+            .unwrap();
+        assert_eq!(0, current_turn);
+        assert_eq!(1, piece.get_valid_moves().len());
+    }
+
+    #[test]
+    fn test_promotion_delivering_checkmate_is_logged_as_mate() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let pawn = ChessPiece::new(PieceType::Pawn, PieceColor::White, location("e7"), 1);
+        let pawn_id = pawn.id;
+        chess_match.set_pieces(vec![
+            pawn,
+            ChessPiece::new(PieceType::King, PieceColor::White, location("a1"), 0),
+            ChessPiece::new(PieceType::King, PieceColor::Black, location("h8"), 0),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, location("g7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, location("h7"), 1),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        chess_match.move_piece(&pawn_id, &location("e8")).unwrap();
+
+        assert_eq!(
+            PieceType::Queen,
+            chess_match.get_piece_by_id_copy(&pawn_id).get_type()
+        );
+        assert_eq!(KingState::InCheckMate, chess_match.get_black_king_state());
+        assert_eq!("e8=Q#", chess_match.get_log_entries()[0].get_notation());
+    }
+
+    #[test]
+    fn test_en_passant_capture_removes_victim() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let play = |chess_match: &mut ChessMatch, from: &str, to: &str| {
+            let piece = chess_match.get_piece_at_location(location(from)).unwrap();
+            chess_match.move_piece(&piece.id, &location(to)).unwrap();
+        };
+        play(&mut chess_match, "e2", "e4");
+        play(&mut chess_match, "a7", "a6");
+        play(&mut chess_match, "e4", "e5");
+        play(&mut chess_match, "d7", "d5");
+
+        let victim = chess_match.en_passant_victim().unwrap();
+        assert_eq!(Some(location("d6")), chess_match.get_en_passant_target());
+        assert_eq!(location("d5"), victim.location);
+
+        play(&mut chess_match, "e5", "d6");
+
+        assert_eq!(None, chess_match.get_piece_at_location(location("d5")));
+        assert_eq!(None, chess_match.en_passant_victim());
+        let entry = &chess_match.get_log_entries()[4];
+        assert_eq!("exd6", entry.get_notation());
+        assert!(entry.is_en_passant());
+        assert!(!chess_match.get_log_entries()[3].is_en_passant());
+    }
+
+    #[test]
+    fn test_forks_for_finds_knight_fork() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let knight = ChessPiece::new(PieceType::Knight, PieceColor::White, location("b5"), 3);
+        let knight_id = knight.id;
+        chess_match.set_pieces(vec![
+            knight,
+            ChessPiece::new(PieceType::King, PieceColor::White, location("e1"), 0),
+            ChessPiece::new(PieceType::King, PieceColor::Black, location("e8"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::Black, location("a8"), 5),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        let forks = chess_match.forks_for(PieceColor::White);
+
+        assert_eq!(vec![(knight_id, vec![location("c7")])], forks);
+        assert!(chess_match.forks_for(PieceColor::Black).is_empty());
+    }
+
+    #[test]
+    fn test_set_position_uci() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+
+        chess_match
+            .set_position_uci("position startpos moves e2e4 e7e5 g1f3")
+            .unwrap();
+
+        let notation: Vec<String> = chess_match
+            .get_log_entries()
+            .iter()
+            .map(|e| e.get_notation())
+            .collect();
+        assert_eq!(vec!["e4", "e5", "Nf3"], notation);
+        assert_eq!(
+            PieceColor::Black,
+            chess_match.get_current_turn_and_color().1
+        );
+
+        chess_match.set_position_uci("startpos").unwrap();
+        assert!(chess_match.get_log_entries().is_empty());
+
+        assert_eq!(
+            Err(MoveError::IllegalMove("e2e5".to_string())),
+            chess_match.set_position_uci("startpos moves e2e5")
+        );
+    }
+
+    #[test]
+    fn test_square_visit_counts() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match
+            .set_position_uci("startpos moves g1f3 g8f6 f3g1 f6g8 g1f3")
+            .unwrap();
+
+        let counts = chess_match.square_visit_counts();
+
+        assert_eq!(
+            Some(&2),
+            counts.get(&PieceLocation::new_from_string("f3").unwrap())
+        );
+        assert_eq!(
+            Some(&1),
+            counts.get(&PieceLocation::new_from_string("g1").unwrap())
+        );
+        assert_eq!(
+            None,
+            counts.get(&PieceLocation::new_from_string("e4").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_book_moves_of_the_current_position() {
+        let book = OpeningBook::from_lines(&[
+            BookLine {
+                moves: "e2e4 e7e5 g1f3".to_string(),
+                weight: 3,
+            },
+            BookLine {
+                moves: "e2e4 c7c5".to_string(),
+                weight: 5,
+            },
+        ])
+        .unwrap();
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        assert!(chess_match.is_in_opening_book(&book));
+
+        chess_match.apply_uci_move("e2e4").unwrap();
+        let moves: Vec<(String, u32)> = chess_match
+            .book_moves(&book)
+            .into_iter()
+            .map(|(m, weight)| (m.to_string(), weight))
+            .collect();
+        assert_eq!(
+            vec![("c7c5".to_string(), 5), ("e7e5".to_string(), 3)],
+            moves
+        );
+
+        chess_match.apply_uci_move("e7e5").unwrap();
+        chess_match.apply_uci_move("g1f3").unwrap();
+        assert!(!chess_match.is_in_opening_book(&book));
+        assert!(chess_match.book_moves(&book).is_empty());
+    }
+
+    #[test]
+    fn test_takeback_after_opponent_reply_undoes_both_moves() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match
+            .set_position_uci("startpos moves e2e4 d7d5 e4d5 d8d5")
+            .unwrap();
+
+        chess_match.request_takeback(PieceColor::White).unwrap();
+        assert!(!chess_match.respond_takeback(false));
+        assert_eq!(4, chess_match.get_log_entries().len());
+
+        chess_match.request_takeback(PieceColor::White).unwrap();
+        assert!(chess_match.respond_takeback(true));
+
+        // white's capture and black's recapture are both taken back
+        let pawn = chess_match
+            .get_piece_at_location(PieceLocation::new_from_string("d5").unwrap())
+            .unwrap();
+        assert_eq!(PieceColor::Black, pawn.get_color());
+        assert_eq!(PieceType::Pawn, pawn.get_type());
+        assert_eq!(
+            PieceColor::White,
+            chess_match.get_current_turn_and_color().1
+        );
+        assert_eq!(None, chess_match.get_pending_takeback());
+        assert_eq!(2, chess_match.get_log_entries().len());
+    }
+
+    #[test]
+    fn test_takeback_only_undoes_the_requesters_moves() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match.set_position_uci("startpos moves e2e4").unwrap();
+
+        // black hasn't moved yet, so white's move is not black's to take back
+        assert!(matches!(
+            chess_match.request_takeback(PieceColor::Black),
+            Err(MoveError::IllegalMove(_))
+        ));
+        assert_eq!(None, chess_match.get_pending_takeback());
+        assert_eq!(1, chess_match.get_log_entries().len());
+
+        chess_match.apply_san("e5").unwrap();
+        chess_match.apply_san("Nf3").unwrap();
+        chess_match.request_takeback(PieceColor::Black).unwrap();
+        assert!(chess_match.respond_takeback(true));
+
+        // white's reply and black's move are taken back, white's first move stays
+        assert_eq!(1, chess_match.get_log_entries().len());
+        assert_eq!(
+            PieceColor::Black,
+            chess_match.get_current_turn_and_color().1
+        );
+    }
+
+    #[test]
+    fn test_takeback_is_refused_once_the_game_is_over() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5")
+            .unwrap();
+        chess_match.request_takeback(PieceColor::Black).unwrap();
+        chess_match.record_result(MatchResult::WhiteWonResignation);
+
+        // a request made before the game ended can't reopen it either
+        assert!(!chess_match.respond_takeback(true));
+        assert!(matches!(
+            chess_match.request_takeback(PieceColor::Black),
+            Err(MoveError::GameOver)
+        ));
+        assert_eq!(2, chess_match.get_log_entries().len());
+        assert_eq!(MatchResult::WhiteWonResignation, chess_match.result());
+    }
+
+    #[test]
+    fn test_san_of_candidate_moves() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match
+            .set_position_uci("startpos moves d2d4 d7d5 g1f3 g8f6")
+            .unwrap();
+        let find_move = |chess_match: &ChessMatch, from: &str, to: &str| {
+            let from = PieceLocation::new_from_string(from).unwrap();
+            let to = PieceLocation::new_from_string(to).unwrap();
+            chess_match
+                .legal_moves()
+                .into_iter()
+                .find(|m| m.from == from && m.to == to)
+                .unwrap()
+        };
+
+        let knight_to_d2 = find_move(&chess_match, "b1", "d2");
+        let knight_to_c3 = find_move(&chess_match, "b1", "c3");
+        assert_eq!(Ok("Nbd2".to_string()), chess_match.san_of(&knight_to_d2));
+        assert_eq!(Ok("Nc3".to_string()), chess_match.san_of(&knight_to_c3));
+        // the candidate was not played
+        assert_eq!(4, chess_match.get_log_entries().len());
+
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 d1h5 b8c6")
+            .unwrap();
+        let queen_takes_f7 = find_move(&chess_match, "h5", "f7");
+        assert_eq!(Ok("Qxf7+".to_string()), chess_match.san_of(&queen_takes_f7));
+    }
+
+    #[test]
+    fn test_rematch_swaps_colors() {
+        let white_player = Uuid::new_v4();
+        let black_player = Uuid::new_v4();
+        let chess_match = ChessMatch::new(white_player, black_player);
+
+        let rematch = chess_match.rematch();
+
+        assert_eq!(black_player, rematch.get_white_player_id());
+        assert_eq!(white_player, rematch.get_black_player_id());
+        assert_ne!(chess_match.get_match_id(), rematch.get_match_id());
+    }
+
+    #[test]
+    fn test_evasion_kind() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("e1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("h4"), 5),
+            ChessPiece::new(PieceType::Knight, PieceColor::White, loc("c4"), 3),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("h8"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::Black, loc("e5"), 5),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        assert_eq!(1, chess_match.checking_pieces().len());
+        let moves = chess_match.legal_moves();
+        let kind_of = |from: &str, to: &str| {
+            let m = moves
+                .iter()
+                .find(|m| m.from == loc(from) && m.to == loc(to))
+                .expect("move should be legal");
+            chess_match.evasion_kind(m)
+        };
+
+        assert_eq!(Some(EvasionKind::KingMove), kind_of("e1", "d1"));
+        assert_eq!(Some(EvasionKind::Block), kind_of("h4", "e4"));
+        assert_eq!(Some(EvasionKind::Block), kind_of("c4", "e3"));
+        assert_eq!(Some(EvasionKind::CaptureChecker), kind_of("c4", "e5"));
+        assert!(moves.iter().all(|m| chess_match.evasion_kind(m).is_some()));
+        assert!(!moves
+            .iter()
+            .any(|m| m.from == loc("a4") && m.to == loc("a5")));
+    }
+
+    #[test]
+    fn test_winning_captures() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("g1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("d1"), 5),
+            ChessPiece::new(PieceType::Knight, PieceColor::White, loc("c3"), 3),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("g8"), 0),
+            // defended by the pawn on e6, so taking it with the rook loses the exchange
+            ChessPiece::new(PieceType::Knight, PieceColor::Black, loc("d5"), 3),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("e6"), 1),
+            // undefended
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("b5"), 1),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        let knight_takes_d5 = chess_match
+            .legal_captures()
+            .into_iter()
+            .find(|m| m.from == loc("c3") && m.to == loc("d5"))
+            .unwrap();
+        let rook_takes_d5 = chess_match
+            .legal_captures()
+            .into_iter()
+            .find(|m| m.from == loc("d1") && m.to == loc("d5"))
+            .unwrap();
+        // NxN, exd5, Rxd5 nets a knight for a knight plus a pawn
+        assert_eq!(1, chess_match.see(&knight_takes_d5));
+        // RxN, exd5, Nxd5 gives up the rook for a knight and a pawn
+        assert_eq!(-1, chess_match.see(&rook_takes_d5));
+
+        let mut winning: Vec<(PieceLocation, PieceLocation)> = chess_match
+            .winning_captures()
+            .into_iter()
+            .map(|m| (m.from, m.to))
+            .collect();
+        winning.sort_by_key(|(_, to)| to.to_string());
+        assert_eq!(
+            vec![(loc("c3"), loc("b5")), (loc("c3"), loc("d5"))],
+            winning
+        );
+    }
+
+    #[test]
+    fn test_eco_classification() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 g1f3 b8c6 f1b5")
+            .unwrap();
+
+        assert_eq!(
+            Some(("C60".to_string(), "Ruy Lopez".to_string())),
+            chess_match.eco_classification()
+        );
+    }
+
+    #[test]
+    fn test_parse_move_formats() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+
+        for input in ["Nf3", "Ng1-f3", "g1f3", "Ngf3"] {
+            let m = chess_match.parse_move(input).unwrap();
+            assert_eq!((loc("g1"), loc("f3")), (m.from, m.to), "{}", input);
+        }
+        assert_eq!(loc("e4"), chess_match.parse_move("e4").unwrap().to);
+        assert_eq!(
+            Err(MoveError::IllegalMove("Nf4".to_string())),
+            chess_match.parse_move("Nf4")
+        );
+        assert!(matches!(
+            chess_match.parse_move("N-KB3"),
+            Err(MoveError::InvalidNotation(_))
+        ));
+
+        chess_match
+            .set_position_uci("startpos moves e2e4 d7d5 g1f3 g8f6 f1e2 b8c6")
+            .unwrap();
+        let capture = chess_match.parse_move("exd5").unwrap();
+        assert!(capture.capture);
+        let castle = chess_match.parse_move("0-0").unwrap();
+        assert_eq!((loc("e1"), loc("g1")), (castle.from, castle.to));
+    }
+
+    #[test]
+    fn test_apply_san() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        for san in ["e4", "d5", "exd5", "Nf6", "Nf3", "Nxd5", "Be2", "Nc6"] {
+            chess_match.apply_san(san).unwrap();
+        }
+        let entry = chess_match.apply_san("O-O").unwrap();
+        assert_eq!("O-O", entry.get_notation());
+        assert_eq!(Some(CastleSide::KingSide), entry.get_castle_side());
+        assert_eq!(
+            Err(MoveError::IllegalMove("Nb5".to_string())),
+            chess_match.apply_san("Nb5")
+        );
+
+        let (mut chess_match, pawn_id) = promotion_match();
+        let entry = chess_match.apply_san("b8=N").unwrap();
+        assert_eq!("b8=N", entry.get_notation());
+        assert_eq!(
+            PieceType::Knight,
+            chess_match.get_piece_by_id_copy(&pawn_id).get_type()
+        );
+
+        let (mut chess_match, pawn_id) = promotion_match();
+        chess_match.apply_uci_move("b7b8r").unwrap();
+        assert_eq!(
+            PieceType::Rook,
+            chess_match.get_piece_by_id_copy(&pawn_id).get_type()
+        );
+    }
+
+    #[test]
+    fn test_defenders_of_counts_x_rays() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("g1"), 0),
+            ChessPiece::new(PieceType::Knight, PieceColor::White, loc("d5"), 3),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("d2"), 5),
+            ChessPiece::new(PieceType::Queen, PieceColor::White, loc("d1"), 9),
+            ChessPiece::new(PieceType::Pawn, PieceColor::White, loc("e4"), 1),
+            ChessPiece::new(PieceType::Bishop, PieceColor::White, loc("h4"), 3),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("g8"), 0),
+            ChessPiece::new(PieceType::Bishop, PieceColor::Black, loc("b7"), 3),
+        ]);
+        chess_match.calculate_valid_moves();
+        let d5 = loc("d5");
+
+        let defenders: Vec<PieceLocation> = chess_match
+            .defenders_of(&d5, PieceColor::White)
+            .into_iter()
+            .map(|p| p.location)
+            .collect();
+        assert_eq!(3, defenders.len());
+        assert!(defenders.contains(&loc("e4")));
+        assert!(defenders.contains(&loc("d2")));
+        assert_eq!(loc("d1"), defenders[2]);
+
+        let attackers = chess_match.attackers_of(&d5, PieceColor::Black);
+        assert_eq!(1, attackers.len());
+        assert_eq!(loc("b7"), attackers[0].location);
+    }
+
+    fn promotion_match() -> (ChessMatch, Uuid) {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let pawn = ChessPiece::new(PieceType::Pawn, PieceColor::White, loc("b7"), 1);
+        let pawn_id = pawn.id;
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("e1"), 0),
+            pawn,
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("h7"), 0),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        (chess_match, pawn_id)
+    }
+
+    #[test]
+    fn test_promotion_policy_always_queen() {
+        let (mut chess_match, pawn_id) = promotion_match();
+        let b8 = PieceLocation::new_from_string("b8").unwrap();
+
+        assert!(chess_match.move_piece(&pawn_id, &b8).is_ok());
+        assert_eq!(
+            PieceType::Queen,
+            chess_match.get_piece_by_id_copy(&pawn_id).get_type()
+        );
+    }
+
+    #[test]
+    fn test_promotion_policy_prompt() {
+        let (mut chess_match, pawn_id) = promotion_match();
+        chess_match.set_promotion_policy(PromotionPolicy::Prompt);
+        let b8 = PieceLocation::new_from_string("b8").unwrap();
+
+        assert_eq!(
+            Err(MoveError::PromotionRequired("b7b8".to_string())),
+            chess_match.move_piece(&pawn_id, &b8)
+        );
+        assert!(chess_match.get_log_entries().is_empty());
+
+        chess_match
+            .move_piece_with_promotion(&pawn_id, &b8, PieceType::Knight)
+            .unwrap();
+        assert_eq!(
+            PieceType::Knight,
+            chess_match.get_piece_by_id_copy(&pawn_id).get_type()
+        );
+        assert_eq!("b8=N", chess_match.get_log_entries()[0].get_notation());
+    }
+
+    #[test]
+    fn test_promotion_policy_prompt_applies_to_every_move_call() {
+        let (mut chess_match, pawn_id) = promotion_match();
+        chess_match.set_promotion_policy(PromotionPolicy::Prompt);
+        // legal moves name the piece, so leave it out to play the pawn move as such
+        let unnamed = Move {
+            promotion: None,
+            ..chess_match
+                .legal_moves()
+                .into_iter()
+                .find(|m| m.piece_id == pawn_id)
+                .unwrap()
+        };
+
+        chess_match.apply_move(&unnamed);
+        assert!(chess_match.get_log_entries().is_empty());
+        assert_eq!(
+            PieceType::Pawn,
+            chess_match.get_piece_by_id_copy(&pawn_id).get_type()
+        );
+
+        chess_match.apply_uci_move("b7b8q").unwrap();
+        assert_eq!(
+            PieceType::Queen,
+            chess_match.get_piece_by_id_copy(&pawn_id).get_type()
+        );
+    }
+
+    #[test]
+    fn test_pause_and_resume_clock() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        assert_eq!(None, chess_match.time_remaining(PieceColor::White));
+        chess_match.set_time_control(TimeControl::blitz());
+        assert!(!chess_match.pause_clock());
+
+        chess_match.apply_uci_move("e2e4").unwrap();
+        assert!(chess_match.pause_clock());
+        assert!(chess_match.get_clock().unwrap().is_paused());
+        assert!(chess_match.resume_clock());
+        assert!(!chess_match.resume_clock());
+
+        // moving while paused picks the clock back up
+        chess_match.pause_clock();
+        chess_match.apply_uci_move("e7e5").unwrap();
+        assert!(!chess_match.get_clock().unwrap().is_paused());
+        assert_eq!(
+            Some(PieceColor::White),
+            chess_match.get_clock().unwrap().running()
+        );
+        assert!(chess_match.time_remaining(PieceColor::Black).unwrap() > 300_000);
+    }
+
+    #[test]
+    fn test_promote_pawn_completes_pending_promotion() {
+        let (mut chess_match, pawn_id) = promotion_match();
+        chess_match.set_promotion_policy(PromotionPolicy::Prompt);
+        let b8 = PieceLocation::new_from_string("b8").unwrap();
+
+        assert!(chess_match.promote_pawn(&pawn_id, PieceType::Rook).is_err());
+        assert!(chess_match.move_piece(&pawn_id, &b8).is_err());
+        assert_eq!(
+            Some(PendingPromotion {
+                piece_id: pawn_id,
+                from: PieceLocation::new_from_string("b7").unwrap(),
+                to: b8.clone(),
+            }),
+            chess_match.get_pending_promotion()
+        );
+        assert!(chess_match.promote_pawn(&pawn_id, PieceType::King).is_err());
+
+        chess_match.promote_pawn(&pawn_id, PieceType::Rook).unwrap();
+        assert_eq!(None, chess_match.get_pending_promotion());
+        assert_eq!(
+            PieceType::Rook,
+            chess_match.get_piece_by_id_copy(&pawn_id).get_type()
+        );
+        assert_eq!(b8, chess_match.get_piece_by_id_copy(&pawn_id).location);
+        assert!(chess_match.get_log_entries()[0]
+            .get_notation()
+            .starts_with("b8=R"));
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let start = ChessMatch::from_fen(fen::STARTING_FEN).unwrap();
+        assert_eq!(fen::STARTING_FEN, start.to_fen());
+        assert_eq!(20, start.legal_moves().len());
+
+        let fen = "r3k2r/8/8/8/3pP3/8/8/R3K2R b Kq e3 5 20";
+        let chess_match = ChessMatch::from_fen(fen).unwrap();
+        assert_eq!(fen, chess_match.to_fen());
+        assert_eq!(
+            CastlingRights {
+                white_king_side: true,
+                white_queen_side: false,
+                black_king_side: false,
+                black_queen_side: true,
+            },
+            chess_match.castling_rights()
+        );
+        let legal: Vec<String> = chess_match
+            .legal_moves()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        assert!(legal.contains(&"d4e3".to_string()));
+        assert!(legal.contains(&"e8c8".to_string()));
+        assert!(!legal.contains(&"e8g8".to_string()));
+    }
+
+    #[test]
+    fn test_fen_tracks_move_counters() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_position_uci("startpos moves e2e4").unwrap();
+        assert_eq!(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            chess_match.to_fen()
+        );
+
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 g1f3 b8c6 f1c4")
+            .unwrap();
+        assert_eq!(3, chess_match.halfmove_clock());
+        assert_eq!(3, chess_match.fullmove_number());
+
+        chess_match
+            .set_position_uci("position fen 4k3/8/8/8/8/8/4P3/4K3 w - - 12 40 moves e1d1 e8d8")
+            .unwrap();
+        assert_eq!("3k4/8/8/8/8/8/4P3/3K4 w - - 14 41", chess_match.to_fen());
+    }
+
+    #[test]
+    fn test_to_pgn() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 f1c4 b8c6 d1h5 g8f6 h5f7")
+            .unwrap();
+        let pgn = chess_match.to_pgn();
+
+        assert!(pgn.starts_with("[Event \"Casual Game\"]\n[Site \"?\"]\n"));
+        assert!(pgn.contains(&format!(
+            "[White \"{}\"]",
+            chess_match.get_white_player_id()
+        )));
+        assert!(pgn.contains("[Result \"1-0\"]"));
+        assert!(!pgn.contains("[Date \"????.??.??\"]"));
+        assert!(!pgn.contains("[FEN"));
+        assert!(pgn.ends_with("\n\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"));
+    }
+
+    #[test]
+    fn test_to_pgn_from_starting_fen_has_no_set_up() {
+        let mut from_fen = ChessMatch::from_fen(fen::STARTING_FEN).unwrap();
+        let mut standard = ChessMatch::new(
+            from_fen.get_white_player_id(),
+            from_fen.get_black_player_id(),
+        );
+        standard.calculate_valid_moves();
+        for uci_move in ["e2e4", "e7e5", "g1f3"] {
+            from_fen.apply_uci_move(uci_move).unwrap();
+            standard.apply_uci_move(uci_move).unwrap();
+        }
+
+        let pgn = from_fen.to_pgn();
+        assert!(!pgn.contains("[SetUp"));
+        assert!(!pgn.contains("[FEN"));
+        assert_eq!(standard.to_pgn(), pgn);
+    }
+
+    #[test]
+    fn test_to_pgn_from_fen_and_wrapping() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("fen 4k3/8/8/8/8/8/4P3/4K3 b - - 0 20 moves e8d8 e1d1")
+            .unwrap();
+        let pgn = chess_match.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 20\"]"));
+        assert!(pgn.ends_with("\n20... Kd8 21. Kd1 *\n"));
+        assert_eq!(
+            vec!["20... Kd8", "21. Kd1"],
+            MovementLogger::get_move_rows(&chess_match)
+        );
+
+        chess_match.reset();
+        for _ in 0..10 {
+            for uci_move in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                chess_match.apply_uci_move(uci_move).unwrap();
+            }
+        }
+        let movetext = MovementLogger::get_pgn_movetext(&chess_match, "*");
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|line| line.len() <= 80));
+        assert!(movetext.starts_with("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3"));
+        let rows = MovementLogger::get_move_rows(&chess_match);
+        assert_eq!(20, rows.len());
+        assert_eq!("2. Ng1 Ng8", rows[1]);
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        assert_eq!(1, chess_match.repetition_count());
+
+        for _ in 0..2 {
+            for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+                chess_match.apply_san(san).unwrap();
+            }
+        }
+        assert_eq!(3, chess_match.repetition_count());
+        assert!(chess_match.is_threefold_repetition());
+        // only claimable, so the game goes on unless someone records the draw
+        assert_eq!(MatchResult::InProgress, chess_match.result());
+
+        chess_match.undo_move();
+        assert!(!chess_match.is_threefold_repetition());
+        chess_match.apply_san("Ng8").unwrap();
+        chess_match.apply_san("e4").unwrap();
+        assert_eq!(1, chess_match.repetition_count());
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let drawn = |fen: &str| {
+            ChessMatch::from_fen(fen)
+                .unwrap()
+                .has_insufficient_material()
+        };
+        assert!(drawn("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(drawn("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"));
+        assert!(drawn("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(!drawn("4k1b1/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(!drawn("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1"));
+        assert!(!drawn("4k3/8/8/8/8/8/8/4K2R w K - 0 1"));
+
+        // taking the last pawn ends the game
+        let mut chess_match = ChessMatch::from_fen("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1").unwrap();
+        chess_match.apply_san("Kxd2").unwrap();
+        assert!(chess_match.is_game_over());
+        assert_eq!(
+            MatchResult::Draw {
+                reason: DrawReason::InsufficientMaterial
+            },
+            chess_match.result()
+        );
+        assert_eq!(MatchStatus::Completed, chess_match.get_status());
+    }
+
+    #[test]
+    fn test_fifty_move_draw() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("fen 4k3/8/8/8/8/8/4P3/4K3 w - - 98 70 moves e1d1")
+            .unwrap();
+        assert_eq!(99, chess_match.halfmove_clock());
+        assert!(!chess_match.is_fifty_move_draw());
+
+        let mut pawn_move = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        pawn_move
+            .set_position_uci("fen 4k3/8/8/8/8/8/4P3/4K3 w - - 98 70 moves e2e4")
+            .unwrap();
+        assert_eq!(0, pawn_move.halfmove_clock());
+
+        chess_match.apply_uci_move("e8d8").unwrap();
+        assert!(chess_match.is_fifty_move_draw());
+        assert!(chess_match.is_game_over());
+        assert_eq!(
+            MatchResult::Draw {
+                reason: DrawReason::FiftyMoveRule
+            },
+            chess_match.result()
+        );
+        assert_eq!(Err(MoveError::GameOver), chess_match.apply_uci_move("d1c1"));
+
+        let restored = ChessMatch::new_from_json(chess_match.get_json_string());
+        assert_eq!(100, restored.halfmove_clock());
+        assert!(restored.is_fifty_move_draw());
+        assert!(restored.to_fen().ends_with(" 100 71"));
+    }
+
+    #[test]
+    fn test_fen_errors() {
+        assert_eq!(
+            Err(FenError::WrongFieldCount(2)),
+            ChessMatch::from_fen("8/8/8/8/8/8/8/8 w")
+        );
+        assert!(matches!(
+            ChessMatch::from_fen("4k3/8/8/8/8/8/8/4K3 x - -"),
+            Err(FenError::InvalidSideToMove(_))
+        ));
+        assert!(matches!(
+            ChessMatch::from_fen("4k3/8/8/8/8/8/8/4K4 w - -"),
+            Err(FenError::InvalidPlacement(_))
+        ));
+        assert!(matches!(
+            ChessMatch::from_fen("4k3/8/8/8/8/8/8/4K3 w - e4"),
+            Err(FenError::InvalidEnPassant(_))
+        ));
+        assert!(matches!(
+            ChessMatch::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(FenError::InvalidPosition(_))
+        ));
+    }
+
+    #[test]
+    fn test_legal_moves_sorted() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+
+        let moves: Vec<String> = chess_match
+            .legal_moves_sorted()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+
+        assert_eq!(20, moves.len());
+        assert_eq!(
+            vec!["b1a3", "b1c3", "g1f3", "g1h3", "a2a3", "a2a4"],
+            moves[..6]
+        );
+        assert_eq!("h2h4", moves[19]);
+    }
+
+    #[test]
+    fn test_move_between() {
+        let mut before = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        before.calculate_valid_moves();
+        let mut after = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        after.set_position_uci("startpos moves g1f3").unwrap();
+
+        let found = before.move_between(&after).unwrap();
+        assert_eq!("g1f3", found.to_string());
+
+        after.apply_uci_move("g8f6").unwrap();
+        assert_eq!(None, before.move_between(&after));
+    }
+
+    #[test]
+    fn test_validate_position() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        assert_eq!(Ok(()), chess_match.validate_position());
+
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("e4"), 0),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("f5"), 0),
+        ]);
+        assert!(matches!(
+            chess_match.validate_position(),
+            Err(MoveError::InvalidPosition(_))
+        ));
+
+        chess_match.set_pieces(vec![ChessPiece::new(
+            PieceType::King,
+            PieceColor::White,
+            loc("e4"),
+            0,
+        )]);
+        assert_eq!(
+            Err(MoveError::InvalidPosition("Black has 0 kings".to_string())),
+            chess_match.validate_position()
+        );
+    }
+
+    #[test]
+    fn test_capture_moves() {
+        let chess_match = ChessMatch::from_fen("r3k3/1P6/8/3p4/4P3/8/8/4K2R w K - 0 1").unwrap();
+
+        let mut captures: Vec<String> = chess_match
+            .capture_moves()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        captures.sort();
+        let mut expected: Vec<String> = chess_match
+            .legal_moves()
+            .iter()
+            .filter(|m| m.capture || m.promotion.is_some())
+            .map(|m| m.to_string())
+            .collect();
+        expected.sort();
+
+        assert_eq!(
+            vec!["b7a8b", "b7a8n", "b7a8q", "b7a8r", "b7b8b", "b7b8n", "b7b8q", "b7b8r", "e4d5"],
+            captures
+        );
+        assert_eq!(expected, captures);
+    }
+
+    #[test]
+    fn test_legal_moves_carry_promotion_and_castle() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("fen 2n1k3/1P6/8/8/8/8/8/R3K2R w KQ - 0 1")
+            .unwrap();
+        let to_b8: Vec<String> = chess_match
+            .legal_moves_sorted()
+            .iter()
+            .filter(|m| m.from == PieceLocation::new_from_string("b7").unwrap())
+            .map(|m| m.to_string())
+            .collect();
+        assert_eq!(
+            vec!["b7b8q", "b7b8r", "b7b8b", "b7b8n", "b7c8q", "b7c8r", "b7c8b", "b7c8n"],
+            to_b8
+        );
+
+        let castles: Vec<(String, Option<CastleSide>)> = chess_match
+            .legal_moves_sorted()
+            .into_iter()
+            .filter(|m| m.castle.is_some())
+            .map(|m| (m.to_string(), m.castle))
+            .collect();
+        assert_eq!(
+            vec![
+                ("e1c1".to_string(), Some(CastleSide::QueenSide)),
+                ("e1g1".to_string(), Some(CastleSide::KingSide)),
+            ],
+            castles
+        );
+
+        // an underpromotion is played as the move says
+        let knight = chess_match
+            .legal_moves()
+            .into_iter()
+            .find(|m| m.to_string() == "b7c8n")
+            .unwrap();
+        chess_match.apply_move(&knight);
+        assert_eq!("bxc8=N", chess_match.get_log_entries()[0].get_notation());
+    }
+
+    #[test]
+    fn test_checking_moves() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("e1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("h1"), 5),
+            ChessPiece::new(PieceType::Pawn, PieceColor::White, loc("b7"), 1),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("f8"), 0),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        let mut checks: Vec<String> = chess_match
+            .checking_moves()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        checks.sort();
+
+        // castling puts the rook on f1, a new queen or rook on b8 sees f8, and the rook can
+        // check from f1 or h8
+        assert_eq!(vec!["b7b8q", "b7b8r", "e1g1", "h1f1", "h1h8"], checks);
+    }
+
+    #[test]
+    fn test_no_moves_after_checkmate() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves f2f3 e7e5 g2g4 d8h4")
+            .unwrap();
+        assert!(chess_match.is_game_over());
+
+        let pawn = chess_match
+            .get_piece_at_location(PieceLocation::new_from_string("a2").unwrap())
+            .unwrap();
+        let a3 = PieceLocation::new_from_string("a3").unwrap();
+        assert_eq!(
+            Err(MoveError::GameOver),
+            chess_match.move_piece(&pawn.id, &a3)
+        );
+        assert_eq!(Err(MoveError::GameOver), chess_match.apply_uci_move("a2a3"));
+        assert_eq!(4, chess_match.get_log_entries().len());
+    }
+
+    #[test]
+    fn test_refused_moves_keep_the_turn() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 d7d5 e4d5")
+            .unwrap();
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let captured = chess_match.get_log_entries()[1].get_piece_id();
+        let white_knight = chess_match.get_piece_at_location(location("g1")).unwrap();
+        let black_knight = chess_match.get_piece_at_location(location("g8")).unwrap();
+
+        assert_eq!(
+            Err(MoveError::NotYourTurn(PieceColor::White)),
+            chess_match.move_piece(&white_knight.id, &location("f3"))
+        );
+        assert_eq!(
+            Err(MoveError::PieceCaptured(captured)),
+            chess_match.move_piece(&captured, &location("d4"))
+        );
+        assert_eq!(
+            Err(MoveError::IllegalDestination("g8g6".to_string())),
+            chess_match.move_piece(&black_knight.id, &location("g6"))
+        );
+        assert_eq!(3, chess_match.get_log_entries().len());
+        assert_eq!(
+            PieceColor::Black,
+            chess_match.get_current_turn_and_color().1
+        );
+
+        let entry = chess_match
+            .move_piece(&black_knight.id, &location("f6"))
+            .unwrap();
+        assert_eq!("Nf6", entry.get_notation());
+        assert_eq!(
+            PieceColor::White,
+            chess_match.get_current_turn_and_color().1
+        );
+    }
+
+    #[test]
+    fn test_perft_known_positions() {
+        // published node counts for the starting position and the standard tricky positions,
+        // which between them cover castling, pins, en passant, promotions and checks
+        let positions = [
+            ("startpos", 3, 8902),
+            (
+                "fen r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                2,
+                2039,
+            ),
+            ("fen 8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3, 2812),
+            (
+                "fen r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                2,
+                264,
+            ),
+            (
+                "fen rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                2,
+                1486,
+            ),
+            (
+                "fen r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+                2,
+                2079,
+            ),
+        ];
+        for (position, depth, nodes) in positions {
+            let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+            chess_match.set_position_uci(position).unwrap();
+            assert_eq!(nodes, chess_match.perft(depth), "{}", position);
+        }
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("fen 4k3/1P6/8/8/8/8/8/4K3 w - - 0 1")
+            .unwrap();
+
+        let divide = chess_match.perft_divide(2);
+        assert_eq!(
+            vec!["b7b8b", "b7b8n", "b7b8q", "b7b8r", "e1d1", "e1d2", "e1e2", "e1f1", "e1f2"],
+            divide.iter().map(|(m, _)| m.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(("b7b8q".to_string(), 3), divide[2]);
+        assert_eq!(
+            chess_match.perft(2),
+            divide.iter().map(|(_, nodes)| nodes).sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn test_targets_of() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 d7d5")
+            .unwrap();
+        let pawn = chess_match
+            .get_piece_at_location(PieceLocation::new_from_string("e4").unwrap())
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                PieceLocation::new_from_string("e5").unwrap(),
+                PieceLocation::new_from_string("d5").unwrap()
+            ],
+            chess_match.targets_of(&pawn.id).unwrap()
+        );
+        assert!(chess_match.targets_of(&Uuid::new_v4()).is_err());
+        assert_eq!(
+            Some(crate::piece_base::TargetKind::Capture),
+            pawn.get_targets().last().map(|(_, kind)| *kind)
+        );
+    }
+
+    #[test]
+    fn test_legal_moves_iter_matches_legal_moves() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 d1h5 b8c6")
+            .unwrap();
+
+        let from_iter: Vec<Move> = chess_match.legal_moves_iter().collect();
+        assert_eq!(chess_match.legal_moves(), from_iter);
+        assert!(chess_match.has_legal_move());
+    }
+
+    #[test]
+    fn test_board_string_start_position() {
+        let chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+
+        assert_eq!(
+            concat!(
+                "rnbqkbnr", "pppppppp", "........", "........", "........", "........", "PPPPPPPP",
+                "RNBQKBNR"
+            ),
+            chess_match.board_string()
+        );
+    }
+
+    #[test]
+    fn test_pawn_can_be_stopped_rule_of_the_square() {
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let pawn = ChessPiece::new(PieceType::Pawn, PieceColor::White, loc("a4"), 1);
+        let pawn_id = pawn.id;
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("h1"), 0),
+            pawn,
+            // a8 is five king moves away, the pawn needs four moves
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("f6"), 0),
+        ]);
+        chess_match.calculate_valid_moves();
 
-        fn get_location(file: usize, rank: u32) -> PieceLocation {
-            PieceLocation::new(FILES.get(file).unwrap().to_string(), rank)
+        // with white to move the pawn is out of reach
+        assert!(!chess_match.pawn_can_be_stopped(&pawn_id));
+
+        // with black to move Ke6 steps into the square in time
+        chess_match.change_turn();
+        assert!(chess_match.pawn_can_be_stopped(&pawn_id));
+    }
+
+    #[test]
+    fn test_king_attack_balance() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("g1"), 0),
+            ChessPiece::new(PieceType::Queen, PieceColor::White, loc("h5"), 9),
+            ChessPiece::new(PieceType::Knight, PieceColor::White, loc("g5"), 3),
+            ChessPiece::new(PieceType::Bishop, PieceColor::White, loc("d3"), 3),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("g8"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::Black, loc("f8"), 5),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("f7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("g7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("h7"), 1),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        // queen, knight and bishop all bear on h7, only the rook guards the zone
+        assert_eq!((3, 1), chess_match.king_attack_balance(PieceColor::Black));
+        // the bishop covers f1 and the queen covers h2
+        assert_eq!((0, 2), chess_match.king_attack_balance(PieceColor::White));
+    }
+
+    #[test]
+    fn test_json_round_trip_keeps_every_field() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 d7d5 e4d5 g8f6 f1b5")
+            .unwrap();
+
+        let restored = ChessMatch::new_from_json(chess_match.get_json_string());
+
+        assert_eq!(chess_match, restored);
+        assert_eq!(5, restored.get_log_entries().len());
+        assert_eq!(KingState::InCheck, restored.get_black_king_state());
+        assert_eq!(chess_match.white_king_castle, restored.white_king_castle);
+    }
+
+    #[test]
+    fn test_debug_moves_shows_illegal_en_passant() {
+        // taking en passant would empty the rank between the king and the rook
+        let chess_match = ChessMatch::from_fen("7k/8/8/KPp4r/8/8/8/8 w - c6 0 1").unwrap();
+
+        let (pseudo, legal) = chess_match.debug_moves();
+        let en_passant = |m: &Move| m.to == PieceLocation::new("c".to_string(), 6);
+
+        assert!(pseudo.iter().any(en_passant));
+        assert!(!legal.iter().any(en_passant));
+        assert!(legal.iter().all(|m| pseudo.contains(m)));
+        assert_eq!(chess_match.legal_moves_sorted(), legal);
+    }
+
+    #[test]
+    fn test_bishop_pair_counts_promoted_bishop() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(vec![
+            ChessPiece::new(
+                PieceType::King,
+                PieceColor::White,
+                PieceLocation::new("e".to_string(), 1),
+                0,
+            ),
+            ChessPiece::new(
+                PieceType::Bishop,
+                PieceColor::White,
+                PieceLocation::new("c".to_string(), 1),
+                3,
+            ),
+            ChessPiece::new(
+                PieceType::Pawn,
+                PieceColor::White,
+                PieceLocation::new("b".to_string(), 7),
+                1,
+            ),
+            ChessPiece::new(
+                PieceType::King,
+                PieceColor::Black,
+                PieceLocation::new("h".to_string(), 8),
+                0,
+            ),
+        ]);
+        chess_match.calculate_valid_moves();
+        assert!(!chess_match.has_bishop_pair(PieceColor::White));
+
+        // the new bishop runs on the dark squares just like the one on c1, but the two
+        // still count as a pair
+        let pawn_id = chess_match
+            .get_piece_at_location(PieceLocation::new("b".to_string(), 7))
+            .unwrap()
+            .id;
+        chess_match
+            .move_piece_with_promotion(
+                &pawn_id,
+                &PieceLocation::new("b".to_string(), 8),
+                PieceType::Bishop,
+            )
+            .unwrap();
+
+        assert_eq!(
+            Some(&2),
+            chess_match
+                .material_breakdown(PieceColor::White)
+                .get(&PieceType::Bishop)
+        );
+        assert!(chess_match.has_bishop_pair(PieceColor::White));
+        assert!(!chess_match.has_bishop_pair(PieceColor::Black));
+    }
+
+    #[test]
+    fn test_legal_moves_for_piece() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 d2d4 f8b4")
+            .unwrap();
+
+        // white is in check from b4, so the b1 knight may only block on c3 or d2
+        let knight_id = chess_match
+            .get_piece_at_location(PieceLocation::new("b".to_string(), 1))
+            .unwrap()
+            .id;
+        let knight_moves = chess_match.legal_moves_for_piece(&knight_id);
+        let expected: Vec<Move> = chess_match
+            .legal_moves()
+            .into_iter()
+            .filter(|m| m.piece_id == knight_id)
+            .collect();
+        assert_eq!(expected, knight_moves);
+        assert_eq!(2, knight_moves.len());
+
+        // the g1 knight can't reach the diagonal, so it has no moves at all
+        let g_knight_id = chess_match
+            .get_piece_at_location(PieceLocation::new("g".to_string(), 1))
+            .unwrap()
+            .id;
+        assert!(chess_match.legal_moves_for_piece(&g_knight_id).is_empty());
+        assert!(chess_match
+            .legal_moves_for_piece(&Uuid::new_v4())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_captured_pieces() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        for san in [
+            "e4", "d5", "exd5", "Nf6", "Bb5+", "c6", "dxc6", "Qd6", "cxb7+", "Qd7",
+        ] {
+            chess_match.apply_san(san).unwrap();
         }
 
-        for color in PieceColor::iter() {
-            // generate pawns
-            let mut rank = pawn_ranks.get(&color).unwrap();
-            for f in FILES {
-                let location =
-                    PieceLocation::new_from_string(format!("{}{}", f, rank).as_str()).unwrap();
-                let piece = ChessPiece::new(PieceType::Pawn, color.clone(), location, 1);
-                result.push(piece);
-            }
+        let taken: Vec<PieceType> = chess_match
+            .captured_pieces(PieceColor::Black)
+            .iter()
+            .map(|p| p.get_type())
+            .collect();
+        assert_eq!(vec![PieceType::Pawn; 3], taken);
+        assert!(chess_match.captured_pieces(PieceColor::White).is_empty());
+        assert_eq!(3, chess_match.material_balance());
 
-            // generate rooks
-            rank = other_ranks.get(&color).unwrap();
-            let rook_positions = vec![0, 7];
-            for p in rook_positions {
-                let location = get_location(p, *rank);
-                let rook = ChessPiece::new(PieceType::Rook, color.clone(), location, 5);
-                result.push(rook);
-            }
+        chess_match.apply_san("bxa8=Q").unwrap();
+        assert_eq!(
+            Some(PieceType::Rook),
+            chess_match
+                .captured_pieces(PieceColor::Black)
+                .last()
+                .map(|p| p.get_type())
+        );
+        chess_match.undo_move();
+        assert_eq!(3, chess_match.captured_pieces(PieceColor::Black).len());
+    }
 
-            // generate knights
-            let knight_positions = vec![1, 6];
-            for p in knight_positions {
-                let location = get_location(p, *rank);
-                let knight = ChessPiece::new(PieceType::Knight, color.clone(), location, 3);
-                result.push(knight);
-            }
+    #[test]
+    fn test_rook_file_status() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 d7d5 e4d5 d8d5 h2h4 g7g5 h4g5 h7h6 h1h5")
+            .unwrap();
+        let rook_at = |chess_match: &ChessMatch, file: &str, rank: u32| {
+            chess_match
+                .get_piece_at_location(PieceLocation::new(file.to_string(), rank))
+                .unwrap()
+                .id
+        };
 
-            // generate bishops
-            let bishop_positions = vec![2, 5];
-            for p in bishop_positions {
-                let location = get_location(p, *rank);
-                let bishop = ChessPiece::new(PieceType::Bishop, color.clone(), location, 3);
-                result.push(bishop);
-            }
+        // h6 is black's pawn, so white's rook has a semi-open file and black's is closed
+        let white_rook = rook_at(&chess_match, "h", 5);
+        assert_eq!(
+            FileStatus::SemiOpen,
+            chess_match.rook_file_status(&white_rook)
+        );
+        let black_rook = rook_at(&chess_match, "h", 8);
+        assert_eq!(
+            FileStatus::Closed,
+            chess_match.rook_file_status(&black_rook)
+        );
+        let a_rook = rook_at(&chess_match, "a", 1);
+        assert_eq!(FileStatus::Closed, chess_match.rook_file_status(&a_rook));
 
-            // generate queen
-            let queen_position = 3;
-            let queen_location = get_location(queen_position, *rank);
-            let queen = ChessPiece::new(PieceType::Queen, color.clone(), queen_location, 9);
+        chess_match.set_pieces(vec![
+            ChessPiece::new(
+                PieceType::King,
+                PieceColor::White,
+                PieceLocation::new("e".to_string(), 1),
+                0,
+            ),
+            ChessPiece::new(
+                PieceType::Rook,
+                PieceColor::White,
+                PieceLocation::new("d".to_string(), 1),
+                5,
+            ),
+            ChessPiece::new(
+                PieceType::Pawn,
+                PieceColor::White,
+                PieceLocation::new("c".to_string(), 2),
+                1,
+            ),
+            ChessPiece::new(
+                PieceType::King,
+                PieceColor::Black,
+                PieceLocation::new("e".to_string(), 8),
+                0,
+            ),
+        ]);
+        chess_match.calculate_valid_moves();
+        let open_rook = rook_at(&chess_match, "d", 1);
+        assert_eq!(FileStatus::Open, chess_match.rook_file_status(&open_rook));
+    }
 
-            // generate king
-            let king_position = 4;
-            let king_location = get_location(king_position, *rank);
-            let king = ChessPiece::new(PieceType::King, color.clone(), king_location, 0);
+    #[test]
+    fn test_explain_move() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 g1f3 b8c6")
+            .unwrap();
+        let uci_move = |chess_match: &ChessMatch, uci: &str| {
+            let mut match_copy = chess_match.copy();
+            match_copy.apply_uci_move(uci).unwrap()
+        };
 
-            result.push(queen);
-            result.push(king);
+        let bb5 = uci_move(&chess_match, "f1b5");
+        assert_eq!(
+            "develops a piece and attacks the knight",
+            chess_match.explain_move(&bb5)
+        );
+        let h3 = uci_move(&chess_match, "h2h3");
+        assert_eq!("is a quiet move", chess_match.explain_move(&h3));
+
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 g1f3 d8g5")
+            .unwrap();
+        let nxg5 = uci_move(&chess_match, "f3g5");
+        assert_eq!("wins a queen", chess_match.explain_move(&nxg5));
+    }
+
+    fn coach() -> Engine {
+        Engine::with_seed(3)
+    }
+
+    #[test]
+    fn test_evaluate_current_position() {
+        let start = ChessMatch::from_fen(fen::STARTING_FEN).unwrap();
+        match start.evaluate_current_position() {
+            Evaluation::Centipawns(cp) => assert!(cp.abs() < 100),
+            other => panic!("expected a centipawn score, got {:?}", other),
         }
 
-        result
+        // black to move and a queen down
+        let queen_down = ChessMatch::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+        assert!(matches!(
+            queen_down.evaluate_current_position(),
+            Evaluation::Centipawns(cp) if cp > 500
+        ));
+
+        let mut back_rank = ChessMatch::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(
+            Evaluation::Mate {
+                winner: PieceColor::White,
+                moves: 1
+            },
+            back_rank.evaluate_current_position()
+        );
+        back_rank.apply_san("Ra8#").unwrap();
+        assert_eq!(
+            Evaluation::Mate {
+                winner: PieceColor::White,
+                moves: 0
+            },
+            back_rank.evaluate_current_position()
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_suggest_move_delivers_mate() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("g1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("e1"), 5),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("g8"), 0),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("f7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("g7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("h7"), 1),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        let suggestion = chess_match.suggest_move(&mut coach()).unwrap();
+        assert_eq!(loc("e8"), suggestion.chess_move.to);
+        assert_eq!("delivers checkmate", suggestion.explanation);
+    }
 
     #[test]
-    fn test_pieces_generate() {
-        let chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+    fn test_suggest_move_wins_hanging_queen() {
+        // the queen on d5 is loose; taking the pawn on a7 instead wins far less
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let loc = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("g1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("a1"), 5),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("d1"), 5),
+            ChessPiece::new(PieceType::Pawn, PieceColor::White, loc("f2"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::White, loc("g2"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::White, loc("h2"), 1),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("g8"), 0),
+            ChessPiece::new(PieceType::Queen, PieceColor::Black, loc("d5"), 9),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("a7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("f7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("g7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("h7"), 1),
+        ]);
+        chess_match.calculate_valid_moves();
 
-        assert_eq!(32, chess_match.pieces.len());
+        let suggestion = chess_match.suggest_move(&mut coach()).unwrap();
+        assert_eq!("Rxd5", chess_match.san_of(&suggestion.chess_move).unwrap());
+        assert!(suggestion.explanation.contains("wins a queen"));
     }
 
     #[test]
-    fn test_move_piece_and_update_valid_moves() {
-        env_logger::init();
+    fn test_best_line_with_principal_variation() {
+        let chess_match = ChessMatch::from_fen("6k1/5ppp/8/8/8/8/8/4R1K1 w - - 0 1").unwrap();
+
+        let line = chess_match.best_line(SearchLimit::Depth(2)).unwrap();
+        assert_eq!(MATE_SCORE - 1, line.score);
+        assert_eq!(vec!["Re8#".to_string()], line.pv);
+
+        // the same move twice doesn't make a line
+        let repeated = vec![line.best_move.clone(), line.best_move];
+        assert!(chess_match.line_to_san(&repeated).is_err());
+    }
+
+    #[test]
+    fn test_loaded_resignation_is_not_recomputed() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5")
+            .unwrap();
+        chess_match.record_result(MatchResult::WhiteWonResignation);
+
+        let mut restored = ChessMatch::new_from_json(chess_match.get_json_string());
+        restored.calculate_valid_moves();
+
+        assert_eq!(MatchResult::WhiteWonResignation, restored.result());
+        assert!(restored.is_game_over());
+        assert!(restored.has_legal_move());
+    }
+
+    #[test]
+    fn test_status_and_result_follow_the_game() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves f2f3 e7e5 g2g4")
+            .unwrap();
+        assert_eq!(MatchStatus::InProgress, chess_match.get_status());
+        assert_eq!(None, chess_match.get_winner());
+
+        chess_match.apply_uci_move("d8h4").unwrap();
+        assert_eq!(MatchStatus::Completed, chess_match.get_status());
+        assert_eq!(MatchResult::BlackWonCheckmate, chess_match.result());
+        assert_eq!(
+            Some(chess_match.get_black_player_id()),
+            chess_match.get_winner()
+        );
+
+        chess_match.undo_last_move();
+        assert_eq!(MatchStatus::InProgress, chess_match.get_status());
+        assert_eq!(MatchResult::InProgress, chess_match.result());
+        assert_eq!(None, chess_match.get_winner());
+
+        let json = chess_match.get_json_string();
+        assert!(json.contains("\"status\":\"InProgress\""));
+        let old_format = json
+            .replace("\"status\":\"InProgress\"", "\"status\":0")
+            .replace("\"result\":\"InProgress\"", "\"result\":0");
+        assert_eq!(
+            chess_match,
+            ChessMatch::new_from_json(unversioned(old_format))
+        );
+    }
+
+    #[test]
+    fn test_time_report() {
         let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
         chess_match.calculate_valid_moves();
+        for m in ["e2e4", "e7e5", "g1f3"] {
+            chess_match.apply_uci_move(m).unwrap();
+        }
+        assert!(chess_match
+            .get_log_entries()
+            .iter()
+            .all(|e| e.get_timestamp().is_some()));
 
-        let piece = chess_match
-            .get_piece_at_location(PieceLocation::new_from_string("a2").unwrap())
+        let white = chess_match.get_white_player_id();
+        let black = chess_match.get_black_player_id();
+        let entry = |player_id, time_span| {
+            let mut entry = MovementLogEntry::new(
+                player_id,
+                Uuid::new_v4(),
+                PieceLocation::new("a".to_string(), 1),
+                PieceLocation::new("a".to_string(), 2),
+            );
+            entry.time_span(time_span);
+            entry
+        };
+        let mut timed = ChessMatch::new(white, black);
+        timed.add_log_entry(entry(white, 4000));
+        timed.add_log_entry(entry(black, 1500));
+        timed.add_log_entry(entry(white, 2000));
+
+        let report = MovementLogger::get_time_report(&timed);
+        assert_eq!(
+            PlayerTimes {
+                moves: 2,
+                total_ms: 6000,
+                average_ms: 3000,
+                longest_ms: 4000
+            },
+            report.white
+        );
+        assert_eq!(1500, report.black.total_ms);
+        assert_eq!(
+            "White: 2 moves, 6.0s total, 3.0s average, 4.0s longest\n\
+             Black: 1 moves, 1.5s total, 1.5s average, 1.5s longest\n",
+            report.to_string()
+        );
+    }
+
+    #[test]
+    fn test_flag_fall_loses_on_time() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        // no time at all, so black's flag falls as soon as white's first move starts it
+        chess_match.set_time_control(TimeControl::increment(0, 0));
+        assert!(!chess_match.pause_clock());
+
+        chess_match.apply_uci_move("e2e4").unwrap();
+        assert_eq!(Some(0), chess_match.time_remaining(PieceColor::Black));
+        assert_eq!(0, chess_match.movement_log[0].get_time_span());
+
+        assert_eq!(Err(MoveError::GameOver), chess_match.apply_uci_move("e7e5"));
+        assert_eq!(MatchResult::WhiteWonTimeout, chess_match.result());
+        assert_eq!(
+            Some(TimeControl::increment(0, 0)),
+            chess_match
+                .rematch()
+                .get_clock()
+                .map(|c| c.get_time_control())
+        );
+    }
+
+    #[test]
+    fn test_resign_and_draw_offers() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_position_uci("startpos moves e2e4").unwrap();
+        let white = chess_match.get_white_player_id();
+        let black = chess_match.get_black_player_id();
+        let stranger = Uuid::new_v4();
+
+        assert_eq!(
+            Err(MoveError::NotAPlayer(stranger)),
+            chess_match.offer_draw(&stranger)
+        );
+        assert!(chess_match.respond_to_draw(&black, true).is_err());
+
+        // black moves instead of answering, which declines the offer
+        chess_match.offer_draw(&white).unwrap();
+        assert_eq!(
+            Some(PieceColor::White),
+            chess_match.get_pending_draw_offer()
+        );
+        assert!(chess_match.respond_to_draw(&white, true).is_err());
+        chess_match.apply_uci_move("e7e5").unwrap();
+        assert_eq!(None, chess_match.get_pending_draw_offer());
+
+        let mut drawn = chess_match.copy();
+        drawn.offer_draw(&white).unwrap();
+        assert_eq!(Ok(true), drawn.respond_to_draw(&black, true));
+        assert_eq!(
+            MatchResult::Draw {
+                reason: DrawReason::Agreement
+            },
+            drawn.result()
+        );
+        assert_eq!("1.e4 e5 ½-½", MovementLogger::get_formatted_entries(&drawn));
+        assert_eq!(Err(MoveError::GameOver), drawn.apply_uci_move("g1f3"));
+
+        assert_eq!(
+            Ok(MatchResult::WhiteWonResignation),
+            chess_match.resign(&black)
+        );
+        assert_eq!(Some(white), chess_match.get_winner());
+        assert_eq!(
+            "1.e4 e5 1-0",
+            MovementLogger::get_formatted_entries(&chess_match)
+        );
+        assert_eq!(Err(MoveError::GameOver), chess_match.resign(&white));
+    }
+
+    #[test]
+    fn test_undo_and_redo() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 d7d5 e4d5 g8f6 g1f3 b8c6 f1b5 e7e5 e1g1")
             .unwrap();
-        chess_match.move_piece(&piece.id, &PieceLocation::new_from_string("a4").unwrap());
-        let current_turn = chess_match.change_turn();
+        let played = chess_match.copy();
+        let start = ChessMatch::new_from_json(chess_match.get_json_string());
+
+        // undo everything, including the castle and the capture, then redo it all
+        let mut undone = 0;
+        while chess_match.undo_move().is_some() {
+            undone += 1;
+        }
+        assert_eq!(9, undone);
+        assert_eq!(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            chess_match.to_fen()
+        );
+        assert!(chess_match.can_redo());
+
+        while chess_match.redo_move().is_some() {}
+        assert_eq!(played.to_fen(), chess_match.to_fen());
+        assert_eq!(
+            MovementLogger::get_formatted_entries(&start),
+            MovementLogger::get_formatted_entries(&chess_match)
+        );
+
+        // a new move drops whatever could have been redone
+        chess_match.undo_move();
+        chess_match.undo_move();
+        chess_match.apply_uci_move("a7a6").unwrap();
+        assert!(!chess_match.can_redo());
+        assert_eq!(None, chess_match.redo_move());
+    }
+
+    #[test]
+    fn test_undo_keeps_recorded_results() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 g1f3")
+            .unwrap();
+        let black = chess_match.get_black_player_id();
+        chess_match.resign(&black).unwrap();
+
+        assert_eq!(None, chess_match.undo_move());
+        assert_eq!(3, chess_match.get_log_entries().len());
+        assert_eq!(MatchResult::WhiteWonResignation, chess_match.result());
+        assert_eq!(MatchStatus::Completed, chess_match.get_status());
+
+        // the game can still be stepped through
+        let before = chess_match.position_at_move(2).unwrap();
+        assert_eq!(MatchResult::InProgress, before.result());
+        assert_eq!(
+            MatchResult::WhiteWonResignation,
+            chess_match.position_at_move(3).unwrap().result()
+        );
+    }
+
+    #[test]
+    fn test_undo_reopens_a_checkmate() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves f2f3 e7e5 g2g4 d8h4")
+            .unwrap();
+        assert_eq!(MatchResult::BlackWonCheckmate, chess_match.result());
+
+        assert!(chess_match.undo_move().is_some());
+        assert_eq!(MatchResult::InProgress, chess_match.result());
+        assert_eq!(MatchStatus::InProgress, chess_match.get_status());
+    }
+
+    #[test]
+    fn test_last_move_squares() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
         chess_match.calculate_valid_moves();
-        let piece = chess_match
-            .get_piece_at_location(PieceLocation::new_from_string("a4").unwrap())
+        assert_eq!(None, chess_match.last_move_squares());
+
+        chess_match.apply_san("Nf3").unwrap();
+        assert_eq!(
+            Some((
+                PieceLocation::new_from_string("g1").unwrap(),
+                PieceLocation::new_from_string("f3").unwrap()
+            )),
+            chess_match.last_move_squares()
+        );
+    }
+
+    #[test]
+    fn test_position_at_move() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves f2f3 e7e5 g2g4 d8h4")
             .unwrap();
-        assert_eq!(0, current_turn);
-        assert_eq!(1, piece.get_valid_moves().len());
+
+        let start = chess_match.position_at_move(0).unwrap();
+        assert_eq!(fen::STARTING_FEN, start.to_fen());
+        let second = chess_match.position_at_move(2).unwrap();
+        assert_eq!(
+            "rnbqkbnr/pppp1ppp/8/4p3/8/5P2/PPPPP1PP/RNBQKBNR w KQkq e6 0 2",
+            second.to_fen()
+        );
+        assert_eq!(MatchResult::InProgress, second.result());
+        assert_eq!(2, second.get_log_entries().len());
+
+        let mated = chess_match.position_at_move(4).unwrap();
+        assert_eq!(MatchResult::BlackWonCheckmate, mated.result());
+        assert!(chess_match.position_at_move(5).is_none());
+        // the match itself is left alone
+        assert_eq!(4, chess_match.get_log_entries().len());
+    }
+
+    #[test]
+    fn test_turn_loads_from_old_and_new_saves() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.change_turn();
+        let json = chess_match.get_json_string();
+        assert!(json.contains("\"current_turn\":\"Black\""));
+
+        let old_format = json.replace("\"current_turn\":\"Black\"", "\"current_turn\":1");
+        let restored = ChessMatch::new_from_json(unversioned(old_format));
+        assert_eq!(chess_match, restored);
+        assert_eq!(
+            (1, PieceColor::Black),
+            restored.get_current_turn_and_color()
+        );
+    }
+
+    #[test]
+    fn test_can_castle_now() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 f8c5")
+            .unwrap();
+
+        assert!(chess_match.can_castle_now(PieceColor::White, CastleSide::KingSide));
+        assert!(!chess_match.can_castle_now(PieceColor::White, CastleSide::QueenSide));
+        // the rights are still there, the path just isn't clear
+        assert!(chess_match.castling_rights().white_queen_side);
+
+        // after Bxf7+ black is in check and cannot castle even though the path is clear
+        chess_match.apply_uci_move("c4f7").unwrap();
+        assert!(!chess_match.can_castle_now(PieceColor::Black, CastleSide::KingSide));
+        assert!(chess_match.castling_rights().black_king_side);
+    }
+
+    #[test]
+    fn test_mobility_by_type() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+
+        let mobility = chess_match.mobility_by_type(PieceColor::White);
+        assert_eq!(Some(&16), mobility.get(&PieceType::Pawn));
+        assert_eq!(Some(&4), mobility.get(&PieceType::Knight));
+        assert_eq!(None, mobility.get(&PieceType::Bishop));
     }
 }
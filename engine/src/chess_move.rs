@@ -0,0 +1,91 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    chess_match::CastleSide,
+    fen,
+    piece_base::{PieceColor, PieceType},
+    piece_location::PieceLocation,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+pub struct Move {
+    pub piece_id: Uuid,
+    pub from: PieceLocation,
+    pub to: PieceLocation,
+    pub capture: bool,
+    /// The piece a pawn reaching the last rank turns into. Each choice is a separate move.
+    #[serde(default)]
+    pub promotion: Option<PieceType>,
+    /// Set when this is the king's move of a castle.
+    #[serde(default)]
+    pub castle: Option<CastleSide>,
+}
+
+/// Writes the move in UCI coordinate notation, e.g. `e2e4` or `e7e8q`.
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(piece_type) = self.promotion {
+            write!(f, "{}", fen::piece_char(piece_type, PieceColor::Black))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Move {
+    pub fn new(piece_id: Uuid, from: PieceLocation, to: PieceLocation, capture: bool) -> Move {
+        Move {
+            piece_id,
+            from,
+            to,
+            capture,
+            promotion: None,
+            castle: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MoveError {
+    InvalidNotation(String),
+    IllegalMove(String),
+    InvalidPosition(String),
+    PromotionRequired(String),
+    GameOver,
+    NotAPlayer(Uuid),
+    NotYourTurn(PieceColor),
+    PieceCaptured(Uuid),
+    IllegalDestination(String),
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::InvalidNotation(notation) => {
+                write!(f, "Invalid move notation: {}", notation)
+            }
+            MoveError::IllegalMove(notation) => write!(f, "Illegal move: {}", notation),
+            MoveError::InvalidPosition(reason) => write!(f, "Invalid position: {}", reason),
+            MoveError::PromotionRequired(notation) => {
+                write!(f, "Promotion piece required: {}", notation)
+            }
+            MoveError::GameOver => write!(f, "The game is already over"),
+            MoveError::NotAPlayer(player_id) => {
+                write!(f, "{} is not playing in this match", player_id)
+            }
+            MoveError::NotYourTurn(color) => write!(f, "It is not {:?}'s turn", color),
+            MoveError::PieceCaptured(piece_id) => {
+                write!(f, "Piece {} has been captured", piece_id)
+            }
+            MoveError::IllegalDestination(notation) => {
+                write!(f, "Illegal destination: {}", notation)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
@@ -0,0 +1,266 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::piece_base::PieceColor;
+
+/// How much time each player gets: a starting amount, plus an increment added after every
+/// move, or a delay at the start of every move before their clock starts counting down.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub base_ms: u64,
+    pub increment_ms: u64,
+    pub delay_ms: u64,
+}
+
+impl TimeControl {
+    /// 5 minutes plus 3 seconds a move.
+    pub fn blitz() -> TimeControl {
+        TimeControl::increment(5 * 60, 3)
+    }
+
+    /// 15 minutes plus 10 seconds a move.
+    pub fn rapid() -> TimeControl {
+        TimeControl::increment(15 * 60, 10)
+    }
+
+    /// 90 minutes plus 30 seconds a move.
+    pub fn classical() -> TimeControl {
+        TimeControl::increment(90 * 60, 30)
+    }
+
+    pub fn increment(base_secs: u64, increment_secs: u64) -> TimeControl {
+        TimeControl {
+            base_ms: base_secs * 1000,
+            increment_ms: increment_secs * 1000,
+            delay_ms: 0,
+        }
+    }
+
+    pub fn delay(base_secs: u64, delay_secs: u64) -> TimeControl {
+        TimeControl {
+            base_ms: base_secs * 1000,
+            increment_ms: 0,
+            delay_ms: delay_secs * 1000,
+        }
+    }
+}
+
+/// Both players' clocks. Only the side to move's clock runs, from when the opponent finished
+/// their move. Every method takes the current time so callers decide where time comes from.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MatchClock {
+    time_control: TimeControl,
+    white_ms: u64,
+    black_ms: u64,
+    running: Option<PieceColor>,
+    /// When the running clock last started or resumed.
+    turn_started: Option<DateTime<Utc>>,
+    /// Time the side to move already spent on this move before the clock was paused.
+    turn_used_ms: u64,
+    paused_at: Option<DateTime<Utc>>,
+    paused_ms: u64,
+}
+
+impl MatchClock {
+    pub fn new(time_control: TimeControl) -> MatchClock {
+        MatchClock {
+            time_control,
+            white_ms: time_control.base_ms,
+            black_ms: time_control.base_ms,
+            running: None,
+            turn_started: None,
+            turn_used_ms: 0,
+            paused_at: None,
+            paused_ms: 0,
+        }
+    }
+
+    pub fn get_time_control(&self) -> TimeControl {
+        self.time_control
+    }
+
+    /// The side whose clock is running, if the clock has started.
+    pub fn running(&self) -> Option<PieceColor> {
+        self.running
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Total time the clock has spent paused.
+    pub fn paused_ms(&self) -> u64 {
+        self.paused_ms
+    }
+
+    /// Stops `mover`'s clock at the end of their move and starts the opponent's. Returns the
+    /// time the move took, not counting pauses. The first press only starts the clock.
+    pub fn press(&mut self, mover: PieceColor, now: DateTime<Utc>) -> u64 {
+        self.resume(now);
+        let spent = if self.running == Some(mover) {
+            let spent = self.turn_time(now);
+            let TimeControl {
+                increment_ms,
+                delay_ms,
+                ..
+            } = self.time_control;
+            let remaining = self.remaining_mut(mover);
+            *remaining = remaining.saturating_sub(spent.saturating_sub(delay_ms));
+            // no increment once the flag has fallen
+            if *remaining > 0 {
+                *remaining += increment_ms;
+            }
+            spent
+        } else {
+            0
+        };
+
+        self.running = Some(mover.opposite());
+        self.turn_started = Some(now);
+        self.turn_used_ms = 0;
+        spent
+    }
+
+    /// Stops the running clock without charging anyone for the time until `resume`.
+    /// Returns false if it wasn't running.
+    pub fn pause(&mut self, now: DateTime<Utc>) -> bool {
+        if self.running.is_none() || self.is_paused() {
+            return false;
+        }
+
+        self.turn_used_ms = self.turn_time(now);
+        self.paused_at = Some(now);
+        true
+    }
+
+    /// Restarts a paused clock from where it stopped. Returns false if it wasn't paused.
+    pub fn resume(&mut self, now: DateTime<Utc>) -> bool {
+        match self.paused_at.take() {
+            Some(paused_at) => {
+                self.paused_ms += elapsed_ms(paused_at, now);
+                self.turn_started = Some(now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// How long `color` has left, counting the move in progress if it is their turn.
+    pub fn time_remaining(&self, color: PieceColor, now: DateTime<Utc>) -> u64 {
+        let remaining = match color {
+            PieceColor::White => self.white_ms,
+            PieceColor::Black => self.black_ms,
+        };
+        if self.running != Some(color) {
+            return remaining;
+        }
+
+        let charged = self
+            .turn_time(now)
+            .saturating_sub(self.time_control.delay_ms);
+        remaining.saturating_sub(charged)
+    }
+
+    /// The side whose time has run out, if any.
+    pub fn flagged(&self, now: DateTime<Utc>) -> Option<PieceColor> {
+        self.running
+            .filter(|color| self.time_remaining(*color, now) == 0)
+    }
+
+    /// Time spent on the move in progress, not counting pauses.
+    fn turn_time(&self, now: DateTime<Utc>) -> u64 {
+        match (self.paused_at, self.turn_started) {
+            (Some(_), _) | (None, None) => self.turn_used_ms,
+            (None, Some(started)) => self.turn_used_ms + elapsed_ms(started, now),
+        }
+    }
+
+    fn remaining_mut(&mut self, color: PieceColor) -> &mut u64 {
+        match color {
+            PieceColor::White => &mut self.white_ms,
+            PieceColor::Black => &mut self.black_ms,
+        }
+    }
+}
+
+fn elapsed_ms(from: DateTime<Utc>, to: DateTime<Utc>) -> u64 {
+    (to - from).num_milliseconds().max(0) as u64
+}
+
+/// Formats milliseconds as `m:ss`, or `h:mm:ss` from an hour up.
+pub fn format_time(ms: u64) -> String {
+    let secs = ms / 1000;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000, 0).unwrap() + Duration::seconds(secs)
+    }
+
+    #[test]
+    fn test_increment() {
+        let mut clock = MatchClock::new(TimeControl::increment(60, 2));
+        assert_eq!(0, clock.press(PieceColor::White, at(0)));
+        assert_eq!(Some(PieceColor::Black), clock.running());
+
+        assert_eq!(50_000, clock.time_remaining(PieceColor::Black, at(10)));
+        assert_eq!(10_000, clock.press(PieceColor::Black, at(10)));
+        assert_eq!(52_000, clock.time_remaining(PieceColor::Black, at(10)));
+        assert_eq!(60_000, clock.time_remaining(PieceColor::White, at(10)));
+    }
+
+    #[test]
+    fn test_delay() {
+        let mut clock = MatchClock::new(TimeControl::delay(60, 5));
+        clock.press(PieceColor::White, at(0));
+
+        assert_eq!(60_000, clock.time_remaining(PieceColor::Black, at(4)));
+        clock.press(PieceColor::Black, at(8));
+        assert_eq!(57_000, clock.time_remaining(PieceColor::Black, at(8)));
+    }
+
+    #[test]
+    fn test_pause_is_not_charged() {
+        let mut clock = MatchClock::new(TimeControl::increment(60, 0));
+        assert!(!clock.pause(at(0)));
+        clock.press(PieceColor::White, at(0));
+
+        assert!(clock.pause(at(10)));
+        assert!(!clock.pause(at(11)));
+        assert_eq!(50_000, clock.time_remaining(PieceColor::Black, at(500)));
+        assert!(clock.resume(at(500)));
+        assert!(!clock.resume(at(501)));
+
+        assert_eq!(45_000, clock.time_remaining(PieceColor::Black, at(505)));
+        assert_eq!(15_000, clock.press(PieceColor::Black, at(505)));
+        assert_eq!(490_000, clock.paused_ms());
+    }
+
+    #[test]
+    fn test_flag_falls() {
+        let mut clock = MatchClock::new(TimeControl::increment(60, 5));
+        clock.press(PieceColor::White, at(0));
+
+        assert_eq!(None, clock.flagged(at(59)));
+        assert_eq!(Some(PieceColor::Black), clock.flagged(at(61)));
+        clock.press(PieceColor::Black, at(61));
+        assert_eq!(0, clock.time_remaining(PieceColor::Black, at(61)));
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!("0:07", format_time(7_999));
+        assert_eq!("5:00", format_time(300_000));
+        assert_eq!("1:30:00", format_time(5_400_000));
+    }
+}
@@ -0,0 +1,89 @@
+/// A small table of well-known openings: ECO code, name and the moves in SAN, as they
+/// appear in the movement log.
+pub const ECO_OPENINGS: &[(&str, &str, &str)] = &[
+    ("A00", "Uncommon Opening", ""),
+    ("A04", "Reti Opening", "Nf3"),
+    ("A10", "English Opening", "c4"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A45", "Indian Defense", "d4 Nf6"),
+    ("A80", "Dutch Defense", "d4 f5"),
+    ("B00", "King's Pawn Opening", "e4"),
+    ("B01", "Scandinavian Defense", "e4 d5"),
+    ("B07", "Pirc Defense", "e4 d6 d4 Nf6"),
+    ("B10", "Caro-Kann Defense", "e4 c6"),
+    ("B20", "Sicilian Defense", "e4 c5"),
+    (
+        "B90",
+        "Sicilian Defense, Najdorf Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6",
+    ),
+    ("C00", "French Defense", "e4 e6"),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+    (
+        "C44",
+        "King's Knight Opening, Normal Variation",
+        "e4 e5 Nf3 Nc6",
+    ),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C65", "Ruy Lopez, Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    (
+        "C68",
+        "Ruy Lopez, Exchange Variation",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6",
+    ),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    ("D10", "Slav Defense", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+];
+
+/// Finds the opening whose moves are the longest prefix of `moves`, returning its ECO code
+/// and name.
+pub fn classify(moves: &[String]) -> Option<(String, String)> {
+    ECO_OPENINGS
+        .iter()
+        .filter_map(|(code, name, line)| {
+            let line: Vec<&str> = line.split_whitespace().collect();
+            let matches = line.len() <= moves.len()
+                && line.iter().zip(moves).all(|(expected, played)| {
+                    // the log marks checks, the table doesn't
+                    played.trim_end_matches(['+', '#']) == *expected
+                });
+            matches.then_some((line.len(), code, name))
+        })
+        .max_by_key(|(length, _, _)| *length)
+        .map(|(_, code, name)| (code.to_string(), name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn test_classify_longest_prefix() {
+        assert_eq!(
+            Some(("C65".to_string(), "Ruy Lopez, Berlin Defense".to_string())),
+            classify(&moves("e4 e5 Nf3 Nc6 Bb5 Nf6 O-O"))
+        );
+        assert_eq!(
+            Some(("C60".to_string(), "Ruy Lopez".to_string())),
+            classify(&moves("e4 e5 Nf3 Nc6 Bb5 d6"))
+        );
+        assert_eq!(
+            Some(("A00".to_string(), "Uncommon Opening".to_string())),
+            classify(&moves("g4"))
+        );
+    }
+}
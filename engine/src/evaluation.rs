@@ -0,0 +1,559 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chess_match::ChessMatch,
+    piece_base::{ChessPiece, PieceColor, PieceType},
+    piece_location::PieceLocation,
+};
+
+/// Tunable weights for `evaluate`, in centipawns. Missing fields are read as their default,
+/// so saved parameters keep loading when terms are added.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    pub pawn_value: i32,
+    /// Bonus for having the move.
+    pub tempo_bonus: i32,
+    /// Bonus per legal move for each piece type.
+    pub knight_mobility: i32,
+    pub bishop_mobility: i32,
+    pub rook_mobility: i32,
+    pub queen_mobility: i32,
+    /// Bonus for having both bishops.
+    pub bishop_pair: i32,
+    /// How much of the piece-square table values to count, in percent.
+    pub piece_square_percent: i32,
+    /// Bonus per pawn on the three squares in front of the king.
+    pub king_shield: i32,
+    /// Penalty per enemy piece attacking the king or the squares around it.
+    pub king_attacker: i32,
+    /// Penalty per pawn beyond the first on a file.
+    pub doubled_pawn: i32,
+    /// Penalty per pawn with no friendly pawns on the files next to it.
+    pub isolated_pawn: i32,
+    /// Bonus per pawn with no enemy pawns ahead of it on its own or the next files.
+    pub passed_pawn: i32,
+}
+
+impl EvalParams {
+    pub fn mobility_weight(&self, piece_type: PieceType) -> i32 {
+        match piece_type {
+            PieceType::Knight => self.knight_mobility,
+            PieceType::Bishop => self.bishop_mobility,
+            PieceType::Rook => self.rook_mobility,
+            PieceType::Queen => self.queen_mobility,
+            PieceType::Pawn | PieceType::King => 0,
+        }
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            pawn_value: 100,
+            tempo_bonus: 10,
+            knight_mobility: 4,
+            bishop_mobility: 5,
+            rook_mobility: 2,
+            queen_mobility: 1,
+            bishop_pair: 30,
+            piece_square_percent: 100,
+            king_shield: 10,
+            king_attacker: 8,
+            doubled_pawn: 15,
+            isolated_pawn: 12,
+            passed_pawn: 20,
+        }
+    }
+}
+
+// Piece-square tables from white's point of view, laid out as the board is seen from the
+// white side: the first row is rank 8.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+/// How a position stands, from White's point of view, as an evaluation bar would show it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Evaluation {
+    /// Positive when White is better.
+    Centipawns(i32),
+    /// `winner` can force mate in `moves` moves, or has already mated at 0.
+    Mate { winner: PieceColor, moves: u32 },
+}
+
+impl Evaluation {
+    /// How much of an evaluation bar is White's, from 0.0 to 1.0. Centipawns are squashed so
+    /// that a pawn up is about 64% and a rook up about 95%.
+    pub fn white_share(&self) -> f64 {
+        match self {
+            Evaluation::Centipawns(cp) => 1.0 / (1.0 + 10f64.powf(-(*cp as f64) / 400.0)),
+            Evaluation::Mate {
+                winner: PieceColor::White,
+                ..
+            } => 1.0,
+            Evaluation::Mate {
+                winner: PieceColor::Black,
+                ..
+            } => 0.0,
+        }
+    }
+}
+
+/// In pawns like `+1.25`, or `#3` and `#-3` for White and Black mating in 3.
+impl Display for Evaluation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Evaluation::Centipawns(cp) => write!(f, "{:+.2}", *cp as f64 / 100.0),
+            Evaluation::Mate { winner, moves } => match winner {
+                PieceColor::White => write!(f, "#{}", moves),
+                PieceColor::Black => write!(f, "#-{}", moves),
+            },
+        }
+    }
+}
+
+/// Scores the position in centipawns from the point of view of the side to move, so a
+/// positive score means the side to move is better.
+pub fn evaluate(chess_match: &ChessMatch, params: &EvalParams) -> i32 {
+    let (_, color) = chess_match.get_current_turn_and_color();
+    evaluate_for(chess_match, color, params)
+}
+
+/// Scores the position in centipawns from `color`'s point of view: material, mobility, the
+/// bishop pair, piece placement, king safety and pawn structure, plus the tempo bonus when
+/// `color` has the move.
+pub fn evaluate_for(chess_match: &ChessMatch, color: PieceColor, params: &EvalParams) -> i32 {
+    let material = chess_match.material_balance() * params.pawn_value;
+    let material = match color {
+        PieceColor::White => material,
+        PieceColor::Black => -material,
+    };
+    let (_, to_move) = chess_match.get_current_turn_and_color();
+    let tempo = if to_move == color {
+        params.tempo_bonus
+    } else {
+        -params.tempo_bonus
+    };
+
+    let pieces = chess_match.get_pieces_in_play();
+    let static_terms = |color: PieceColor| -> i32 {
+        piece_squares(&pieces, color, params)
+            + pawn_structure(&pieces, color, params)
+            + king_safety(chess_match, &pieces, color, params)
+    };
+
+    material
+        + mobility(chess_match, color, params)
+        + bishop_pair(chess_match, color, params)
+        + static_terms(color)
+        - static_terms(color.opposite())
+        + tempo
+}
+
+/// The piece-square table value of a piece standing on `location`.
+pub fn piece_square_value(
+    piece_type: PieceType,
+    color: PieceColor,
+    location: &PieceLocation,
+) -> i32 {
+    let table = match piece_type {
+        PieceType::Pawn => &PAWN_TABLE,
+        PieceType::Knight => &KNIGHT_TABLE,
+        PieceType::Bishop => &BISHOP_TABLE,
+        PieceType::Rook => &ROOK_TABLE,
+        PieceType::Queen => &QUEEN_TABLE,
+        PieceType::King => &KING_TABLE,
+    };
+    // the tables list rank 8 first, so white's squares are found by flipping the rank
+    let index = match color {
+        PieceColor::White => location.index() ^ 56,
+        PieceColor::Black => location.index(),
+    };
+
+    table[index]
+}
+
+fn piece_squares(pieces: &[ChessPiece], color: PieceColor, params: &EvalParams) -> i32 {
+    let total: i32 = pieces
+        .iter()
+        .filter(|p| p.get_color() == color)
+        .map(|p| piece_square_value(p.get_type(), color, &p.location))
+        .sum();
+
+    total * params.piece_square_percent / 100
+}
+
+/// Doubled and isolated pawn penalties and passed pawn bonuses for `color`.
+fn pawn_structure(pieces: &[ChessPiece], color: PieceColor, params: &EvalParams) -> i32 {
+    let pawns = |color: PieceColor| -> Vec<(i32, i32)> {
+        pieces
+            .iter()
+            .filter(|p| p.get_color() == color && p.get_type() == PieceType::Pawn)
+            .map(|p| {
+                let (x, y) = p.location.get_x_y();
+                (x as i32, y as i32)
+            })
+            .collect()
+    };
+    let own = pawns(color);
+    let enemy = pawns(color.opposite());
+    let ahead = |rank: i32, other_rank: i32| match color {
+        PieceColor::White => other_rank > rank,
+        PieceColor::Black => other_rank < rank,
+    };
+
+    let mut pawns_per_file = [0; 8];
+    for (file, _) in &own {
+        pawns_per_file[*file as usize] += 1;
+    }
+    let doubled: i32 = pawns_per_file.iter().map(|n| (n - 1).max(0)).sum();
+
+    let mut score = -doubled * params.doubled_pawn;
+    for (file, rank) in &own {
+        if !own.iter().any(|(f, _)| (f - file).abs() == 1) {
+            score -= params.isolated_pawn;
+        }
+        if !enemy
+            .iter()
+            .any(|(f, r)| (f - file).abs() <= 1 && ahead(*rank, *r))
+        {
+            score += params.passed_pawn;
+        }
+    }
+
+    score
+}
+
+/// The pawn shield in front of `color`'s king, less the enemy pieces bearing down on it.
+fn king_safety(
+    chess_match: &ChessMatch,
+    pieces: &[ChessPiece],
+    color: PieceColor,
+    params: &EvalParams,
+) -> i32 {
+    let king = match pieces
+        .iter()
+        .find(|p| p.get_color() == color && p.get_type() == PieceType::King)
+    {
+        Some(king) => king,
+        None => return 0,
+    };
+    let forward = match color {
+        PieceColor::White => 1,
+        PieceColor::Black => -1,
+    };
+    let shield: Vec<PieceLocation> = (-1..=1)
+        .filter_map(|dx| king.location.offset(dx, forward))
+        .collect();
+    let shield_pawns = pieces
+        .iter()
+        .filter(|p| {
+            p.get_color() == color
+                && p.get_type() == PieceType::Pawn
+                && shield.contains(&p.location)
+        })
+        .count() as i32;
+    let (attackers, _) = chess_match.king_attack_balance(color);
+
+    shield_pawns * params.king_shield - attackers as i32 * params.king_attacker
+}
+
+/// The weighted mobility of `color` minus that of the opponent.
+fn mobility(chess_match: &ChessMatch, color: PieceColor, params: &EvalParams) -> i32 {
+    let score = |color: PieceColor| -> i32 {
+        chess_match
+            .mobility_by_type(color)
+            .iter()
+            .map(|(piece_type, count)| params.mobility_weight(*piece_type) * *count as i32)
+            .sum()
+    };
+
+    score(color) - score(color.opposite())
+}
+
+/// The bishop pair bonus of `color` minus that of the opponent.
+fn bishop_pair(chess_match: &ChessMatch, color: PieceColor, params: &EvalParams) -> i32 {
+    let bonus = |color: PieceColor| -> i32 {
+        if chess_match.has_bishop_pair(color) {
+            params.bishop_pair
+        } else {
+            0
+        }
+    };
+
+    bonus(color) - bonus(color.opposite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece_base::ChessPiece;
+    use uuid::Uuid;
+
+    /// Counts material only, so each test can switch on the term it looks at.
+    fn material_only() -> EvalParams {
+        EvalParams {
+            pawn_value: 100,
+            tempo_bonus: 0,
+            knight_mobility: 0,
+            bishop_mobility: 0,
+            rook_mobility: 0,
+            queen_mobility: 0,
+            bishop_pair: 0,
+            piece_square_percent: 0,
+            king_shield: 0,
+            king_attacker: 0,
+            doubled_pawn: 0,
+            isolated_pawn: 0,
+            passed_pawn: 0,
+        }
+    }
+
+    #[test]
+    fn test_evaluation_display_and_share() {
+        assert_eq!("+1.25", Evaluation::Centipawns(125).to_string());
+        assert_eq!("-0.40", Evaluation::Centipawns(-40).to_string());
+        let mate = |winner, moves| Evaluation::Mate { winner, moves };
+        assert_eq!("#3", mate(PieceColor::White, 3).to_string());
+        assert_eq!("#-2", mate(PieceColor::Black, 2).to_string());
+
+        assert_eq!(0.5, Evaluation::Centipawns(0).white_share());
+        assert!(Evaluation::Centipawns(100).white_share() > 0.6);
+        assert!(Evaluation::Centipawns(-100).white_share() < 0.4);
+        assert_eq!(1.0, mate(PieceColor::White, 3).white_share());
+        assert_eq!(0.0, mate(PieceColor::Black, 0).white_share());
+    }
+
+    #[test]
+    fn test_tempo_bonus_goes_to_side_to_move() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        let params = EvalParams {
+            tempo_bonus: 15,
+            ..material_only()
+        };
+
+        assert_eq!(15, evaluate(&chess_match, &params));
+
+        chess_match
+            .set_position_uci("startpos moves e2e4 d7d5 e4d5")
+            .unwrap();
+        // black is a pawn down but has the move
+        assert_eq!(-100 + 15, evaluate(&chess_match, &params));
+    }
+
+    #[test]
+    fn test_mobility_term() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_position_uci("startpos moves e2e4").unwrap();
+        let params = EvalParams {
+            knight_mobility: 4,
+            bishop_mobility: 5,
+            rook_mobility: 2,
+            queen_mobility: 1,
+            ..material_only()
+        };
+
+        // e4 frees white's bishop (5 moves) and queen (4 moves) and adds Ne2
+        assert_eq!(-(5 * 5 + 4 + 4), evaluate(&chess_match, &params));
+    }
+
+    #[test]
+    fn test_bishop_pair_bonus() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        // white trades a bishop for a knight, leaving black with the pair
+        chess_match
+            .set_position_uci("startpos moves e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5c6 d7c6 e1g1")
+            .unwrap();
+        let without_pair = EvalParams {
+            bishop_pair: 0,
+            ..EvalParams::default()
+        };
+        let with_pair = EvalParams {
+            bishop_pair: 40,
+            ..EvalParams::default()
+        };
+
+        assert_eq!(
+            evaluate(&chess_match, &without_pair) + 40,
+            evaluate(&chess_match, &with_pair)
+        );
+    }
+
+    fn place(pieces: Vec<(PieceType, PieceColor, &str)>) -> ChessMatch {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(
+            pieces
+                .into_iter()
+                .map(|(piece_type, color, square)| {
+                    ChessPiece::new(
+                        piece_type,
+                        color,
+                        PieceLocation::new_from_string(square).unwrap(),
+                        piece_type.get_points(),
+                    )
+                })
+                .collect(),
+        );
+        chess_match.calculate_valid_moves();
+        chess_match
+    }
+
+    #[test]
+    fn test_piece_square_tables() {
+        let square = |s: &str| PieceLocation::new_from_string(s).unwrap();
+        assert_eq!(
+            20,
+            piece_square_value(PieceType::Knight, PieceColor::White, &square("e4"))
+        );
+        assert_eq!(
+            -50,
+            piece_square_value(PieceType::Knight, PieceColor::White, &square("a1"))
+        );
+        // the tables are mirrored for black
+        assert_eq!(
+            piece_square_value(PieceType::Pawn, PieceColor::White, &square("d2")),
+            piece_square_value(PieceType::Pawn, PieceColor::Black, &square("d7"))
+        );
+
+        let params = EvalParams {
+            piece_square_percent: 50,
+            ..material_only()
+        };
+        let chess_match = place(vec![
+            (PieceType::King, PieceColor::White, "g1"),
+            (PieceType::Knight, PieceColor::White, "e4"),
+            (PieceType::King, PieceColor::Black, "g8"),
+            (PieceType::Knight, PieceColor::Black, "a8"),
+        ]);
+        // white: king 30 + knight 20, black: king 30 + knight -50, at half weight
+        assert_eq!(
+            (50 - (-20)) / 2,
+            evaluate_for(&chess_match, PieceColor::White, &params)
+        );
+        assert_eq!(-35, evaluate_for(&chess_match, PieceColor::Black, &params));
+    }
+
+    #[test]
+    fn test_pawn_structure() {
+        let params = EvalParams {
+            doubled_pawn: 15,
+            isolated_pawn: 12,
+            passed_pawn: 20,
+            ..material_only()
+        };
+        // white's c-pawns are doubled and isolated, and neither is passed because of b7;
+        // black's pawns support each other and a6 is passed
+        let chess_match = place(vec![
+            (PieceType::King, PieceColor::White, "h1"),
+            (PieceType::Pawn, PieceColor::White, "c2"),
+            (PieceType::Pawn, PieceColor::White, "c3"),
+            (PieceType::King, PieceColor::Black, "h8"),
+            (PieceType::Pawn, PieceColor::Black, "a6"),
+            (PieceType::Pawn, PieceColor::Black, "b7"),
+        ]);
+
+        let white = -15 - 2 * 12;
+        let black = 20;
+        assert_eq!(
+            white - black,
+            evaluate_for(&chess_match, PieceColor::White, &params)
+        );
+    }
+
+    #[test]
+    fn test_king_safety() {
+        let params = EvalParams {
+            king_shield: 10,
+            king_attacker: 8,
+            ..material_only()
+        };
+        let chess_match = place(vec![
+            (PieceType::King, PieceColor::White, "g1"),
+            (PieceType::Pawn, PieceColor::White, "f2"),
+            (PieceType::Pawn, PieceColor::White, "g2"),
+            (PieceType::Pawn, PieceColor::White, "h2"),
+            (PieceType::King, PieceColor::Black, "g8"),
+            (PieceType::Rook, PieceColor::Black, "a1"),
+        ]);
+
+        // white has a full shield but the rook attacks its back rank, black has no shield
+        // and nothing attacking it
+        let material = (3 - 5) * 100;
+        assert_eq!(
+            material + 3 * 10 - 8,
+            evaluate_for(&chess_match, PieceColor::White, &params)
+        );
+    }
+}
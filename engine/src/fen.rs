@@ -0,0 +1,91 @@
+use std::fmt::Display;
+
+use crate::piece_base::{PieceColor, PieceType};
+
+/// The FEN of the standard starting position.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    InvalidPlacement(String),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidClock(String),
+    InvalidPosition(String),
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::WrongFieldCount(count) => {
+                write!(f, "Expected 4 or 6 FEN fields, found {}", count)
+            }
+            FenError::InvalidPlacement(placement) => {
+                write!(f, "Invalid piece placement: {}", placement)
+            }
+            FenError::InvalidSideToMove(side) => write!(f, "Invalid side to move: {}", side),
+            FenError::InvalidCastling(castling) => {
+                write!(f, "Invalid castling rights: {}", castling)
+            }
+            FenError::InvalidEnPassant(square) => {
+                write!(f, "Invalid en passant square: {}", square)
+            }
+            FenError::InvalidClock(clock) => write!(f, "Invalid move counter: {}", clock),
+            FenError::InvalidPosition(reason) => write!(f, "Invalid position: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// The FEN letter of a piece: upper case for white, lower case for black.
+pub fn piece_char(piece_type: PieceType, color: PieceColor) -> char {
+    let c = match piece_type {
+        PieceType::Pawn => 'p',
+        PieceType::Rook => 'r',
+        PieceType::Knight => 'n',
+        PieceType::Bishop => 'b',
+        PieceType::Queen => 'q',
+        PieceType::King => 'k',
+    };
+    match color {
+        PieceColor::White => c.to_ascii_uppercase(),
+        PieceColor::Black => c,
+    }
+}
+
+/// The piece a FEN letter stands for, if any.
+pub fn char_piece(c: char) -> Option<(PieceType, PieceColor)> {
+    let piece_type = match c.to_ascii_lowercase() {
+        'p' => PieceType::Pawn,
+        'r' => PieceType::Rook,
+        'n' => PieceType::Knight,
+        'b' => PieceType::Bishop,
+        'q' => PieceType::Queen,
+        'k' => PieceType::King,
+        _ => return None,
+    };
+    let color = if c.is_ascii_uppercase() {
+        PieceColor::White
+    } else {
+        PieceColor::Black
+    };
+
+    Some((piece_type, color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_piece_chars_round_trip() {
+        for c in "PRNBQKprnbqk".chars() {
+            let (piece_type, color) = char_piece(c).unwrap();
+            assert_eq!(c, piece_char(piece_type, color));
+        }
+        assert_eq!(None, char_piece('x'));
+    }
+}
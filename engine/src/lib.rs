@@ -1,6 +1,27 @@
+pub mod ai;
+pub mod analysis;
+pub mod board;
+pub mod board_diagram;
 pub mod chess_match;
+pub mod chess_move;
+pub mod clock;
+pub mod eco;
+pub mod evaluation;
+pub mod fen;
 pub mod match_helpers;
+pub mod match_snapshot;
 pub mod move_resolver;
 pub mod movement_log;
+pub mod opening_book;
+pub mod persistence;
 pub mod piece_base;
 pub mod piece_location;
+pub mod player;
+pub mod position_builder;
+pub mod save_format;
+pub mod search;
+pub mod tournament;
+pub mod transposition;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod zobrist;
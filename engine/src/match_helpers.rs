@@ -1,4 +1,5 @@
 use crate::{
+    board::Board,
     chess_match::ChessMatch,
     piece_base::{ChessPiece, PieceColor},
     piece_location::PieceLocation,
@@ -46,4 +47,36 @@ impl MatchHelpers {
 
         result
     }
+
+    /// Determines whether any piece of `attacking_color` attacks `location`, regardless of
+    /// whether the square is empty. Unlike the helpers above this does not rely on the
+    /// calculated valid captures, so it can be used on simulated positions without a full
+    /// recalculation.
+    pub fn is_location_attacked_by(
+        chess_match: &ChessMatch,
+        location: &PieceLocation,
+        attacking_color: &PieceColor,
+    ) -> bool {
+        chess_match
+            .get_board()
+            .is_attacked_by(Board::square(location), *attacking_color)
+    }
+
+    /// Returns every piece of `attacking_color` that attacks `location`, worked out from the
+    /// board geometry the same way as `is_location_attacked_by`.
+    pub fn get_attackers_of_location(
+        chess_match: &ChessMatch,
+        location: &PieceLocation,
+        attacking_color: &PieceColor,
+    ) -> Vec<ChessPiece> {
+        let attackers = chess_match
+            .get_board()
+            .attackers_of(Board::square(location), *attacking_color);
+
+        chess_match
+            .get_player_pieces_in_play(attacking_color)
+            .into_iter()
+            .filter(|p| attackers & (1 << Board::square(&p.location)) != 0)
+            .collect()
+    }
 }
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    chess_match::{CastlingRights, KingState, MatchResult},
+    chess_move::Move,
+    piece_base::{PieceColor, PieceType},
+    piece_location::PieceLocation,
+};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SnapshotPiece {
+    pub id: Uuid,
+    pub piece_type: PieceType,
+    pub color: PieceColor,
+}
+
+/// The whole state of a match as a single payload for clients, so rendering the game
+/// doesn't take a dozen separate calls.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MatchSnapshot {
+    pub match_id: Uuid,
+    /// Ranks from 8 down to 1, each holding files a to h.
+    pub board: Vec<Vec<Option<SnapshotPiece>>>,
+    pub side_to_move: PieceColor,
+    pub castling_rights: CastlingRights,
+    pub en_passant_target: Option<PieceLocation>,
+    pub legal_moves: Vec<Move>,
+    pub white_king_state: KingState,
+    pub black_king_state: KingState,
+    pub result: MatchResult,
+    pub move_history: Vec<String>,
+    pub material_balance: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_match::ChessMatch;
+
+    #[test]
+    fn test_snapshot_after_capture() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match
+            .set_position_uci("startpos moves e2e4 d7d5 e4d5")
+            .unwrap();
+
+        let snapshot = chess_match.snapshot();
+
+        assert_eq!(PieceColor::Black, snapshot.side_to_move);
+        assert_eq!(vec!["e4", "d5", "exd5"], snapshot.move_history);
+        assert_eq!(1, snapshot.material_balance);
+        assert_eq!(MatchResult::InProgress, snapshot.result);
+        assert!(snapshot.castling_rights.black_queen_side);
+        // d5 is on the fourth row from the top
+        let d5 = snapshot.board[3][3].as_ref().unwrap();
+        assert_eq!(PieceType::Pawn, d5.piece_type);
+        assert_eq!(PieceColor::White, d5.color);
+        assert!(serde_json::to_string(&snapshot).is_ok());
+    }
+}
@@ -1,12 +1,11 @@
-use std::collections::HashMap;
-
 use log::debug;
 use uuid::Uuid;
 
 use crate::{
+    board::Board,
     chess_match::{CastleSide, ChessMatch, KingCastleData, KingState},
-    match_helpers::MatchHelpers,
-    piece_base::{ChessPiece, LocationState, MoveDirection, PeekResult, PieceColor, PieceType},
+    chess_move::Move,
+    piece_base::{ChessPiece, PieceColor, PieceType},
     piece_location::PieceLocation,
 };
 
@@ -22,9 +21,19 @@ pub struct PieceValidMove {
     color: PieceColor,
 }
 
+/// A piece giving check, with the squares between it and the king that a move could
+/// block on. The squares are empty for knights, pawns and adjacent pieces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckInfo {
+    pub checker_id: Uuid,
+    pub location: PieceLocation,
+    pub ray: Vec<PieceLocation>,
+}
+
 #[derive(Debug)]
 pub struct CheckMateResult {
     pub king_state: KingState,
+    pub checkers: Vec<CheckInfo>,
     pub new_valid_moves: Vec<PieceValidMove>,
     pub new_valid_captures: Vec<PieceValidMove>,
 }
@@ -34,30 +43,173 @@ pub struct MoveResolver {}
 impl MoveResolver {
     pub fn calculate_valid_moves(&self, chess_match: &mut ChessMatch) {
         debug!("Calculating valid moves");
+        let board = chess_match.get_board();
         let mut pieces = chess_match.get_pieces_in_play();
-        for mut p in &mut pieces {
-            p.clear_all_moves();
-
-            match p.get_type() {
-                PieceType::Pawn => self.calculate_pawn_moves(&mut p, &chess_match),
-                PieceType::Rook => self.calculate_rook_moves(&mut p, &chess_match),
-                PieceType::Knight => self.calculate_knight_moves(&mut p, &chess_match),
-                PieceType::Bishop => self.calculate_bishop_moves(&mut p, &chess_match),
-                PieceType::Queen => self.calculate_queen_moves(&mut p, &chess_match),
-                PieceType::King => {
-                    // skip kings here, they need to be calculated last due to checking if their
-                    // valid moves/captures would put them into check
+        for p in &mut pieces {
+            self.calculate_piece_moves(p, chess_match, &board);
+        }
+        self.restrict_pinned_pieces(&mut pieces, &board);
+
+        chess_match.set_pieces(pieces);
+        self.calculate_kings(chess_match, &board);
+    }
+
+    /// Recalculates the valid moves of only the pieces a move can have affected: the pieces in
+    /// `piece_ids`, any piece whose moves can reach one of `changed_squares` (the squares
+    /// that were emptied or filled), and the kings. Gives the same result as
+    /// `calculate_valid_moves` as long as every changed square is listed.
+    pub fn recalculate_pieces(
+        &self,
+        chess_match: &mut ChessMatch,
+        piece_ids: &[Uuid],
+        changed_squares: &[PieceLocation],
+    ) {
+        debug!("Recalculating valid moves for {:?}", piece_ids);
+        let board = chess_match.get_board();
+        let mut pieces = chess_match.get_pieces_in_play();
+        // a king move can pin or unpin anything, so start over
+        if pieces
+            .iter()
+            .any(|p| piece_ids.contains(&p.id) && p.get_type() == PieceType::King)
+        {
+            return self.calculate_valid_moves(chess_match);
+        }
+
+        let kings: Vec<ChessPiece> = chess_match.get_kings();
+        for p in &mut pieces {
+            // pieces lined up with their king may have been pinned or freed
+            let lined_up = kings.iter().any(|k| {
+                let (x1, y1) = k.location.get_x_y();
+                let (x2, y2) = p.location.get_x_y();
+                k.get_color() == p.get_color()
+                    && (x1 == x2 || y1 == y2 || (x1 - x2).abs() == (y1 - y2).abs())
+            });
+            let affected = piece_ids.contains(&p.id)
+                || lined_up
+                || changed_squares
+                    .iter()
+                    .any(|square| self.piece_reaches_square(p, square, &board));
+            if affected {
+                self.calculate_piece_moves(p, chess_match, &board);
+            }
+        }
+        self.restrict_pinned_pieces(&mut pieces, &board);
+
+        chess_match.set_pieces(pieces);
+        self.calculate_kings(chess_match, &board);
+    }
+
+    /// Whether a change on `square` can alter the moves of `piece`, given the occupied
+    /// squares of the position. Errs on the side of including pawns.
+    fn piece_reaches_square(
+        &self,
+        piece: &ChessPiece,
+        square: &PieceLocation,
+        board: &Board,
+    ) -> bool {
+        let (x1, y1) = piece.location.get_x_y();
+        let (x2, y2) = square.get_x_y();
+        let dx = (x1 - x2).abs() as i32;
+        let dy = (y1 - y2).abs() as i32;
+        if dx == 0 && dy == 0 {
+            return true;
+        }
+
+        let clear_line = || {
+            piece
+                .location
+                .squares_between(square)
+                .iter()
+                .all(|l| !board.is_occupied(Board::square(l)))
+        };
+        match piece.get_type() {
+            PieceType::Pawn => dx <= 1 && dy <= 2,
+            PieceType::Knight => (dx == 1 && dy == 2) || (dx == 2 && dy == 1),
+            PieceType::Rook => (dx == 0 || dy == 0) && clear_line(),
+            PieceType::Bishop => dx == dy && clear_line(),
+            PieceType::Queen => (dx == 0 || dy == 0 || dx == dy) && clear_line(),
+            // kings are always recalculated
+            PieceType::King => false,
+        }
+    }
+
+    fn calculate_piece_moves(&self, p: &mut ChessPiece, chess_match: &ChessMatch, board: &Board) {
+        p.clear_all_moves();
+
+        match p.get_type() {
+            PieceType::Pawn => self.calculate_pawn_moves(p, chess_match, board),
+            PieceType::King => {
+                // skip kings here, they need to be calculated last due to checking if their
+                // valid moves/captures would put them into check
+            }
+            piece_type => {
+                let targets = board.attacks(piece_type, p.get_color(), Board::square(&p.location));
+                self.add_targets(p, targets, board);
+            }
+        }
+    }
+
+    /// Leaves the pieces pinned to their own king only the moves along the pin, since
+    /// stepping off it would expose the king.
+    fn restrict_pinned_pieces(&self, pieces: &mut [ChessPiece], board: &Board) {
+        let kings: Vec<(usize, PieceColor)> = pieces
+            .iter()
+            .filter(|p| p.get_type() == PieceType::King)
+            .map(|k| (Board::square(&k.location), k.get_color()))
+            .collect();
+        for (king_square, color) in kings {
+            for (pinned_square, ray) in board.pins(king_square, color) {
+                let pinned = pieces
+                    .iter_mut()
+                    .find(|p| Board::square(&p.location) == pinned_square);
+                if let Some(pinned) = pinned {
+                    let on_ray = |l: &PieceLocation| ray & (1 << Board::square(l)) != 0;
+                    let moves = pinned.get_valid_moves();
+                    let captures = pinned.get_valid_captures();
+                    pinned.clear_all_moves();
+                    moves
+                        .iter()
+                        .filter(|l| on_ray(l))
+                        .for_each(|l| pinned.add_valid_move(l));
+                    captures
+                        .iter()
+                        .filter(|l| on_ray(l))
+                        .for_each(|l| pinned.add_valid_capture(l));
                 }
             }
         }
+    }
 
+    /// Adds each of `targets` not held by the piece's own side as a valid move, or as a
+    /// valid capture when an opponent's piece stands there.
+    fn add_targets(&self, piece: &mut ChessPiece, targets: u64, board: &Board) {
+        let own = board.occupied_by(piece.get_color());
+        let opponent = board.occupied_by(piece.get_color().opposite());
+        for square in Board::squares(targets & !own) {
+            let location = Board::location(square);
+            if opponent & (1 << square) != 0 {
+                piece.add_valid_capture(&location);
+            } else {
+                piece.add_valid_move(&location);
+            }
+        }
+    }
+
+    fn calculate_kings(&self, chess_match: &mut ChessMatch, board: &Board) {
+        // castling availability is rebuilt from scratch along with the king moves
+        chess_match.white_king_castle.clear();
+        chess_match.black_king_castle.clear();
         let mut kings = chess_match.get_kings();
         kings.iter_mut().for_each(|k| {
-            self.calculate_king_moves(k, chess_match);
-            self.calculate_king_can_castle(k, chess_match);
+            k.clear_all_moves();
+            self.calculate_king_moves(k, board);
+            self.calculate_king_can_castle(k, chess_match, board);
         });
 
-        chess_match.set_pieces(pieces.clone());
+        for king in kings {
+            let king_id = king.id;
+            *chess_match.get_piece_by_id(&king_id) = king;
+        }
     }
 
     pub fn override_valid_moves(
@@ -81,18 +233,14 @@ impl MoveResolver {
     }
 
     pub fn is_king_in_check(&self, king: &ChessPiece, chess_match: &ChessMatch) -> KingState {
-        let location = king.location.clone();
-        let attacking_color = if king.get_color() == PieceColor::White {
-            PieceColor::Black
-        } else {
-            PieceColor::White
-        };
-
-        // detect if king is in check
-        let attacking_pieces =
-            MatchHelpers::get_pieces_with_valid_captures(chess_match, &location, &attacking_color);
-
-        if attacking_pieces.len() > 0 {
+        let attacking_color = king.get_color().opposite();
+
+        // detect if king is in check straight from the board, without needing the
+        // attackers' valid captures to be up to date
+        if chess_match
+            .get_board()
+            .is_attacked_by(Board::square(&king.location), attacking_color)
+        {
             return KingState::InCheck;
         }
 
@@ -109,60 +257,81 @@ impl MoveResolver {
         let color = king.get_color();
         let king_state = self.is_king_in_check(king, chess_match);
 
-        // iterate through all pieceses moves and captures, checking if each one results in the
-        // king still being in check
+        let mut board = chess_match.get_board();
+        let king_square = Board::square(&king.location);
+        let checkers = self.checkers(king, chess_match, &board);
+        // in double check only the king can move, in single check everything else has to
+        // capture the checker or block its ray
+        let answers_check = |to: usize, captured: usize| match checkers.as_slice() {
+            [] => true,
+            [check] => {
+                captured == Board::square(&check.location)
+                    || check.ray.iter().any(|l| Board::square(l) == to)
+            }
+            _ => false,
+        };
+
+        // iterate through all pieceses moves and captures, playing each one on a copy of the
+        // bitboard and checking if it results in the king still being in check
         let pieces = chess_match.get_pieces_in_play();
         for p in pieces {
-            p.get_valid_moves().iter().for_each(|m| {
-                let mut sim_result =
-                    self.simulate_move_or_capture(SimulateType::Move, chess_match, &p, m.clone());
-                self.calculate_valid_moves(&mut sim_result);
-                let sim_king = sim_result.get_piece_by_id_copy(&king.id);
-                let sim_king_state = self.is_king_in_check(&sim_king, &sim_result);
-                if sim_king_state == KingState::NotInCheck {
+            let from = Board::square(&p.location);
+            // the opponent's moves are kept as they are, only this king's side has to get
+            // out of check
+            let own_piece = p.get_color() == color;
+            let is_king = p.id == king.id;
+            let mut leaves_king_safe = |to: usize, captured: Option<usize>| {
+                if !own_piece {
+                    return true;
+                }
+                if !is_king && !answers_check(to, captured.unwrap_or(to)) {
+                    return false;
+                }
+                let undo = board.make_move(from, to, captured);
+                let sim_king_square = if is_king { to } else { king_square };
+                let safe = !board.is_attacked_by(sim_king_square, color.opposite());
+                board.unmake_move(undo);
+                safe
+            };
+
+            for m in p.get_valid_moves() {
+                if leaves_king_safe(Board::square(&m), None) {
                     new_valid_moves.push(PieceValidMove {
-                        piece_id: p.id.clone(),
-                        location: m.clone(),
-                        color: sim_king.get_color(),
+                        piece_id: p.id,
+                        location: m,
+                        color: p.get_color(),
                     });
                 }
-            });
+            }
 
-            p.get_valid_captures().iter().for_each(|c| {
-                let mut sim_result = self.simulate_move_or_capture(
-                    SimulateType::Capture,
-                    chess_match,
-                    &p,
-                    c.clone(),
-                );
-                self.calculate_valid_moves(&mut sim_result);
-                let sim_kings = sim_result.get_kings();
-                let sim_king = sim_kings.iter().find(|k| k.get_color() == king.get_color());
-                if sim_king.is_none() {
-                    return;
+            for c in p.get_valid_captures() {
+                let captured = if chess_match.is_en_passant_capture(&p, &c) {
+                    Board::square(&chess_match.en_passant_victim().unwrap().location)
+                } else {
+                    Board::square(&c)
+                };
+                if captured == king_square {
+                    continue;
                 }
-                let sim_king = sim_king.unwrap();
-                //let sim_king = sim_result.get_piece_by_id_copy(&king.id);
-                let sim_king_state = self.is_king_in_check(&sim_king, &sim_result);
-                if sim_king_state == KingState::NotInCheck {
+                if leaves_king_safe(Board::square(&c), Some(captured)) {
                     new_valid_captures.push(PieceValidMove {
-                        piece_id: p.id.clone(),
-                        location: c.clone(),
-                        color: sim_king.get_color(),
+                        piece_id: p.id,
+                        location: c,
+                        color: p.get_color(),
                     });
                 }
-            })
+            }
         }
 
         let player_new_valid_moves: Vec<PieceValidMove> = new_valid_moves
             .clone()
             .into_iter()
-            .filter(|m| m.color != color)
+            .filter(|m| m.color == color)
             .collect();
         let player_new_valid_captures: Vec<PieceValidMove> = new_valid_captures
             .clone()
             .into_iter()
-            .filter(|c| c.color != color)
+            .filter(|c| c.color == color)
             .collect();
         let new_king_state =
             if player_new_valid_moves.len() == 0 && player_new_valid_captures.len() == 0 {
@@ -177,11 +346,35 @@ impl MoveResolver {
 
         CheckMateResult {
             king_state: new_king_state,
+            checkers,
             new_valid_moves,
             new_valid_captures,
         }
     }
 
+    /// The pieces giving check to `king`, each with the squares a move could block on.
+    pub fn checkers(
+        &self,
+        king: &ChessPiece,
+        chess_match: &ChessMatch,
+        board: &Board,
+    ) -> Vec<CheckInfo> {
+        let king_square = Board::square(&king.location);
+        Board::squares(board.attackers_of(king_square, king.get_color().opposite()))
+            .filter_map(|square| chess_match.get_piece_at_location(Board::location(square)))
+            .map(|checker| CheckInfo {
+                checker_id: checker.id,
+                location: checker.location.clone(),
+                ray: Board::squares(Board::between(
+                    Board::square(&checker.location),
+                    king_square,
+                ))
+                .map(Board::location)
+                .collect(),
+            })
+            .collect()
+    }
+
     pub fn simulate_move_or_capture(
         &self,
         sim_type: SimulateType,
@@ -197,8 +390,13 @@ impl MoveResolver {
                 piece_copy.location = location.clone()
             }
             SimulateType::Capture => {
+                let capture_location = if chess_match.is_en_passant_capture(piece, &location) {
+                    chess_match.en_passant_victim().unwrap().location
+                } else {
+                    location.clone()
+                };
                 let piece_to_capture = match_copy
-                    .get_piece_at_location_mut(location.clone())
+                    .get_piece_at_location_mut(capture_location)
                     .unwrap();
                 piece_to_capture.set_captured();
                 let piece_copy = match_copy.get_piece_by_id(&piece.id);
@@ -209,42 +407,75 @@ impl MoveResolver {
         match_copy
     }
 
-    fn calculate_king_moves(&self, piece: &mut ChessPiece, chess_match: &ChessMatch) {
-        let directions = [
-            MoveDirection::NorthEast,
-            MoveDirection::SouthEast,
-            MoveDirection::NorthWest,
-            MoveDirection::SouthWest,
-            MoveDirection::East,
-            MoveDirection::South,
-            MoveDirection::West,
-            MoveDirection::North,
-        ];
-
-        for d in directions {
-            let peek = piece.peek_direction(chess_match, &d, None);
-            if peek.state == LocationState::Empty {
-                let location = peek.location.clone().unwrap();
-                let can_be_attacked =
-                    MatchHelpers::locations_can_be_attacked(vec![location.clone()], chess_match);
-                if can_be_attacked.len() == 0 {
-                    piece.add_valid_move(&location);
-                    continue;
-                }
-            }
+    /// Checks whether playing `chess_move` would leave the moving side's king under attack.
+    pub fn is_move_legal(&self, chess_match: &ChessMatch, chess_move: &Move) -> bool {
+        let piece = chess_match.get_piece_by_id_copy(&chess_move.piece_id);
+        let to = Board::square(&chess_move.to);
+        let captured = if !chess_move.capture {
+            None
+        } else if chess_match.is_en_passant_capture(&piece, &chess_move.to) {
+            chess_match
+                .en_passant_victim()
+                .map(|victim| Board::square(&victim.location))
+        } else {
+            Some(to)
+        };
+        let sim_board =
+            chess_match
+                .get_board()
+                .after_move(Board::square(&piece.location), to, captured);
+        let king = chess_match
+            .get_player_pieces_by_type(&piece.get_color(), &PieceType::King)
+            .into_iter()
+            .next();
 
-            if peek.state == LocationState::Capture {
-                let location = peek.location.clone().unwrap();
-                let can_be_attacked =
-                    MatchHelpers::locations_can_be_attacked(vec![location.clone()], chess_match);
-                if can_be_attacked.len() == 0 {
-                    piece.add_valid_capture(&location);
-                }
+        match king {
+            Some(king) => {
+                let king_square = if king.id == piece.id {
+                    to
+                } else {
+                    Board::square(&king.location)
+                };
+                !sim_board.is_attacked_by(king_square, piece.get_color().opposite())
             }
+            None => true,
         }
     }
 
-    fn calculate_king_can_castle(&self, piece: &mut ChessPiece, chess_match: &mut ChessMatch) {
+    fn calculate_king_moves(&self, piece: &mut ChessPiece, board: &Board) {
+        let opponent = piece.get_color().opposite();
+        let targets = Board::squares(board.attacks(
+            PieceType::King,
+            piece.get_color(),
+            Board::square(&piece.location),
+        ))
+        .filter(|square| !board.is_attacked_by(*square, opponent))
+        .fold(0, |targets, square| targets | 1 << square);
+
+        self.add_targets(piece, targets, board);
+    }
+
+    /// Whether the opponent attacks any square of `king_path` on `board`. The path is every
+    /// square the king occupies during the castle, starting with the one it stands on, since
+    /// a king can't castle out of check, through check or into check.
+    pub fn castling_path_is_attacked(
+        &self,
+        king: &ChessPiece,
+        king_path: Vec<&PieceLocation>,
+        board: &Board,
+    ) -> bool {
+        let opponent = king.get_color().opposite();
+        king_path
+            .iter()
+            .any(|loc| board.is_attacked_by(Board::square(loc), opponent))
+    }
+
+    fn calculate_king_can_castle(
+        &self,
+        piece: &mut ChessPiece,
+        chess_match: &mut ChessMatch,
+        board: &Board,
+    ) {
         if piece.get_type() != PieceType::King || !piece.is_first_move() {
             return;
         }
@@ -259,65 +490,42 @@ impl MoveResolver {
                 continue;
             }
 
-            let (file, rank) = rook.location.get_x_y();
-            let color = piece.get_color();
-            let rank = rank + 1f64;
-
-            if file == 0f64 {
-                // queen side
-                let file_b = PieceLocation::new_from_string(format!("b{}", rank).as_str()).unwrap();
-                let file_c = PieceLocation::new_from_string(format!("c{}", rank).as_str()).unwrap();
-                let file_d = PieceLocation::new_from_string(format!("d{}", rank).as_str()).unwrap();
+            let rank = rook.location.get_rank();
+            let rook_file = rook.location.get_file();
+            if rank != piece.location.get_rank() || (rook_file != "a" && rook_file != "h") {
+                continue;
+            }
 
-                let locations_can_be_attacked = MatchHelpers::locations_can_be_attacked(
-                    vec![file_b.clone(), file_c.clone(), file_d.clone()],
-                    chess_match,
-                );
+            let color = piece.get_color();
+            let location = |file: &str| PieceLocation::new(file.to_string(), rank);
 
-                let file_b_state = rook.peek_location(&file_b, chess_match);
-                let file_c_state = rook.peek_location(&file_c, chess_match);
-                let file_d_state = rook.peek_location(&file_d, chess_match);
-
-                if file_b_state == LocationState::Empty
-                    && file_c_state == LocationState::Empty
-                    && file_d_state == LocationState::Empty
-                    && locations_can_be_attacked.len() == 0
-                {
-                    self.add_valid_castle(
-                        piece,
-                        file_c,
-                        file_d,
-                        rook.id,
-                        color,
-                        CastleSide::QueenSide,
-                        chess_match,
-                    );
-                }
+            // every square between the king and the rook has to be empty, but only the
+            // squares the king stands on and crosses need to be safe from attack
+            let (king_target, rook_target, side) = if rook_file == "a" {
+                (location("c"), location("d"), CastleSide::QueenSide)
             } else {
-                // king side
-                let file_f = PieceLocation::new_from_string(format!("f{}", rank).as_str()).unwrap();
-                let file_g = PieceLocation::new_from_string(format!("g{}", rank).as_str()).unwrap();
-                let locations_can_be_attacked = MatchHelpers::locations_can_be_attacked(
-                    vec![file_f.clone(), file_g.clone()],
+                (location("g"), location("f"), CastleSide::KingSide)
+            };
+
+            let squares_are_empty = board.occupied()
+                & Board::between(
+                    Board::square(&piece.location),
+                    Board::square(&rook.location),
+                )
+                == 0;
+            let king_path = vec![&piece.location, &rook_target, &king_target];
+            let king_path_is_attacked = self.castling_path_is_attacked(piece, king_path, board);
+
+            if squares_are_empty && !king_path_is_attacked {
+                self.add_valid_castle(
+                    piece,
+                    king_target,
+                    rook_target,
+                    rook.id,
+                    color,
+                    side,
                     chess_match,
                 );
-                let file_f_state = rook.peek_location(&file_f, chess_match);
-                let file_g_state = rook.peek_location(&file_g, chess_match);
-
-                if file_f_state == LocationState::Empty
-                    && file_g_state == LocationState::Empty
-                    && locations_can_be_attacked.len() == 0
-                {
-                    self.add_valid_castle(
-                        piece,
-                        file_g,
-                        file_f,
-                        rook.id,
-                        color,
-                        CastleSide::KingSide,
-                        chess_match,
-                    );
-                }
             }
         }
     }
@@ -347,137 +555,394 @@ impl MoveResolver {
         }
     }
 
-    fn calculate_queen_moves(&self, piece: &mut ChessPiece, chess_match: &ChessMatch) {
-        let directions = [
-            MoveDirection::NorthEast,
-            MoveDirection::SouthEast,
-            MoveDirection::NorthWest,
-            MoveDirection::SouthWest,
-            MoveDirection::East,
-            MoveDirection::South,
-            MoveDirection::West,
-            MoveDirection::North,
-        ];
+    fn calculate_pawn_moves(
+        &self,
+        piece: &mut ChessPiece,
+        chess_match: &ChessMatch,
+        board: &Board,
+    ) {
+        let square = Board::square(&piece.location);
+        let forward = match piece.color {
+            PieceColor::White => 8,
+            PieceColor::Black => -8,
+        };
+        let one_step = square as i32 + forward;
+        if (0..64).contains(&one_step) && !board.is_occupied(one_step as usize) {
+            piece.add_valid_move(&Board::location(one_step as usize));
+
+            let two_steps = one_step + forward;
+            if piece.is_first_move()
+                && (0..64).contains(&two_steps)
+                && !board.is_occupied(two_steps as usize)
+            {
+                piece.add_valid_move(&Board::location(two_steps as usize));
+            }
+        }
 
-        for d in directions {
-            let peek = piece.peek_direction(chess_match, &d, None);
-            piece.walk_direction(&d, peek.location, chess_match, None, None);
+        let en_passant_target = chess_match
+            .en_passant_victim()
+            .filter(|v| v.get_color() != piece.get_color())
+            .and_then(|_| chess_match.get_en_passant_target());
+        let opponent = board.occupied_by(piece.get_color().opposite());
+        for target in Board::squares(board.attacks(PieceType::Pawn, piece.get_color(), square)) {
+            let location = Board::location(target);
+            if opponent & (1 << target) != 0 || en_passant_target.as_ref() == Some(&location) {
+                piece.add_valid_capture(&location);
+            }
         }
     }
+}
 
-    fn calculate_bishop_moves(&self, piece: &mut ChessPiece, chess_match: &ChessMatch) {
-        let directions = [
-            MoveDirection::NorthEast,
-            MoveDirection::SouthEast,
-            MoveDirection::NorthWest,
-            MoveDirection::SouthWest,
-        ];
+#[cfg(test)]
+mod test {
+    use uuid::Uuid;
 
-        for d in directions {
-            let peek = piece.peek_direction(chess_match, &d, None);
-            piece.walk_direction(&d, peek.location, chess_match, None, None);
-        }
-    }
+    use super::*;
+    use crate::match_helpers::MatchHelpers;
 
-    fn calculate_knight_moves(&self, piece: &mut ChessPiece, chess_match: &ChessMatch) {
-        let directions = [
-            MoveDirection::East,
-            MoveDirection::South,
-            MoveDirection::West,
-            MoveDirection::North,
-        ];
+    #[test]
+    fn test_calculate_moves() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
 
-        let secondary_directions: HashMap<MoveDirection, Vec<MoveDirection>> = HashMap::from([
-            (
-                MoveDirection::North,
-                vec![MoveDirection::NorthEast, MoveDirection::NorthWest],
-            ),
-            (
-                MoveDirection::East,
-                vec![MoveDirection::NorthEast, MoveDirection::SouthEast],
-            ),
-            (
-                MoveDirection::South,
-                vec![MoveDirection::SouthEast, MoveDirection::SouthWest],
-            ),
-            (
-                MoveDirection::West,
-                vec![MoveDirection::NorthWest, MoveDirection::SouthWest],
-            ),
+        //println!("{:?}", chess_match);
+    }
+
+    fn castling_match(black_rook: &str) -> ChessMatch {
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, location("e1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, location("a1"), 5),
+            ChessPiece::new(PieceType::King, PieceColor::Black, location("e8"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::Black, location(black_rook), 5),
         ]);
+        chess_match.calculate_valid_moves();
+        chess_match
+    }
 
-        for d in directions {
-            let peek = piece.peek_direction(chess_match, &d, None);
-            if peek.state == LocationState::OutOfBounds {
-                continue;
-            }
+    #[test]
+    fn test_queen_side_castle_ignores_attack_on_rook_path() {
+        let chess_match = castling_match("b8");
+        let king = &chess_match.get_kings()[0];
 
-            let secondary_direction = secondary_directions.get(&d).unwrap();
-            for dd in secondary_direction {
-                let loc = peek.clone().location.unwrap();
-                let result = piece.peek_direction(chess_match, dd, Some(&loc));
-                if result.state == LocationState::Empty {
-                    piece.add_valid_move(&result.location.unwrap());
-                    continue;
-                }
+        assert!(king
+            .get_valid_moves()
+            .contains(&PieceLocation::new_from_string("c1").unwrap()));
+    }
 
-                if result.state == LocationState::Capture {
-                    piece.add_valid_capture(&result.location.unwrap());
-                }
-            }
-        }
+    #[test]
+    fn test_queen_side_castle_blocked_by_attack_on_king_path() {
+        let chess_match = castling_match("d8");
+        let king = &chess_match.get_kings()[0];
+
+        assert!(!king
+            .get_valid_moves()
+            .contains(&PieceLocation::new_from_string("c1").unwrap()));
+        assert!(chess_match.white_king_castle.is_empty());
     }
 
-    fn calculate_rook_moves(&self, piece: &mut ChessPiece, chess_match: &ChessMatch) {
-        let directions = [
-            MoveDirection::East,
-            MoveDirection::South,
-            MoveDirection::West,
-            MoveDirection::North,
-        ];
+    #[test]
+    fn test_no_castling_out_of_check() {
+        let chess_match = castling_match("e5");
+        let king = &chess_match.get_kings()[0];
+
+        assert!(!king
+            .get_valid_moves()
+            .contains(&PieceLocation::new_from_string("c1").unwrap()));
+        assert!(chess_match.white_king_castle.is_empty());
+    }
 
-        for d in directions {
-            let peek = piece.peek_direction(chess_match, &d, None);
-            piece.walk_direction(&d, peek.location, chess_match, None, None);
-        }
+    #[test]
+    fn test_king_side_castle_blocked_by_attack_on_crossed_square() {
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, location("e1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, location("h1"), 5),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, location("a1"), 5),
+            ChessPiece::new(PieceType::King, PieceColor::Black, location("e8"), 0),
+            ChessPiece::new(PieceType::Bishop, PieceColor::Black, location("c4"), 3),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        // the bishop covers f1, which the king would have to cross
+        assert_eq!(1, chess_match.white_king_castle.len());
+        assert_eq!(CastleSide::QueenSide, chess_match.white_king_castle[0].side);
     }
 
-    fn calculate_pawn_moves(&self, piece: &mut ChessPiece, chess_match: &ChessMatch) {
-        //debug!("Calculating pawn moves");
-        let forward_results = piece.peek_forward(chess_match);
-        //debug!("Found {} Forward moves", forward_results.len());
-        //debug!("Pawn location: {:?}", piece.location);
-        for r in forward_results {
-            if r.state == LocationState::Empty {
-                piece.add_valid_move(&r.location.unwrap());
-            }
-        }
+    #[test]
+    fn test_black_queen_side_castle_allowed_with_b8_attacked() {
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, location("e1"), 0),
+            ChessPiece::new(PieceType::Bishop, PieceColor::White, location("e5"), 3),
+            ChessPiece::new(PieceType::King, PieceColor::Black, location("e8"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::Black, location("a8"), 5),
+        ]);
+        chess_match.calculate_valid_moves();
 
-        let directions = match piece.color {
-            PieceColor::White => [MoveDirection::NorthEast, MoveDirection::NorthWest],
-            PieceColor::Black => [MoveDirection::SouthEast, MoveDirection::SouthWest],
-        };
+        // b8 only has to be empty for the rook, the bishop attacking it doesn't matter
+        assert!(MatchHelpers::is_location_attacked_by(
+            &chess_match,
+            &location("b8"),
+            &PieceColor::White
+        ));
+        assert_eq!(1, chess_match.black_king_castle.len());
+        assert_eq!(
+            location("c8"),
+            chess_match.black_king_castle[0].king_target_location
+        );
+    }
 
-        for d in directions {
-            let direction_result = piece.peek_direction(chess_match, &d, None);
-            if direction_result.state == LocationState::Capture {
-                piece.add_valid_capture(&direction_result.location.unwrap());
-            }
+    /// Checks the moves worked out after the last move, which only recalculates the pieces
+    /// the move affected, against a full recalculation.
+    fn assert_matches_full_calculation(chess_match: &ChessMatch, context: &str) {
+        let mut full = chess_match.copy();
+        full.calculate_valid_moves();
+
+        assert_eq!(
+            full.get_pieces_in_play().len(),
+            chess_match.get_pieces_in_play().len(),
+            "{}",
+            context
+        );
+        for p in full.get_pieces_in_play() {
+            let q = chess_match.get_piece_by_id_copy(&p.id);
+            assert_eq!(p.get_valid_moves(), q.get_valid_moves(), "{}", context);
+            assert_eq!(
+                p.get_valid_captures(),
+                q.get_valid_captures(),
+                "{}",
+                context
+            );
         }
+        assert_eq!(
+            full.get_white_king_state(),
+            chess_match.get_white_king_state(),
+            "{}",
+            context
+        );
+        assert_eq!(
+            full.get_black_king_state(),
+            chess_match.get_black_king_state(),
+            "{}",
+            context
+        );
     }
-}
 
-#[cfg(test)]
-mod test {
-    use uuid::Uuid;
+    #[test]
+    fn test_pinned_pieces_only_move_along_the_pin() {
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, location("e1"), 0),
+            ChessPiece::new(PieceType::Knight, PieceColor::White, location("d2"), 3),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, location("e4"), 5),
+            ChessPiece::new(PieceType::King, PieceColor::Black, location("h8"), 0),
+            ChessPiece::new(PieceType::Bishop, PieceColor::Black, location("b4"), 3),
+            ChessPiece::new(PieceType::Queen, PieceColor::Black, location("e7"), 9),
+        ]);
+        chess_match.calculate_valid_moves();
 
-    use super::*;
+        let knight = chess_match.get_piece_at_location(location("d2")).unwrap();
+        assert!(knight.get_valid_moves().is_empty());
+        assert!(knight.get_valid_captures().is_empty());
+
+        let rook = chess_match.get_piece_at_location(location("e4")).unwrap();
+        let mut rook_moves = rook.get_valid_moves();
+        rook_moves.sort_by_key(|l| l.index());
+        assert_eq!(
+            vec![
+                location("e2"),
+                location("e3"),
+                location("e5"),
+                location("e6")
+            ],
+            rook_moves
+        );
+        assert_eq!(vec![location("e7")], rook.get_valid_captures());
+    }
 
-    #[test]
-    fn test_calculate_moves() {
+    fn check_match(double_check: bool) -> ChessMatch {
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let mut pieces = vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, location("e1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, location("a4"), 5),
+            ChessPiece::new(PieceType::Bishop, PieceColor::White, location("b5"), 3),
+            ChessPiece::new(PieceType::King, PieceColor::Black, location("h8"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::Black, location("e8"), 5),
+        ];
+        if double_check {
+            pieces.push(ChessPiece::new(
+                PieceType::Knight,
+                PieceColor::Black,
+                location("d3"),
+                3,
+            ));
+        }
         let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(pieces);
         chess_match.calculate_valid_moves();
+        chess_match
+    }
 
-        //println!("{:?}", chess_match);
+    #[test]
+    fn test_single_check_only_allows_blocks_and_captures() {
+        let chess_match = check_match(false);
+        let king = chess_match.get_kings()[0].clone();
+        let result = MoveResolver {}.is_king_in_check_or_stale_mate(&king, &chess_match);
+
+        let rook = chess_match.get_piece_at_location(PieceLocation::new_from_string("e8").unwrap());
+        assert_eq!(1, result.checkers.len());
+        assert_eq!(rook.unwrap().id, result.checkers[0].checker_id);
+        let ray: Vec<String> = result.checkers[0]
+            .ray
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(vec!["e2", "e3", "e4", "e5", "e6", "e7"], ray);
+
+        let mut moves: Vec<String> = chess_match
+            .legal_moves()
+            .iter()
+            .filter(|m| m.from != king.location)
+            .map(|m| m.to_string())
+            .collect();
+        moves.sort();
+        assert_eq!(vec!["a4e4", "b5e2", "b5e8"], moves);
+    }
+
+    #[test]
+    fn test_double_check_only_allows_king_moves() {
+        let chess_match = check_match(true);
+        let king = chess_match.get_kings()[0].clone();
+        let result = MoveResolver {}.is_king_in_check_or_stale_mate(&king, &chess_match);
+
+        assert_eq!(2, result.checkers.len());
+        assert!(result.checkers.iter().any(|c| c.ray.is_empty()));
+        assert!(!chess_match.legal_moves().is_empty());
+        assert!(chess_match
+            .legal_moves()
+            .iter()
+            .all(|m| m.from == king.location));
+    }
+
+    #[test]
+    fn test_recalculate_pieces_matches_full_calculation() {
+        let location = |l: &str| PieceLocation::new_from_string(l).unwrap();
+        let setup = |pieces: &[(PieceType, PieceColor, &str)], moves: &str| {
+            let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+            if !pieces.is_empty() {
+                chess_match.set_pieces(
+                    pieces
+                        .iter()
+                        .map(|(t, c, l)| ChessPiece::new(*t, *c, location(l), 0))
+                        .collect(),
+                );
+            }
+            chess_match.calculate_valid_moves();
+            for m in moves.split_whitespace() {
+                chess_match.apply_uci_move(m).unwrap();
+            }
+            chess_match
+        };
+        let (white, black) = (PieceColor::White, PieceColor::Black);
+        use PieceType::{Bishop, King, Knight, Pawn, Queen, Rook};
+        let pinned = [
+            (King, white, "e1"),
+            (Bishop, white, "f1"),
+            (Rook, white, "e3"),
+            (King, black, "e8"),
+            (Queen, black, "d8"),
+            (Knight, black, "c6"),
+            (Knight, black, "e7"),
+        ];
+        let positions = [
+            // both sides can castle either way
+            setup(
+                &[
+                    (King, white, "e1"),
+                    (Rook, white, "a1"),
+                    (Rook, white, "h1"),
+                    (Pawn, white, "a2"),
+                    (Pawn, white, "h2"),
+                    (King, black, "e8"),
+                    (Rook, black, "a8"),
+                    (Rook, black, "h8"),
+                    (Bishop, black, "b7"),
+                    (Pawn, black, "a7"),
+                    (Pawn, black, "h7"),
+                ],
+                "",
+            ),
+            // en passant on d6, with another double step to come
+            setup(
+                &[
+                    (King, white, "e1"),
+                    (Pawn, white, "e4"),
+                    (King, black, "e8"),
+                    (Pawn, black, "d7"),
+                    (Pawn, black, "f7"),
+                ],
+                "e4e5 d7d5",
+            ),
+            // promotions on both sides, with and without a capture
+            setup(
+                &[
+                    (King, white, "e1"),
+                    (Rook, white, "h1"),
+                    (Pawn, white, "b7"),
+                    (Pawn, white, "g7"),
+                    (King, black, "e8"),
+                    (Rook, black, "a8"),
+                    (Pawn, black, "b2"),
+                    (Pawn, black, "g2"),
+                ],
+                "",
+            ),
+            // both knights pinned, and a check to give
+            setup(&pinned, "f1b5"),
+            // and to answer
+            setup(&pinned, "f1b5 d8a5"),
+        ];
+        let (mut castles, mut en_passants, mut promotions) = (0, 0, 0);
+        let mut count = |chess_match: &ChessMatch, m: &Move| {
+            let piece_type = chess_match.get_piece_by_id_copy(&m.piece_id).get_type();
+            castles +=
+                (piece_type == PieceType::King && m.from.chebyshev_distance(&m.to) == 2) as u32;
+            promotions += (piece_type == PieceType::Pawn
+                && (m.to.get_rank() == 1 || m.to.get_rank() == 8)) as u32;
+            en_passants +=
+                (m.capture && chess_match.get_en_passant_target() == Some(m.to.clone())) as u32;
+        };
+
+        // every move from each position
+        for (i, start) in positions.iter().enumerate() {
+            for m in start.legal_moves() {
+                count(start, &m);
+                let mut chess_match = start.copy();
+                chess_match.apply_move(&m);
+                assert_matches_full_calculation(&chess_match, &format!("position {} {}", i, m));
+            }
+        }
+
+        // and whole games from the start
+        for seed in 0..3 {
+            let mut engine = crate::ai::Engine::with_seed(seed);
+            let mut chess_match = setup(&[], "");
+            for ply in 0..10 {
+                let m = match engine.random_move(&chess_match) {
+                    Some(m) => m,
+                    None => break,
+                };
+                let context = format!("seed {} ply {} {}", seed, ply, m);
+                chess_match.apply_move(&m);
+                assert_matches_full_calculation(&chess_match, &context);
+            }
+        }
+
+        assert!(castles > 0 && en_passants > 0 && promotions > 0);
     }
 }
@@ -1,15 +1,23 @@
 use std::fmt::Display;
 
+use chrono::{DateTime, Utc};
 use log::info;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{chess_match::ChessMatch, piece_base::PieceType, piece_location::PieceLocation};
+use crate::{
+    analysis::Annotation,
+    chess_match::{CastleSide, ChessMatch, MatchResult},
+    piece_base::{PieceColor, PieceType},
+    piece_location::PieceLocation,
+};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct MovementLogEntry {
     id: Uuid,
-    time_span: u32, // time since previous entry
+    time_span: u32, // milliseconds the move took
+    #[serde(default)]
+    timestamp: Option<DateTime<Utc>>,
     player_id: Uuid,
     notation: String,
     piece_id: Uuid,
@@ -21,6 +29,17 @@ pub struct MovementLogEntry {
     opponent_king_in_checkmate: bool,
     castled_king_side: bool,
     castled_queen_side: bool,
+    #[serde(default)]
+    en_passant: bool,
+    #[serde(default)]
+    promoted_to: Option<PieceType>,
+    #[serde(default)]
+    disambiguation: String,
+    #[serde(default)]
+    annotation: Option<Annotation>,
+    /// How many centipawns the move gave away against the best move, once analysed.
+    #[serde(default)]
+    centipawn_loss: Option<i32>,
 }
 impl Display for MovementLogEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -48,7 +67,13 @@ impl MovementLogEntry {
             opponent_king_in_checkmate: false,
             castled_king_side: false,
             castled_queen_side: false,
+            en_passant: false,
+            promoted_to: None,
+            disambiguation: String::new(),
+            annotation: None,
+            centipawn_loss: None,
             time_span: 0,
+            timestamp: None,
         }
     }
 
@@ -57,6 +82,12 @@ impl MovementLogEntry {
         self
     }
 
+    /// When the move was made.
+    pub fn timestamp(&mut self, timestamp: DateTime<Utc>) -> &mut MovementLogEntry {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
     pub fn captured(&mut self, captured_piece_id: Uuid) -> &mut MovementLogEntry {
         self.captured_piece_id = Some(captured_piece_id);
         self.piece_captured = true;
@@ -83,11 +114,63 @@ impl MovementLogEntry {
         self
     }
 
+    pub fn en_passant(&mut self) -> &mut MovementLogEntry {
+        self.en_passant = true;
+        self
+    }
+
+    pub fn promoted(&mut self, piece_type: PieceType) -> &mut MovementLogEntry {
+        self.promoted_to = Some(piece_type);
+        self
+    }
+
+    /// File and/or rank of the start location, needed when another piece of the same type
+    /// could also have moved to the end location.
+    pub fn disambiguation(&mut self, disambiguation: String) -> &mut MovementLogEntry {
+        self.disambiguation = disambiguation;
+        self
+    }
+
     pub fn notation(&mut self, notation: String) -> &mut MovementLogEntry {
         self.notation = notation;
         self
     }
 
+    /// What analysing the move found, see `analysis::Analyzer`.
+    pub fn annotation(
+        &mut self,
+        annotation: Option<Annotation>,
+        centipawn_loss: i32,
+    ) -> &mut MovementLogEntry {
+        self.annotation = annotation;
+        self.centipawn_loss = Some(centipawn_loss);
+        self
+    }
+
+    pub fn get_time_span(&self) -> u32 {
+        self.time_span
+    }
+
+    pub fn get_timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+
+    pub fn get_player_id(&self) -> Uuid {
+        self.player_id
+    }
+
+    pub fn get_piece_id(&self) -> Uuid {
+        self.piece_id
+    }
+
+    pub fn get_captured_piece_id(&self) -> Option<Uuid> {
+        self.captured_piece_id
+    }
+
+    pub fn get_promoted_to(&self) -> Option<PieceType> {
+        self.promoted_to
+    }
+
     pub fn get_start_location(&self) -> PieceLocation {
         self.start_location.clone()
     }
@@ -96,9 +179,94 @@ impl MovementLogEntry {
         self.end_location.clone()
     }
 
+    /// Whether the capture was en passant, in which case the captured pawn was not on the
+    /// end location but directly behind it.
+    pub fn is_en_passant(&self) -> bool {
+        self.en_passant
+    }
+
+    pub fn get_castle_side(&self) -> Option<CastleSide> {
+        if self.castled_king_side {
+            Some(CastleSide::KingSide)
+        } else if self.castled_queen_side {
+            Some(CastleSide::QueenSide)
+        } else {
+            None
+        }
+    }
+
+    /// Whether this move was a capture or a pawn move, either of which restarts the count
+    /// towards the fifty-move rule. Pawn moves are the only ones notated starting with the
+    /// file letter.
+    pub fn resets_halfmove_clock(&self) -> bool {
+        self.piece_captured
+            || self.promoted_to.is_some()
+            || self
+                .notation
+                .starts_with(|c: char| ('a'..='h').contains(&c))
+    }
+
     pub fn get_notation(&self) -> String {
         self.notation.clone()
     }
+
+    pub fn get_annotation(&self) -> Option<Annotation> {
+        self.annotation
+    }
+
+    pub fn get_centipawn_loss(&self) -> Option<i32> {
+        self.centipawn_loss
+    }
+
+    /// The notation with the annotation's symbol after it, e.g. `Nf6??`.
+    pub fn get_annotated_notation(&self) -> String {
+        match self.annotation {
+            Some(annotation) => format!("{}{}", self.notation, annotation.symbol()),
+            None => self.notation.clone(),
+        }
+    }
+}
+
+/// How long one player has spent on their moves.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct PlayerTimes {
+    pub moves: u32,
+    pub total_ms: u64,
+    pub average_ms: u64,
+    pub longest_ms: u64,
+}
+
+impl PlayerTimes {
+    fn add(&mut self, time_ms: u64) {
+        self.moves += 1;
+        self.total_ms += time_ms;
+        self.average_ms = self.total_ms / self.moves as u64;
+        self.longest_ms = self.longest_ms.max(time_ms);
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct TimeReport {
+    pub white: PlayerTimes,
+    pub black: PlayerTimes,
+}
+
+impl Display for TimeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = |ms: u64| ms as f64 / 1000.0;
+        for (name, times) in [("White", self.white), ("Black", self.black)] {
+            writeln!(
+                f,
+                "{}: {} moves, {:.1}s total, {:.1}s average, {:.1}s longest",
+                name,
+                times.moves,
+                seconds(times.total_ms),
+                seconds(times.average_ms),
+                seconds(times.longest_ms)
+            )?;
+        }
+        Ok(())
+    }
 }
 
 pub struct MovementLogger {}
@@ -110,10 +278,16 @@ impl MovementLogger {
     ) -> MovementLogEntry {
         let mut entry = entry.clone();
         let piece = chess_match.get_piece_by_id_copy(&entry.piece_id);
-        let piece_text = piece.get_notation_text();
+        // a promoted piece is notated as the pawn that made the move
+        let moved_type = if entry.promoted_to.is_some() {
+            PieceType::Pawn
+        } else {
+            piece.get_type()
+        };
+        let piece_text = moved_type.get_notation_text();
         let start_location_text = entry.get_start_location();
         let captured_text = if entry.piece_captured {
-            if piece.get_type() == PieceType::Pawn {
+            if moved_type == PieceType::Pawn {
                 format!("{}x", start_location_text.get_file())
             } else {
                 "x".to_string()
@@ -122,17 +296,10 @@ impl MovementLogger {
             "".to_string()
         };
         let end_location_text = entry.get_end_location().to_string();
-        if entry.castled_king_side {
-            return entry.notation("O-O".to_string()).clone();
-        }
-        if entry.castled_queen_side {
-            return entry.notation("O-O-O".to_string()).clone();
-        }
-        if piece.got_promoted() {
-            return entry
-                .notation(format!("{}={}", end_location_text, piece_text))
-                .clone();
-        }
+        let promotion_text = match entry.promoted_to {
+            Some(piece_type) => format!("={}", piece_type.get_notation_text()),
+            None => "".to_string(),
+        };
         let check_suffix = if entry.opponent_king_in_check {
             "+".to_string()
         } else {
@@ -144,10 +311,22 @@ impl MovementLogger {
             "".to_string()
         };
 
-        let final_notation = format!(
-            "{}{}{}{}{}",
-            piece_text, captured_text, end_location_text, check_suffix, checkmate_suffix
-        );
+        let final_notation = if entry.castled_king_side {
+            format!("O-O{}{}", check_suffix, checkmate_suffix)
+        } else if entry.castled_queen_side {
+            format!("O-O-O{}{}", check_suffix, checkmate_suffix)
+        } else {
+            format!(
+                "{}{}{}{}{}{}{}",
+                piece_text,
+                entry.disambiguation,
+                captured_text,
+                end_location_text,
+                promotion_text,
+                check_suffix,
+                checkmate_suffix
+            )
+        };
 
         let result = entry.notation(final_notation).clone();
         info!("Log entry added: {:?}", result.clone());
@@ -155,6 +334,21 @@ impl MovementLogger {
         result
     }
 
+    /// Adds up the time each player took over their moves in the log.
+    pub fn get_time_report(chess_match: &ChessMatch) -> TimeReport {
+        let mut report = TimeReport::default();
+        for entry in &chess_match.get_log_entries() {
+            let times = if entry.player_id == chess_match.get_white_player_id() {
+                &mut report.white
+            } else {
+                &mut report.black
+            };
+            times.add(entry.time_span as u64);
+        }
+
+        report
+    }
+
     pub fn get_formatted_entries(chess_match: &ChessMatch) -> String {
         let mut current_turn = 1;
         let mut result = String::new();
@@ -174,7 +368,97 @@ impl MovementLogger {
                 current_turn += 1;
             }
         }
+        if !first_move {
+            // white's move without black's reply yet
+            result.push_str(entry_text.as_str());
+        }
 
-        result
+        let outcome = chess_match.result();
+        match outcome {
+            MatchResult::InProgress => {}
+            MatchResult::Draw { .. } => result.push_str(" ½-½"),
+            _ => result.push_str(&format!(" {}", outcome.pgn_marker())),
+        }
+
+        result.trim_start().to_string()
+    }
+
+    /// The moves of the match in PGN movetext form, numbered from the move the match started
+    /// on and ending with `result`, wrapped so no line is longer than 80 characters.
+    pub fn get_pgn_movetext(chess_match: &ChessMatch, result: &str) -> String {
+        const MAX_LINE_LENGTH: usize = 80;
+        let entries = chess_match.get_log_entries();
+        let start_ply = MovementLogger::start_ply(chess_match, entries.len());
+
+        let mut tokens: Vec<String> = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let ply = start_ply + index as u32;
+            let move_number = ply / 2 + 1;
+            if ply.is_multiple_of(2) {
+                tokens.push(format!("{}.", move_number));
+            } else if index == 0 {
+                // a game that starts with black to move
+                tokens.push(format!("{}...", move_number));
+            }
+            tokens.push(entry.get_annotated_notation());
+        }
+        tokens.push(result.to_string());
+
+        let mut lines: Vec<String> = vec![String::new()];
+        for token in tokens {
+            let line = lines.last_mut().unwrap();
+            if line.is_empty() {
+                line.push_str(&token);
+            } else if line.len() + 1 + token.len() <= MAX_LINE_LENGTH {
+                line.push(' ');
+                line.push_str(&token);
+            } else {
+                lines.push(token);
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    /// The moves of the match one full move per row, e.g. `1. e4 e5`, numbered from the move
+    /// the match started on.
+    pub fn get_move_rows(chess_match: &ChessMatch) -> Vec<String> {
+        let entries = chess_match.get_log_entries();
+        let start_ply = MovementLogger::start_ply(chess_match, entries.len());
+
+        let mut rows: Vec<String> = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let ply = start_ply + index as u32;
+            if ply.is_multiple_of(2) {
+                rows.push(format!(
+                    "{}. {}",
+                    ply / 2 + 1,
+                    entry.get_annotated_notation()
+                ));
+            } else if index == 0 {
+                rows.push(format!(
+                    "{}... {}",
+                    ply / 2 + 1,
+                    entry.get_annotated_notation()
+                ));
+            } else if let Some(row) = rows.last_mut() {
+                row.push(' ');
+                row.push_str(&entry.get_annotated_notation());
+            }
+        }
+
+        rows
+    }
+
+    /// The ply, counted from 0 for white's first move, that the first of `logged` moves was
+    /// played on.
+    fn start_ply(chess_match: &ChessMatch, logged: usize) -> u32 {
+        let (_, color) = chess_match.get_current_turn_and_color();
+        let current_ply = (chess_match.fullmove_number() - 1) * 2
+            + match color {
+                PieceColor::White => 0,
+                PieceColor::Black => 1,
+            };
+        current_ply - logged as u32
     }
 }
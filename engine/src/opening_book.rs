@@ -0,0 +1,221 @@
+use std::{cmp::Reverse, collections::HashMap, fmt::Display, fs, path::Path};
+
+use crate::{
+    chess_match::ChessMatch,
+    chess_move::{Move, MoveError},
+    fen,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// The small book shipped with the engine, covering the main lines of the common openings.
+const BUNDLED_BOOK: &str = include_str!("../books/bundled.json");
+
+/// One line of a JSON book: moves in UCI notation from the starting position, and how
+/// strongly to prefer each of them over the other book moves of the same position.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BookLine {
+    pub moves: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// A move the book knows for a position, in UCI notation. Lines sharing a move add up their
+/// weights.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BookMove {
+    pub uci_move: String,
+    pub weight: u32,
+}
+
+#[derive(Debug)]
+pub enum BookError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    IllegalMove(String, MoveError),
+}
+
+impl Display for BookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BookError::Io(e) => write!(f, "Could not read opening book: {}", e),
+            BookError::Json(e) => write!(f, "Invalid opening book: {}", e),
+            BookError::IllegalMove(line, e) => write!(f, "{} in book line {}", e, line),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+/// Known good moves by position, looked up by the position's Zobrist key so a move is found
+/// however the position was reached.
+#[derive(Debug, Clone, Default)]
+pub struct OpeningBook {
+    positions: HashMap<u64, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+    /// Builds a book by playing out every line from the starting position.
+    pub fn from_lines(lines: &[BookLine]) -> Result<OpeningBook, BookError> {
+        let mut book = OpeningBook::default();
+        let start = ChessMatch::from_fen(fen::STARTING_FEN).expect("valid starting position");
+        for line in lines {
+            let mut chess_match = start.copy();
+            for uci_move in line.moves.split_whitespace() {
+                book.add(chess_match.zobrist_key(), uci_move, line.weight);
+                chess_match
+                    .apply_uci_move(uci_move)
+                    .map_err(|e| BookError::IllegalMove(line.moves.clone(), e))?;
+            }
+        }
+
+        Ok(book)
+    }
+
+    /// Reads a book from a JSON array of `BookLine`s.
+    pub fn from_json(json: &str) -> Result<OpeningBook, BookError> {
+        let lines: Vec<BookLine> = serde_json::from_str(json).map_err(BookError::Json)?;
+        OpeningBook::from_lines(&lines)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<OpeningBook, BookError> {
+        let json = fs::read_to_string(path).map_err(BookError::Io)?;
+        OpeningBook::from_json(&json)
+    }
+
+    /// The book that ships with the engine.
+    pub fn bundled() -> OpeningBook {
+        OpeningBook::from_json(BUNDLED_BOOK).expect("bundled opening book is valid")
+    }
+
+    fn add(&mut self, key: u64, uci_move: &str, weight: u32) {
+        let moves = self.positions.entry(key).or_default();
+        match moves.iter_mut().find(|m| m.uci_move == uci_move) {
+            Some(book_move) => book_move.weight += weight,
+            None => moves.push(BookMove {
+                uci_move: uci_move.to_string(),
+                weight,
+            }),
+        }
+    }
+
+    /// How many positions the book has moves for.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The book moves for the current position, heaviest first. Empty once the game has
+    /// left the book.
+    pub fn book_moves(&self, chess_match: &ChessMatch) -> Vec<BookMove> {
+        let mut moves = self
+            .positions
+            .get(&chess_match.zobrist_key())
+            .cloned()
+            .unwrap_or_default();
+        moves.sort_by_key(|m| Reverse(m.weight));
+        moves
+    }
+
+    /// Picks one of the book moves at random, in proportion to their weights, as a legal
+    /// move of the match. `None` when the position isn't in the book.
+    pub fn choose_move(&self, chess_match: &ChessMatch, rng: &mut impl Rng) -> Option<Move> {
+        let candidates = chess_match.book_moves(self);
+
+        let total: u32 = candidates.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        for (chess_move, weight) in candidates {
+            if pick < weight {
+                return Some(chess_move);
+            }
+            pick -= weight;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn starting_match() -> ChessMatch {
+        ChessMatch::from_fen(fen::STARTING_FEN).unwrap()
+    }
+
+    #[test]
+    fn test_weights_add_up_across_lines() {
+        let book = OpeningBook::from_json(
+            r#"[
+                { "moves": "e2e4 e7e5", "weight": 3 },
+                { "moves": "e2e4 c7c5", "weight": 2 },
+                { "moves": "d2d4" }
+            ]"#,
+        )
+        .unwrap();
+
+        let moves = book.book_moves(&starting_match());
+        assert_eq!(
+            vec![
+                BookMove {
+                    uci_move: "e2e4".to_string(),
+                    weight: 5
+                },
+                BookMove {
+                    uci_move: "d2d4".to_string(),
+                    weight: 1
+                },
+            ],
+            moves
+        );
+        assert_eq!(2, book.len());
+    }
+
+    #[test]
+    fn test_finds_transposed_positions() {
+        let book = OpeningBook::from_json(r#"[{ "moves": "g1f3 g8f6 b1c3 b8c6 e2e4" }]"#).unwrap();
+
+        let mut chess_match = starting_match();
+        for m in ["b1c3", "b8c6", "g1f3", "g8f6"] {
+            chess_match.apply_uci_move(m).unwrap();
+        }
+        let chosen = book.choose_move(&chess_match, &mut StdRng::seed_from_u64(1));
+        assert_eq!(Some("e2e4".to_string()), chosen.map(|m| m.to_string()));
+
+        chess_match.apply_uci_move("e2e4").unwrap();
+        assert!(book.book_moves(&chess_match).is_empty());
+    }
+
+    #[test]
+    fn test_rejects_illegal_lines() {
+        assert!(matches!(
+            OpeningBook::from_json(r#"[{ "moves": "e2e5" }]"#),
+            Err(BookError::IllegalMove(_, _))
+        ));
+        assert!(matches!(
+            OpeningBook::from_json("not json"),
+            Err(BookError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_bundled_book_loads() {
+        let book = OpeningBook::bundled();
+        assert!(!book.is_empty());
+        assert!(book
+            .choose_move(&starting_match(), &mut StdRng::seed_from_u64(7))
+            .is_some());
+    }
+}
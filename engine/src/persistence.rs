@@ -0,0 +1,78 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    chess_match::{ChessMatch, MatchResult},
+    save_format::SaveError,
+};
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+/// What a store knows about a saved match without loading it, for listing saved games.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SavedMatch {
+    pub match_id: Uuid,
+    pub white_player: Uuid,
+    pub black_player: Uuid,
+    pub result: MatchResult,
+    pub move_count: usize,
+    /// When the match was first saved.
+    pub created_at: DateTime<Utc>,
+    /// When the match was last saved.
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    NotFound(Uuid),
+    Json(serde_json::Error),
+    Save(SaveError),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+}
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::NotFound(match_id) => write!(f, "No saved match with id {}", match_id),
+            StoreError::Json(e) => write!(f, "Invalid saved match: {}", e),
+            StoreError::Save(e) => write!(f, "{}", e),
+            #[cfg(feature = "sqlite")]
+            StoreError::Sqlite(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<SaveError> for StoreError {
+    fn from(e: SaveError) -> Self {
+        StoreError::Save(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Json(e)
+    }
+}
+
+/// Somewhere to keep matches between sessions, keyed by match id.
+pub trait MatchStore {
+    /// Saves the match, replacing an earlier save of the same match.
+    fn save(&mut self, chess_match: &ChessMatch) -> Result<(), StoreError>;
+
+    /// Loads a saved match. Its valid moves still need calculating before play resumes.
+    fn load(&self, match_id: &Uuid) -> Result<ChessMatch, StoreError>;
+
+    /// Every saved match, most recently saved first.
+    fn list(&self) -> Result<Vec<SavedMatch>, StoreError>;
+
+    /// Removes a saved match. Returns false if there was none with that id.
+    fn delete(&mut self, match_id: &Uuid) -> Result<bool, StoreError>;
+}
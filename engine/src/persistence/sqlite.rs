@@ -0,0 +1,196 @@
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
+
+use crate::chess_match::ChessMatch;
+
+use super::{MatchStore, SavedMatch, StoreError};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS matches (
+        match_id TEXT PRIMARY KEY,
+        white_player TEXT NOT NULL,
+        black_player TEXT NOT NULL,
+        result TEXT NOT NULL,
+        move_count INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        data TEXT NOT NULL
+    )";
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+/// Keeps matches in a SQLite database: the match JSON alongside its players, result and
+/// save times, so saved games can be listed without loading them.
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<SqliteStore, StoreError> {
+        SqliteStore::with_connection(Connection::open(path)?)
+    }
+
+    /// A database that lives only as long as the store.
+    pub fn in_memory() -> Result<SqliteStore, StoreError> {
+        SqliteStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<SqliteStore, StoreError> {
+        connection.execute(SCHEMA, [])?;
+        Ok(SqliteStore { connection })
+    }
+}
+
+impl MatchStore for SqliteStore {
+    fn save(&mut self, chess_match: &ChessMatch) -> Result<(), StoreError> {
+        let now = Utc::now().to_rfc3339();
+        self.connection.execute(
+            "INSERT INTO matches
+                (match_id, white_player, black_player, result, move_count, created_at,
+                 updated_at, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7)
+             ON CONFLICT(match_id) DO UPDATE SET
+                result = excluded.result,
+                move_count = excluded.move_count,
+                updated_at = excluded.updated_at,
+                data = excluded.data",
+            params![
+                chess_match.get_match_id().to_string(),
+                chess_match.get_white_player_id().to_string(),
+                chess_match.get_black_player_id().to_string(),
+                serde_json::to_string(&chess_match.result())?,
+                chess_match.get_log_entries().len(),
+                now,
+                chess_match.get_json_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load(&self, match_id: &Uuid) -> Result<ChessMatch, StoreError> {
+        let data: Option<String> = self
+            .connection
+            .query_row(
+                "SELECT data FROM matches WHERE match_id = ?1",
+                [match_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match data {
+            Some(data) => Ok(ChessMatch::from_json(&data)?),
+            None => Err(StoreError::NotFound(*match_id)),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<SavedMatch>, StoreError> {
+        let mut statement = self.connection.prepare(
+            "SELECT match_id, white_player, black_player, result, move_count, created_at,
+                    updated_at
+             FROM matches ORDER BY updated_at DESC",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, usize>(4)?,
+                timestamp(row, 5)?,
+                timestamp(row, 6)?,
+            ))
+        })?;
+
+        rows.map(|row| {
+            let (match_id, white, black, result, move_count, created_at, updated_at) = row?;
+            Ok(SavedMatch {
+                match_id: uuid(&match_id)?,
+                white_player: uuid(&white)?,
+                black_player: uuid(&black)?,
+                result: serde_json::from_str(&result)?,
+                move_count,
+                created_at,
+                updated_at,
+            })
+        })
+        .collect()
+    }
+
+    fn delete(&mut self, match_id: &Uuid) -> Result<bool, StoreError> {
+        let deleted = self.connection.execute(
+            "DELETE FROM matches WHERE match_id = ?1",
+            [match_id.to_string()],
+        )?;
+        Ok(deleted > 0)
+    }
+}
+
+fn timestamp(row: &Row, index: usize) -> rusqlite::Result<DateTime<Utc>> {
+    let text: String = row.get(index)?;
+    DateTime::parse_from_rfc3339(&text)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(
+                index,
+                rusqlite::types::Type::Text,
+                Box::new(e),
+            )
+        })
+}
+
+fn uuid(text: &str) -> Result<Uuid, StoreError> {
+    Uuid::parse_str(text).map_err(|e| {
+        StoreError::Sqlite(rusqlite::Error::FromSqlConversionFailure(
+            0,
+            rusqlite::types::Type::Text,
+            Box::new(e),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_match::MatchResult;
+
+    #[test]
+    fn test_save_list_load_delete() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match.apply_san("e4").unwrap();
+        store.save(&chess_match).unwrap();
+
+        let saved = store.list().unwrap();
+        assert_eq!(1, saved.len());
+        assert_eq!(chess_match.get_match_id(), saved[0].match_id);
+        assert_eq!(chess_match.get_white_player_id(), saved[0].white_player);
+        assert_eq!(MatchResult::InProgress, saved[0].result);
+        assert_eq!(1, saved[0].move_count);
+
+        // saving again updates the same row
+        chess_match.apply_san("e5").unwrap();
+        store.save(&chess_match).unwrap();
+        let saved_again = store.list().unwrap();
+        assert_eq!(1, saved_again.len());
+        assert_eq!(2, saved_again[0].move_count);
+        assert_eq!(saved[0].created_at, saved_again[0].created_at);
+
+        let loaded = store.load(&chess_match.get_match_id()).unwrap();
+        assert_eq!(chess_match.to_fen(), loaded.to_fen());
+
+        assert!(store.delete(&chess_match.get_match_id()).unwrap());
+        assert!(!store.delete(&chess_match.get_match_id()).unwrap());
+        assert!(matches!(
+            store.load(&chess_match.get_match_id()),
+            Err(StoreError::NotFound(_))
+        ));
+    }
+}
@@ -24,6 +24,12 @@ pub enum LocationState {
     OutOfBounds,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Hash, Eq)]
+pub enum TargetKind {
+    Move,
+    Capture,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Hash, Eq)]
 pub enum PieceType {
     Pawn,
@@ -34,6 +40,41 @@ pub enum PieceType {
     King,
 }
 
+impl PieceType {
+    pub fn get_notation_text(&self) -> String {
+        match self {
+            PieceType::Pawn => "".to_string(),
+            PieceType::Rook => "R".to_string(),
+            PieceType::Knight => "N".to_string(),
+            PieceType::Bishop => "B".to_string(),
+            PieceType::Queen => "Q".to_string(),
+            PieceType::King => "K".to_string(),
+        }
+    }
+
+    pub fn get_name(&self) -> String {
+        match self {
+            PieceType::Pawn => "pawn".to_string(),
+            PieceType::Rook => "rook".to_string(),
+            PieceType::Knight => "knight".to_string(),
+            PieceType::Bishop => "bishop".to_string(),
+            PieceType::Queen => "queen".to_string(),
+            PieceType::King => "king".to_string(),
+        }
+    }
+
+    pub fn get_points(&self) -> u32 {
+        match self {
+            PieceType::Pawn => 1,
+            PieceType::Rook => 5,
+            PieceType::Knight => 3,
+            PieceType::Bishop => 3,
+            PieceType::Queen => 9,
+            PieceType::King => 0,
+        }
+    }
+}
+
 #[derive(Clone, Hash, Eq, PartialEq)]
 pub enum MoveDirection {
     North,
@@ -52,6 +93,15 @@ pub enum PieceColor {
     Black,
 }
 
+impl PieceColor {
+    pub fn opposite(&self) -> PieceColor {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Hash, Eq)]
 pub struct ChessPiece {
     pub id: Uuid,
@@ -94,6 +144,22 @@ impl ChessPiece {
         self.location = location;
     }
 
+    pub fn promote(&mut self, piece_type: PieceType) {
+        self.original_piece_type = Some(self.piece_type);
+        self.piece_type = piece_type;
+        self.promoted = true;
+        self.points = piece_type.get_points();
+    }
+
+    /// A pawn is promoted as soon as it reaches the last rank for its color.
+    pub fn can_be_promoted(&self) -> bool {
+        let last_rank = match self.color {
+            PieceColor::White => 8,
+            PieceColor::Black => 1,
+        };
+        self.piece_type == PieceType::Pawn && self.location.get_rank() == last_rank
+    }
+
     pub fn set_captured(&mut self) {
         self.captured = true;
     }
@@ -136,16 +202,7 @@ impl ChessPiece {
             self.location.clone()
         };
 
-        let direction_location = match direction {
-            MoveDirection::East => location.move_east(),
-            MoveDirection::North => location.move_north(),
-            MoveDirection::South => location.move_south(),
-            MoveDirection::West => location.move_west(),
-            MoveDirection::NorthEast => location.move_north_east(),
-            MoveDirection::NorthWest => location.move_north_west(),
-            MoveDirection::SouthEast => location.move_south_east(),
-            MoveDirection::SouthWest => location.move_south_west(),
-        };
+        let direction_location = location.move_in_direction(direction);
 
         if direction_location.is_none() {
             return PeekResult {
@@ -250,6 +307,10 @@ impl ChessPiece {
         self.piece_type
     }
 
+    pub fn points(&self) -> u32 {
+        self.points
+    }
+
     pub fn got_promoted(&self) -> bool {
         self.promoted
     }
@@ -296,6 +357,28 @@ impl ChessPiece {
         self.valid_captures.clone()
     }
 
+    /// Every square the piece can go to, its valid moves followed by its valid captures.
+    pub fn get_all_targets(&self) -> Vec<PieceLocation> {
+        self.get_targets()
+            .into_iter()
+            .map(|(location, _)| location)
+            .collect()
+    }
+
+    /// Same as `get_all_targets`, with each square tagged as a move or a capture.
+    pub fn get_targets(&self) -> Vec<(PieceLocation, TargetKind)> {
+        let moves = self
+            .valid_moves
+            .iter()
+            .map(|l| (l.clone(), TargetKind::Move));
+        let captures = self
+            .valid_captures
+            .iter()
+            .map(|l| (l.clone(), TargetKind::Capture));
+
+        moves.chain(captures).collect()
+    }
+
     pub fn clear_all_moves(&mut self) {
         self.valid_captures.clear();
         self.valid_moves.clear();
@@ -322,13 +405,26 @@ impl ChessPiece {
         }
     }
 
-    pub fn get_notation_text(&self) -> String {
-        if self.piece_type == PieceType::Pawn {
-            "".to_string()
-        } else {
-            self.get_text()
+    /// Plain letter for the piece, uppercase for white and lowercase for black, for terminals
+    /// and fonts that can't display the chess glyphs returned by `get_text`.
+    pub fn get_ascii_text(&self) -> char {
+        let letter = match self.piece_type {
+            PieceType::Pawn => 'P',
+            PieceType::Rook => 'R',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+        };
+        match self.color {
+            PieceColor::White => letter,
+            PieceColor::Black => letter.to_ascii_lowercase(),
         }
     }
+
+    pub fn get_notation_text(&self) -> String {
+        self.piece_type.get_notation_text()
+    }
 }
 
 #[cfg(test)]
@@ -384,4 +480,18 @@ mod tests {
         let results = piece.peek_forward(&chess_match);
         assert_eq!(2, results.len());
     }
+
+    #[test]
+    fn test_get_ascii_text() {
+        let chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let white_knight = chess_match
+            .get_piece_at_location(PieceLocation::new_from_string("b1").unwrap())
+            .unwrap();
+        let black_queen = chess_match
+            .get_piece_at_location(PieceLocation::new_from_string("d8").unwrap())
+            .unwrap();
+
+        assert_eq!('N', white_knight.get_ascii_text());
+        assert_eq!('q', black_queen.get_ascii_text());
+    }
 }
@@ -2,6 +2,8 @@ use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
 
+use crate::piece_base::MoveDirection;
+
 pub const FILES: [&'static str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
 
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize, Hash, Eq)]
@@ -49,9 +51,9 @@ impl PieceLocation {
             Some(f) => f.to_string(),
             None => "".to_string(),
         };
-        let rank = match chars.next() {
-            Some(r) => r.to_digit(10).unwrap(),
-            None => 0,
+        let rank = match chars.next().and_then(|r| r.to_digit(10)) {
+            Some(r) => r,
+            None => return Err("Rank out of bounds"),
         };
 
         if rank < 1 || rank > 8 {
@@ -190,6 +192,61 @@ impl PieceLocation {
         }
     }
 
+    pub fn move_in_direction(&self, direction: &MoveDirection) -> Option<PieceLocation> {
+        match direction {
+            MoveDirection::East => self.move_east(),
+            MoveDirection::North => self.move_north(),
+            MoveDirection::South => self.move_south(),
+            MoveDirection::West => self.move_west(),
+            MoveDirection::NorthEast => self.move_north_east(),
+            MoveDirection::NorthWest => self.move_north_west(),
+            MoveDirection::SouthEast => self.move_south_east(),
+            MoveDirection::SouthWest => self.move_south_west(),
+        }
+    }
+
+    /// Returns the location `dx` files and `dy` ranks away, or `None` if that is off the board.
+    pub fn offset(&self, dx: i32, dy: i32) -> Option<PieceLocation> {
+        let (x, y) = self.get_x_y();
+        let x = x as i32 + dx;
+        let y = y as i32 + dy;
+        if !(0..=7).contains(&x) || !(0..=7).contains(&y) {
+            return None;
+        }
+
+        Some(PieceLocation::new_from_x_y(x, y + 1))
+    }
+
+    /// Returns the squares strictly between this location and `other` when they share a
+    /// rank, file or diagonal, ordered outward from this location. Otherwise returns nothing.
+    pub fn squares_between(&self, other: &PieceLocation) -> Vec<PieceLocation> {
+        let (x1, y1) = self.get_x_y();
+        let (x2, y2) = other.get_x_y();
+        let dx = x2 as i32 - x1 as i32;
+        let dy = y2 as i32 - y1 as i32;
+        if (dx == 0 && dy == 0) || (dx != 0 && dy != 0 && dx.abs() != dy.abs()) {
+            return Vec::new();
+        }
+
+        let steps = dx.abs().max(dy.abs());
+        (1..steps)
+            .filter_map(|i| self.offset(dx.signum() * i, dy.signum() * i))
+            .collect()
+    }
+
+    /// The number of king moves between the two locations.
+    pub fn chebyshev_distance(&self, other: &PieceLocation) -> u32 {
+        let (x1, y1) = self.get_x_y();
+        let (x2, y2) = other.get_x_y();
+        (x1 - x2).abs().max((y1 - y2).abs()) as u32
+    }
+
+    /// Square index from 0 for a1 to 63 for h8, counting along each rank.
+    pub fn index(&self) -> usize {
+        let (x, y) = self.get_x_y();
+        y as usize * 8 + x as usize
+    }
+
     pub fn get_x_y(&self) -> (f64, f64) {
         let x = FILES.iter().position(|&r| r == self.file).unwrap();
         let y = self.rank - 1;
@@ -210,6 +267,10 @@ mod tests {
 
         let bad_rank = PieceLocation::new_from_string("a9");
         assert_eq!(Err("Rank out of bounds"), bad_rank);
+        assert_eq!(
+            Err("Rank out of bounds"),
+            PieceLocation::new_from_string("ab")
+        );
 
         let bad_file = PieceLocation::new_from_string("t1");
         assert_eq!(Err("File out of bounds"), bad_file);
@@ -309,4 +370,45 @@ mod tests {
         assert_eq!(None, cant_move_north_west);
         assert_eq!(None, cant_move_south_west);
     }
+
+    #[test]
+    fn test_squares_between() {
+        let a1 = PieceLocation::new_from_string("a1").unwrap();
+        let d4 = PieceLocation::new_from_string("d4").unwrap();
+        let a4 = PieceLocation::new_from_string("a4").unwrap();
+        let b3 = PieceLocation::new_from_string("b3").unwrap();
+
+        assert_eq!(
+            vec![
+                PieceLocation::new_from_string("b2").unwrap(),
+                PieceLocation::new_from_string("c3").unwrap()
+            ],
+            a1.squares_between(&d4)
+        );
+        assert_eq!(
+            vec![
+                PieceLocation::new_from_string("a3").unwrap(),
+                PieceLocation::new_from_string("a2").unwrap()
+            ],
+            a4.squares_between(&a1)
+        );
+        assert!(a1.squares_between(&b3).is_empty());
+        assert!(a1.squares_between(&a1).is_empty());
+    }
+
+    #[test]
+    fn test_index() {
+        assert_eq!(0, PieceLocation::new_from_string("a1").unwrap().index());
+        assert_eq!(12, PieceLocation::new_from_string("e2").unwrap().index());
+        assert_eq!(63, PieceLocation::new_from_string("h8").unwrap().index());
+    }
+
+    #[test]
+    fn test_chebyshev_distance() {
+        let a1 = PieceLocation::new_from_string("a1").unwrap();
+        let c7 = PieceLocation::new_from_string("c7").unwrap();
+
+        assert_eq!(6, a1.chebyshev_distance(&c7));
+        assert_eq!(0, a1.chebyshev_distance(&a1));
+    }
 }
@@ -0,0 +1,185 @@
+use uuid::Uuid;
+
+use crate::{ai::Engine, chess_match::ChessMatch, chess_move::Move, search::AiPlayer, tournament};
+
+/// Anything that can pick the moves for one side of a game.
+pub trait Player {
+    /// Picks a move for the side to move. Only called while that side has a legal move,
+    /// and must return one of `ChessMatch::legal_moves`.
+    fn choose_move(&mut self, chess_match: &ChessMatch) -> Move;
+}
+
+/// Plays a random legal move every turn.
+pub struct RandomPlayer {
+    engine: Engine,
+}
+
+impl RandomPlayer {
+    pub fn new() -> RandomPlayer {
+        RandomPlayer {
+            engine: Engine::new(),
+        }
+    }
+
+    /// A player that picks the same moves every time it is given the same seed.
+    pub fn with_seed(seed: u64) -> RandomPlayer {
+        RandomPlayer {
+            engine: Engine::with_seed(seed),
+        }
+    }
+}
+
+impl Default for RandomPlayer {
+    fn default() -> Self {
+        RandomPlayer::new()
+    }
+}
+
+impl Player for RandomPlayer {
+    fn choose_move(&mut self, chess_match: &ChessMatch) -> Move {
+        self.engine
+            .random_move(chess_match)
+            .expect("no legal move to choose from")
+    }
+}
+
+/// Plays a fixed list of moves in turn, in any notation `ChessMatch::parse_move` reads.
+/// Panics when a move is illegal or the list runs out, since that means the script is wrong.
+pub struct ScriptedPlayer {
+    moves: Vec<String>,
+    next: usize,
+}
+
+impl ScriptedPlayer {
+    /// Takes the moves separated by whitespace, e.g. `"e4 Nf3 Bc4"`.
+    pub fn new(moves: &str) -> ScriptedPlayer {
+        ScriptedPlayer {
+            moves: moves.split_whitespace().map(|m| m.to_string()).collect(),
+            next: 0,
+        }
+    }
+}
+
+impl Player for ScriptedPlayer {
+    fn choose_move(&mut self, chess_match: &ChessMatch) -> Move {
+        let san = self.moves.get(self.next).expect("script ran out of moves");
+        self.next += 1;
+        chess_match
+            .parse_move(san)
+            .unwrap_or_else(|e| panic!("scripted move {} can't be played: {}", san, e))
+    }
+}
+
+impl Player for AiPlayer {
+    fn choose_move(&mut self, chess_match: &ChessMatch) -> Move {
+        self.search(chess_match)
+            .expect("no legal move to choose from")
+            .best_move
+    }
+}
+
+/// Plays a game between two players until it is decided or reaches the move limit, when it
+/// is adjudicated a draw.
+pub struct GameRunner {
+    white: Box<dyn Player>,
+    black: Box<dyn Player>,
+    max_moves: u32,
+    chess_match: Option<ChessMatch>,
+}
+
+impl GameRunner {
+    pub fn new(white: Box<dyn Player>, black: Box<dyn Player>) -> GameRunner {
+        GameRunner {
+            white,
+            black,
+            max_moves: 200,
+            chess_match: None,
+        }
+    }
+
+    /// The number of full moves after which the game is drawn. Defaults to 200.
+    pub fn max_moves(mut self, max_moves: u32) -> GameRunner {
+        self.max_moves = max_moves;
+        self
+    }
+
+    /// Continues `chess_match` instead of starting from the initial position.
+    pub fn from_match(mut self, chess_match: ChessMatch) -> GameRunner {
+        self.chess_match = Some(chess_match);
+        self
+    }
+
+    /// Plays the game out and returns the finished match.
+    pub fn run(mut self) -> ChessMatch {
+        let chess_match = self.chess_match.take().unwrap_or_else(|| {
+            let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+            chess_match.calculate_valid_moves();
+            chess_match
+        });
+        let white = &mut self.white;
+        let black = &mut self.black;
+        tournament::play_out(
+            chess_match,
+            |m: &ChessMatch| white.choose_move(m),
+            |m: &ChessMatch| black.choose_move(m),
+            self.max_moves,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_match::{DrawReason, MatchResult};
+
+    #[test]
+    fn test_scripted_players() {
+        let finished = GameRunner::new(
+            Box::new(ScriptedPlayer::new("f3 g4")),
+            Box::new(ScriptedPlayer::new("e5 Qh4#")),
+        )
+        .run();
+
+        assert_eq!(MatchResult::BlackWonCheckmate, finished.result());
+    }
+
+    #[test]
+    fn test_random_players_stop_at_move_limit() {
+        let play = |seed| {
+            GameRunner::new(
+                Box::new(RandomPlayer::with_seed(seed)),
+                Box::new(RandomPlayer::with_seed(seed + 1)),
+            )
+            .max_moves(3)
+            .run()
+        };
+
+        let finished = play(5);
+        assert_eq!(
+            MatchResult::Draw {
+                reason: DrawReason::MoveLimit
+            },
+            finished.result()
+        );
+        assert_eq!(6, finished.get_log_entries().len());
+        assert_eq!(finished.to_fen(), play(5).to_fen());
+    }
+
+    #[test]
+    fn test_ai_player_continues_a_match() {
+        let chess_match = ChessMatch::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let finished = GameRunner::new(
+            Box::new(AiPlayer::new(crate::search::SearchConfig {
+                max_depth: 1,
+                time_limit_ms: None,
+                ..Default::default()
+            })),
+            Box::new(RandomPlayer::with_seed(1)),
+        )
+        .from_match(chess_match)
+        .max_moves(1)
+        .run();
+
+        assert_eq!(MatchResult::WhiteWonCheckmate, finished.result());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{
+    chess_match::ChessMatch,
+    chess_move::MoveError,
+    piece_base::{ChessPiece, PieceColor, PieceType},
+    piece_location::PieceLocation,
+};
+
+/// Sets up an arbitrary position one piece at a time, for tests and puzzles that don't start
+/// from the opening position. Created with `ChessMatch::from_position`.
+#[derive(Debug, Clone)]
+pub struct PositionBuilder {
+    pieces: Vec<(PieceType, PieceColor, String)>,
+    side_to_move: PieceColor,
+    castling: String,
+    en_passant: Option<String>,
+}
+
+impl PositionBuilder {
+    pub fn new() -> PositionBuilder {
+        PositionBuilder {
+            pieces: Vec::new(),
+            side_to_move: PieceColor::White,
+            castling: String::new(),
+            en_passant: None,
+        }
+    }
+
+    /// Places a piece on `square`, given like "e4".
+    pub fn piece(mut self, piece_type: PieceType, color: PieceColor, square: &str) -> Self {
+        self.pieces.push((piece_type, color, square.to_string()));
+        self
+    }
+
+    pub fn side_to_move(mut self, color: PieceColor) -> Self {
+        self.side_to_move = color;
+        self
+    }
+
+    /// Sets the castling rights with FEN letters, like "KQk". Kings and rooks that no right
+    /// covers count as having moved.
+    pub fn castling(mut self, rights: &str) -> Self {
+        self.castling = rights.to_string();
+        self
+    }
+
+    /// Sets the square a pawn can be taken on en passant, behind the pawn that just moved
+    /// two squares.
+    pub fn en_passant(mut self, square: &str) -> Self {
+        self.en_passant = Some(square.to_string());
+        self
+    }
+
+    /// Checks the setup and returns the match with its moves calculated, or
+    /// `MoveError::InvalidPosition` saying what is wrong with it.
+    pub fn build(self) -> Result<ChessMatch, MoveError> {
+        let square = |square: &str| {
+            PieceLocation::new_from_string(square)
+                .map_err(|_| MoveError::InvalidPosition(format!("no square {}", square)))
+        };
+
+        let mut pieces: Vec<ChessPiece> = Vec::new();
+        for (piece_type, color, location) in &self.pieces {
+            let location = square(location)?;
+            if pieces.iter().any(|p| p.location == location) {
+                return Err(MoveError::InvalidPosition(format!(
+                    "two pieces on {}",
+                    location
+                )));
+            }
+            pieces.push(ChessPiece::new(
+                *piece_type,
+                *color,
+                location,
+                piece_type.get_points(),
+            ));
+        }
+
+        if !self.castling.chars().all(|c| "KQkq".contains(c)) {
+            return Err(MoveError::InvalidPosition(format!(
+                "castling rights {}",
+                self.castling
+            )));
+        }
+
+        let en_passant_target = match &self.en_passant {
+            Some(target) => {
+                let location = square(target)?;
+                let rank = match self.side_to_move {
+                    PieceColor::White => 6,
+                    PieceColor::Black => 3,
+                };
+                if location.get_rank() != rank {
+                    return Err(MoveError::InvalidPosition(format!(
+                        "en passant square {}",
+                        location
+                    )));
+                }
+                Some(location)
+            }
+            None => None,
+        };
+
+        ChessMatch::set_up(pieces, self.side_to_move, &self.castling, en_passant_target)
+            .finish_set_up()
+    }
+}
+
+impl Default for PositionBuilder {
+    fn default() -> Self {
+        PositionBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_matches_fen() {
+        let chess_match = ChessMatch::from_position()
+            .piece(PieceType::King, PieceColor::White, "e1")
+            .piece(PieceType::Rook, PieceColor::White, "a1")
+            .piece(PieceType::Pawn, PieceColor::White, "d5")
+            .piece(PieceType::King, PieceColor::Black, "e8")
+            .piece(PieceType::Rook, PieceColor::Black, "h8")
+            .piece(PieceType::Pawn, PieceColor::Black, "e5")
+            .side_to_move(PieceColor::White)
+            .castling("Qk")
+            .en_passant("e6")
+            .build()
+            .unwrap();
+
+        assert_eq!("4k2r/8/8/3Pp3/8/8/8/R3K3 w Qk e6 0 1", chess_match.to_fen());
+        assert!(chess_match
+            .legal_moves()
+            .iter()
+            .any(|m| m.to_string() == "d5e6"));
+    }
+
+    #[test]
+    fn test_build_rejects_invalid_setups() {
+        let kings = || {
+            ChessMatch::from_position()
+                .piece(PieceType::King, PieceColor::White, "e1")
+                .piece(PieceType::King, PieceColor::Black, "e8")
+        };
+
+        assert!(kings().build().is_ok());
+        assert!(kings()
+            .piece(PieceType::Rook, PieceColor::White, "e9")
+            .build()
+            .is_err());
+        assert!(kings()
+            .piece(PieceType::Rook, PieceColor::White, "e1")
+            .build()
+            .is_err());
+        assert!(kings().castling("KX").build().is_err());
+        assert!(kings().en_passant("e3").build().is_err());
+        assert!(ChessMatch::from_position()
+            .piece(PieceType::King, PieceColor::White, "e1")
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,383 @@
+use std::fmt::Display;
+
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    chess_match::{ChessMatch, DrawReason, MatchResult},
+    fen::FenError,
+    piece_base::PieceType,
+    piece_location::{PieceLocation, FILES},
+};
+
+/// The save format version `to_json` writes. Bump it and add a migration whenever older saves
+/// need more than `#[serde(default)]` on a new field to load.
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// Upgrades a save by one version, in place.
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` upgrades a version `n` save to version `n + 1`.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [migrate_unversioned];
+
+/// Leads every binary save, followed by `BINARY_VERSION`.
+const BINARY_MAGIC: &[u8; 4] = b"CHSB";
+pub const BINARY_VERSION: u8 = 1;
+
+/// A result's position here is its code in binary saves, so only ever append to it.
+const RESULT_CODES: [MatchResult; 14] = [
+    MatchResult::InProgress,
+    MatchResult::WhiteWonCheckmate,
+    MatchResult::BlackWonCheckmate,
+    MatchResult::WhiteWonResignation,
+    MatchResult::BlackWonResignation,
+    MatchResult::WhiteWonTimeout,
+    MatchResult::BlackWonTimeout,
+    MatchResult::Draw {
+        reason: DrawReason::Stalemate,
+    },
+    MatchResult::Draw {
+        reason: DrawReason::MoveLimit,
+    },
+    MatchResult::Draw {
+        reason: DrawReason::FiftyMoveRule,
+    },
+    MatchResult::Draw {
+        reason: DrawReason::Agreement,
+    },
+    MatchResult::Aborted,
+    MatchResult::Draw {
+        reason: DrawReason::ThreefoldRepetition,
+    },
+    MatchResult::Draw {
+        reason: DrawReason::InsufficientMaterial,
+    },
+];
+
+/// The pieces a pawn can promote to, coded as their position here plus one, with 0 for no
+/// promotion.
+const PROMOTION_CODES: [PieceType; 4] = [
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+];
+
+#[derive(Debug)]
+pub enum SaveError {
+    Json(serde_json::Error),
+    NotAnObject,
+    InvalidVersion(Value),
+    UnsupportedVersion(u64),
+    /// A binary save that is cut short or holds something other than a match.
+    Corrupt(String),
+    InvalidPosition(FenError),
+    IllegalMove(String),
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Json(e) => write!(f, "Invalid saved match: {}", e),
+            SaveError::NotAnObject => write!(f, "Invalid saved match: not a JSON object"),
+            SaveError::InvalidVersion(version) => {
+                write!(f, "Invalid save format version: {}", version)
+            }
+            SaveError::UnsupportedVersion(version) => {
+                write!(f, "Save format version {} is not supported", version)
+            }
+            SaveError::Corrupt(reason) => write!(f, "Corrupt saved match: {}", reason),
+            SaveError::InvalidPosition(e) => write!(f, "Invalid saved starting position: {}", e),
+            SaveError::IllegalMove(m) => write!(f, "Saved match holds an illegal move: {}", m),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Json(e)
+    }
+}
+
+/// Serializes the match along with the `schema_version` it was written in.
+pub fn to_json(chess_match: &ChessMatch) -> String {
+    let mut save = serde_json::to_value(chess_match).expect("Error generating JSON output");
+    if let Some(save) = save.as_object_mut() {
+        save.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    }
+    save.to_string()
+}
+
+/// Reads a save of any supported version, upgrading it to the current one first. Saves
+/// without a `schema_version` date from before versioning and count as version 0.
+pub fn from_json(json: &str) -> Result<ChessMatch, SaveError> {
+    let mut save: Value = serde_json::from_str(json)?;
+    let fields = save.as_object_mut().ok_or(SaveError::NotAnObject)?;
+    let version = match fields.remove("schema_version") {
+        None => 0,
+        Some(version) => version.as_u64().ok_or(SaveError::InvalidVersion(version))?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(fields);
+    }
+    Ok(serde_json::from_value(save)?)
+}
+
+/// Packs the match into a compact binary save: a header with the ids, the result and the
+/// starting position as FEN, then two bytes per move. Only what it takes to replay the game
+/// is kept, so timestamps, the clock, draw offers and undone moves are lost.
+pub fn to_bytes(chess_match: &ChessMatch) -> Vec<u8> {
+    let mut bytes = BINARY_MAGIC.to_vec();
+    bytes.push(BINARY_VERSION);
+    bytes.extend_from_slice(chess_match.get_match_id().as_bytes());
+    bytes.extend_from_slice(chess_match.get_white_player_id().as_bytes());
+    bytes.extend_from_slice(chess_match.get_black_player_id().as_bytes());
+    let result = chess_match.result();
+    bytes.push(RESULT_CODES.iter().position(|r| *r == result).unwrap_or(0) as u8);
+
+    // an empty FEN stands for the standard starting position
+    let start_fen = chess_match.get_start_fen().unwrap_or_default();
+    bytes.extend_from_slice(&(start_fen.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(start_fen.as_bytes());
+
+    let entries = chess_match.get_log_entries();
+    bytes.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+    for entry in entries {
+        let promotion = entry.get_promoted_to().map_or(0, |piece_type| {
+            PROMOTION_CODES
+                .iter()
+                .position(|p| *p == piece_type)
+                .unwrap_or(3)
+                + 1
+        });
+        let code = (entry.get_start_location().index() << 9)
+            | (entry.get_end_location().index() << 3)
+            | promotion;
+        bytes.extend_from_slice(&(code as u16).to_be_bytes());
+    }
+
+    bytes
+}
+
+/// Reads a save made by `to_bytes`, replaying its moves from the starting position.
+pub fn from_bytes(bytes: &[u8]) -> Result<ChessMatch, SaveError> {
+    let mut reader = ByteReader { bytes };
+    if reader.take(BINARY_MAGIC.len())? != BINARY_MAGIC {
+        return Err(SaveError::Corrupt("not a binary save".to_string()));
+    }
+    let version = reader.take(1)?[0];
+    if version != BINARY_VERSION {
+        return Err(SaveError::UnsupportedVersion(version.into()));
+    }
+    let match_id = reader.uuid()?;
+    let white_player = reader.uuid()?;
+    let black_player = reader.uuid()?;
+    let result_code = reader.take(1)?[0];
+    let result = *RESULT_CODES
+        .get(result_code as usize)
+        .ok_or_else(|| SaveError::Corrupt(format!("unknown result code {}", result_code)))?;
+
+    let fen_length = reader.u16()? as usize;
+    let start_fen = std::str::from_utf8(reader.take(fen_length)?)
+        .map_err(|_| SaveError::Corrupt("starting position is not UTF-8".to_string()))?;
+    let mut chess_match = if start_fen.is_empty() {
+        ChessMatch::new(white_player, black_player)
+    } else {
+        ChessMatch::from_fen(start_fen).map_err(SaveError::InvalidPosition)?
+    };
+    chess_match.set_ids(match_id, white_player, black_player);
+    chess_match.calculate_valid_moves();
+
+    for _ in 0..reader.u16()? {
+        let code = reader.u16()? as usize;
+        let from = square(code >> 9);
+        let to = square((code >> 3) & 0x3f);
+        let promotion = match code & 0x7 {
+            0 => None,
+            n => Some(
+                *PROMOTION_CODES
+                    .get(n - 1)
+                    .ok_or_else(|| SaveError::Corrupt(format!("unknown promotion {}", n)))?,
+            ),
+        };
+        let chess_move = chess_match
+            .legal_moves()
+            .into_iter()
+            .find(|m| m.from == from && m.to == to && m.promotion == promotion)
+            .ok_or_else(|| SaveError::IllegalMove(format!("{}{}", from, to)))?;
+        chess_match.apply_move(&chess_move);
+    }
+    if !reader.bytes.is_empty() {
+        return Err(SaveError::Corrupt("trailing bytes".to_string()));
+    }
+
+    // resignations and the like can't be replayed
+    if chess_match.result() != result {
+        chess_match.record_result(result);
+    }
+    Ok(chess_match)
+}
+
+/// The square with `index` as given by `PieceLocation::index`.
+fn square(index: usize) -> PieceLocation {
+    PieceLocation::new(FILES[index % 8].to_string(), (index / 8 + 1) as u32)
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], SaveError> {
+        if self.bytes.len() < count {
+            return Err(SaveError::Corrupt("save is cut short".to_string()));
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn uuid(&mut self) -> Result<Uuid, SaveError> {
+        let bytes = self.take(16)?;
+        Ok(Uuid::from_slice(bytes).expect("16 bytes make a uuid"))
+    }
+}
+
+/// Saves from before versioning hold placeholder numbers for the status and result, and the
+/// side to move as 0 for white and 1 for black.
+fn migrate_unversioned(save: &mut Map<String, Value>) {
+    for field in ["status", "result"] {
+        if save.get(field).is_some_and(Value::is_number) {
+            save.insert(field.to_string(), "InProgress".into());
+        }
+    }
+    if let Some(turn) = save.get("current_turn").and_then(Value::as_u64) {
+        let color = if turn == 0 { "White" } else { "Black" };
+        save.insert("current_turn".to_string(), color.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chess_match::MatchStatus, piece_base::PieceColor};
+
+    #[test]
+    fn test_saves_carry_the_schema_version() {
+        let chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        let json = to_json(&chess_match);
+
+        assert!(json.contains("\"schema_version\":1"));
+        assert_eq!(chess_match, from_json(&json).unwrap());
+    }
+
+    #[test]
+    fn test_unversioned_save_is_migrated() {
+        let chess_match = from_json(include_str!("../../king-in-check.json")).unwrap();
+
+        assert_eq!(MatchStatus::InProgress, chess_match.get_status());
+        assert_eq!(
+            PieceColor::Black,
+            chess_match.get_current_turn_and_color().1
+        );
+    }
+
+    #[test]
+    fn test_unsupported_versions_are_rejected() {
+        let json = to_json(&ChessMatch::new(Uuid::new_v4(), Uuid::new_v4()));
+
+        let future = json.replace("\"schema_version\":1", "\"schema_version\":99");
+        assert!(matches!(
+            from_json(&future),
+            Err(SaveError::UnsupportedVersion(99))
+        ));
+        let garbled = json.replace("\"schema_version\":1", "\"schema_version\":\"one\"");
+        assert!(matches!(
+            from_json(&garbled),
+            Err(SaveError::InvalidVersion(_))
+        ));
+        assert!(matches!(from_json("[]"), Err(SaveError::NotAnObject)));
+    }
+
+    #[test]
+    fn test_binary_save_round_trip() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        for san in ["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"] {
+            chess_match.apply_san(san).unwrap();
+        }
+
+        let bytes = to_bytes(&chess_match);
+        assert_eq!(5 + 48 + 1 + 2 + 2 + 7 * 2, bytes.len());
+        assert!(bytes.len() * 10 < to_json(&chess_match).len());
+
+        let loaded = from_bytes(&bytes).unwrap();
+        assert_eq!(chess_match.get_match_id(), loaded.get_match_id());
+        assert_eq!(
+            chess_match.get_white_player_id(),
+            loaded.get_white_player_id()
+        );
+        assert_eq!(chess_match.to_fen(), loaded.to_fen());
+        assert_eq!(chess_match.to_pgn(), loaded.to_pgn());
+        assert_eq!(MatchResult::WhiteWonCheckmate, loaded.result());
+    }
+
+    #[test]
+    fn test_binary_save_keeps_start_position_promotions_and_results() {
+        let mut chess_match = ChessMatch::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 40").unwrap();
+        chess_match.apply_san("b8=R+").unwrap();
+        chess_match.apply_san("Kf7").unwrap();
+        chess_match.record_result(MatchResult::Draw {
+            reason: DrawReason::Agreement,
+        });
+
+        let loaded = from_bytes(&to_bytes(&chess_match)).unwrap();
+        assert_eq!(chess_match.to_fen(), loaded.to_fen());
+        assert_eq!(chess_match.to_pgn(), loaded.to_pgn());
+        assert_eq!(
+            MatchResult::Draw {
+                reason: DrawReason::Agreement
+            },
+            loaded.result()
+        );
+    }
+
+    #[test]
+    fn test_corrupt_binary_saves_are_rejected() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        chess_match.apply_san("Nf3").unwrap();
+        let bytes = to_bytes(&chess_match);
+
+        assert!(matches!(from_bytes(b"{}"), Err(SaveError::Corrupt(_))));
+        assert!(matches!(
+            from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SaveError::Corrupt(_))
+        ));
+        let mut newer = bytes.clone();
+        newer[4] = BINARY_VERSION + 1;
+        assert!(matches!(
+            from_bytes(&newer),
+            Err(SaveError::UnsupportedVersion(_))
+        ));
+        // Nf3 turned into Nf4
+        let mut illegal = bytes.clone();
+        let last = illegal.len() - 1;
+        illegal[last] += 8 << 3;
+        assert!(matches!(
+            from_bytes(&illegal),
+            Err(SaveError::IllegalMove(_))
+        ));
+    }
+}
@@ -0,0 +1,739 @@
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    ai::MoveChooser,
+    chess_match::ChessMatch,
+    chess_move::Move,
+    evaluation::{evaluate, EvalParams},
+    opening_book::OpeningBook,
+    piece_location::PieceLocation,
+    transposition::{Bound, TranspositionTable},
+};
+
+/// Score of being checkmated, in centipawns. Mates found sooner score further from zero.
+pub const MATE_SCORE: i32 = 1_000_000;
+
+/// How deep and for how long `AiPlayer` searches, and how it scores positions. The settings
+/// serialize, so a setup can be saved, shared and used to rebuild the same player.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct SearchConfig {
+    pub max_depth: u32,
+    /// Stops deepening once this many milliseconds have passed. The first iteration always
+    /// completes, so a move is found however short the limit.
+    pub time_limit_ms: Option<u64>,
+    pub eval_params: EvalParams,
+    /// Size of the transposition table in megabytes.
+    #[serde(default = "default_hash_size_mb")]
+    pub hash_size_mb: usize,
+    /// Plays a random one of the moves scoring within this many centipawns of the best, to
+    /// play weaker. At 0 the best move is always played.
+    #[serde(default)]
+    pub move_margin: i32,
+}
+
+fn default_hash_size_mb() -> usize {
+    16
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            max_depth: 3,
+            time_limit_ms: Some(5000),
+            eval_params: EvalParams::default(),
+            hash_size_mb: default_hash_size_mb(),
+            move_margin: 0,
+        }
+    }
+}
+
+/// How strongly the computer plays.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    /// The difficulty called `name`, as given by `Difficulty::name`.
+    pub fn named(name: &str) -> Option<Difficulty> {
+        Difficulty::ALL.into_iter().find(|d| d.name() == name)
+    }
+
+    /// The search settings for this difficulty. The lower levels search less deeply and
+    /// don't always play the move they rate best.
+    pub fn config(self) -> SearchConfig {
+        let (max_depth, time_limit_ms, move_margin) = match self {
+            Difficulty::Easy => (1, 500, 150),
+            Difficulty::Medium => (2, 2000, 40),
+            Difficulty::Hard => (3, 5000, 0),
+        };
+        SearchConfig {
+            max_depth,
+            time_limit_ms: Some(time_limit_ms),
+            move_margin,
+            ..SearchConfig::default()
+        }
+    }
+}
+
+/// How far a single search may go: to a fixed depth, or as deep as it gets in the time.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SearchLimit {
+    Depth(u32),
+    Time(Duration),
+}
+
+impl SearchLimit {
+    /// The search settings for this limit, otherwise the defaults.
+    pub fn config(self) -> SearchConfig {
+        // deep enough that the clock always runs out first
+        const TIMED_MAX_DEPTH: u32 = 64;
+        let (max_depth, time_limit_ms) = match self {
+            SearchLimit::Depth(depth) => (depth, None),
+            SearchLimit::Time(time) => (TIMED_MAX_DEPTH, Some(time.as_millis() as u64)),
+        };
+        SearchConfig {
+            max_depth,
+            time_limit_ms,
+            ..SearchConfig::default()
+        }
+    }
+}
+
+/// The outcome of a search: the move found, its score from the side to move's point of
+/// view, and the depth of the last iteration that completed. A move taken from the opening
+/// book has a depth of 0 and no score.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    /// The principal variation: the line the search expects, starting with `best_move`.
+    pub pv: Vec<Move>,
+}
+
+/// A search result ready to show a player: the move found, its score and depth, and the
+/// principal variation in SAN.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BestLine {
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: u32,
+    pub pv: Vec<String>,
+}
+
+/// A computer player that picks moves with an iterative-deepening alpha-beta search, or
+/// straight from its opening book while the game is still in it.
+pub struct AiPlayer {
+    config: SearchConfig,
+    book: Option<OpeningBook>,
+    rng: StdRng,
+    table: TranspositionTable,
+    deadline: Option<Instant>,
+    aborted: bool,
+    nodes: u64,
+}
+
+impl AiPlayer {
+    pub fn new(config: SearchConfig) -> AiPlayer {
+        AiPlayer::with_rng(config, StdRng::from_entropy())
+    }
+
+    /// A player whose random choices, among book moves and moves within `move_margin`, are
+    /// the same every time for the same seed.
+    pub fn with_seed(config: SearchConfig, seed: u64) -> AiPlayer {
+        AiPlayer::with_rng(config, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(config: SearchConfig, rng: StdRng) -> AiPlayer {
+        AiPlayer {
+            config,
+            book: None,
+            rng,
+            table: TranspositionTable::new(config.hash_size_mb),
+            deadline: None,
+            aborted: false,
+            nodes: 0,
+        }
+    }
+
+    /// Plays book moves, picked at random by weight, before searching.
+    pub fn with_book(mut self, book: OpeningBook) -> AiPlayer {
+        self.book = Some(book);
+        self
+    }
+
+    /// The settings the player was built with, which can be saved and shared to rebuild the
+    /// same player with `AiPlayer::new`.
+    pub fn config(&self) -> SearchConfig {
+        self.config
+    }
+
+    /// The best move for the side to move, as the piece to move and its destination, or
+    /// `None` when there is no legal move.
+    pub fn best_move(&mut self, chess_match: &ChessMatch) -> Option<(Uuid, PieceLocation)> {
+        self.search(chess_match)
+            .map(|result| (result.best_move.piece_id, result.best_move.to))
+    }
+
+    /// Searches one ply deeper at a time until `max_depth` is reached or time runs out,
+    /// keeping the result of the deepest iteration that completed.
+    pub fn search(&mut self, chess_match: &ChessMatch) -> Option<SearchResult> {
+        self.nodes = 0;
+        self.aborted = false;
+        self.deadline = None;
+        let started = Instant::now();
+
+        let book_move = self
+            .book
+            .as_ref()
+            .and_then(|book| book.choose_move(chess_match, &mut self.rng));
+        if let Some(best_move) = book_move {
+            return Some(SearchResult {
+                pv: vec![best_move.clone()],
+                best_move,
+                score: 0,
+                depth: 0,
+                nodes: 0,
+            });
+        }
+
+        let margin = self.config.move_margin.max(0);
+        let mut root_moves = chess_match.legal_moves();
+        let mut best: Option<SearchResult> = None;
+        // the moves of the last completed iteration close enough to the best to be played
+        let mut candidates: Vec<(Move, i32)> = Vec::new();
+        for depth in 1..=self.config.max_depth.max(1) {
+            if let Some(previous) = &best {
+                // search the previous best move first so it is the one kept on a tie, and
+                // so alpha-beta cuts off more of the rest
+                if let Some(index) = root_moves.iter().position(|m| *m == previous.best_move) {
+                    root_moves.swap(0, index);
+                }
+                self.deadline = self
+                    .config
+                    .time_limit_ms
+                    .map(|ms| started + Duration::from_millis(ms));
+            }
+
+            let mut alpha = -MATE_SCORE - 1;
+            let mut iteration_best: Option<(Move, i32)> = None;
+            let mut scored = Vec::new();
+            for m in &root_moves {
+                let mut child = chess_match.copy();
+                child.apply_move(m);
+                // widening the window by the margin gives every move within it a true score
+                // rather than a bound
+                let score = -self.negamax(&child, depth - 1, -MATE_SCORE - 1, -(alpha - margin), 1);
+                if self.aborted {
+                    break;
+                }
+                if score > alpha - margin {
+                    scored.push((m.clone(), score));
+                }
+                if score > alpha {
+                    alpha = score;
+                    iteration_best = Some((m.clone(), score));
+                }
+            }
+            if self.aborted {
+                break;
+            }
+
+            if let Some((best_move, score)) = iteration_best {
+                scored.retain(|(_, s)| *s >= score - margin);
+                candidates = scored;
+                best = Some(SearchResult {
+                    best_move,
+                    score,
+                    depth,
+                    nodes: self.nodes,
+                    pv: Vec::new(),
+                });
+                if score.abs() >= MATE_SCORE - depth as i32 {
+                    // a forced mate was found, searching deeper can't improve on it
+                    break;
+                }
+            }
+        }
+
+        if let Some(best) = best.as_mut().filter(|_| candidates.len() > 1) {
+            let (chosen, score) = candidates.swap_remove(self.rng.gen_range(0..candidates.len()));
+            best.best_move = chosen;
+            best.score = score;
+        }
+        if let Some(best) = best.as_mut() {
+            best.pv = self.principal_variation(chess_match, &best.best_move, best.depth);
+        }
+        best
+    }
+
+    /// Follows the best moves stored in the transposition table from `first` on, for up to
+    /// `depth` moves. It stops early where an entry has been overwritten, or the line would
+    /// repeat a position.
+    fn principal_variation(&self, chess_match: &ChessMatch, first: &Move, depth: u32) -> Vec<Move> {
+        let mut pv = vec![first.clone()];
+        let mut position = chess_match.copy();
+        position.apply_move(first);
+        let mut seen = HashSet::from([position.zobrist_key()]);
+        while pv.len() < depth as usize {
+            let next = match self
+                .table
+                .probe(position.zobrist_key(), 0)
+                .and_then(|entry| entry.best_move)
+            {
+                Some(next) if position.legal_moves().contains(&next) => next,
+                _ => break,
+            };
+            position.apply_move(&next);
+            if !seen.insert(position.zobrist_key()) {
+                break;
+            }
+            pv.push(next);
+        }
+
+        pv
+    }
+
+    fn negamax(
+        &mut self,
+        chess_match: &ChessMatch,
+        depth: u32,
+        mut alpha: i32,
+        beta: i32,
+        ply: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.aborted = true;
+            return 0;
+        }
+
+        let key = chess_match.zobrist_key();
+        let mut hash_move = None;
+        if let Some(entry) = self.table.probe(key, ply) {
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower if entry.score >= beta => return beta,
+                    Bound::Upper if entry.score <= alpha => return alpha,
+                    _ => {}
+                }
+            }
+            hash_move = entry.best_move;
+        }
+
+        let mut moves = chess_match.legal_moves();
+        if moves.is_empty() {
+            return if chess_match.checking_pieces().is_empty() {
+                0
+            } else {
+                -MATE_SCORE + ply
+            };
+        }
+        if depth == 0 {
+            return self.quiescence(chess_match, alpha, beta);
+        }
+
+        // trying the best move found here before, then captures, makes cutoffs more likely
+        moves.sort_by_key(|m| (Some(m) != hash_move.as_ref(), !m.capture));
+        let original_alpha = alpha;
+        let mut best_move = None;
+        for m in moves {
+            let mut child = chess_match.copy();
+            child.apply_move(&m);
+            let score = -self.negamax(&child, depth - 1, -beta, -alpha, ply + 1);
+            if self.aborted {
+                return 0;
+            }
+            if score >= beta {
+                self.table
+                    .store(key, depth, beta, Bound::Lower, Some(m), ply);
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(m);
+            }
+        }
+
+        let bound = if alpha > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.table.store(key, depth, alpha, bound, best_move, ply);
+
+        alpha
+    }
+
+    /// Keeps playing captures and promotions past the search depth until the position is
+    /// quiet, so a leaf is never scored halfway through an exchange. The side to move can
+    /// always stand pat on the static evaluation instead of capturing.
+    fn quiescence(&mut self, chess_match: &ChessMatch, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.aborted = true;
+            return 0;
+        }
+
+        let stand_pat = evaluate(chess_match, &self.config.eval_params);
+        if stand_pat >= beta {
+            return beta;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves = chess_match.capture_moves();
+        // taking the most valuable piece first makes cutoffs more likely
+        moves.sort_by_key(|m| {
+            Reverse(
+                chess_match
+                    .get_piece_at_location(m.to.clone())
+                    .map_or(0, |p| p.get_type().get_points()),
+            )
+        });
+        for m in moves {
+            let mut child = chess_match.copy();
+            child.apply_move(&m);
+            let score = -self.quiescence(&child, -beta, -alpha);
+            if self.aborted {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+}
+
+impl Default for AiPlayer {
+    fn default() -> Self {
+        AiPlayer::new(SearchConfig::default())
+    }
+}
+
+impl MoveChooser for AiPlayer {
+    fn best_move(&mut self, chess_match: &ChessMatch) -> Option<Move> {
+        self.search(chess_match).map(|result| result.best_move)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chess_match::MatchResult,
+        piece_base::{ChessPiece, PieceColor, PieceType},
+    };
+
+    fn loc(l: &str) -> PieceLocation {
+        PieceLocation::new_from_string(l).unwrap()
+    }
+
+    #[test]
+    fn test_finds_back_rank_mate() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("g1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("a1"), 5),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("g8"), 0),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("f7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("g7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("h7"), 1),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        let mut ai = AiPlayer::new(SearchConfig {
+            max_depth: 2,
+            time_limit_ms: None,
+            ..SearchConfig::default()
+        });
+        let result = ai.search(&chess_match).unwrap();
+
+        assert_eq!(loc("a8"), result.best_move.to);
+        assert_eq!(MATE_SCORE - 1, result.score);
+        assert_eq!(1, result.depth);
+    }
+
+    #[test]
+    fn test_mates_instead_of_stalemating() {
+        // Qc7 would leave the king without a move, stalemate; Qd8, Qc8 and Qb7 all mate
+        let chess_match = ChessMatch::from_fen("k7/3Q4/1K6/8/8/8/8/8 w - - 0 1").unwrap();
+        let mut ai = AiPlayer::new(SearchConfig {
+            max_depth: 2,
+            time_limit_ms: None,
+            ..SearchConfig::default()
+        });
+
+        let result = ai.search(&chess_match).unwrap();
+        assert_eq!(MATE_SCORE - 1, result.score);
+        let mut position = chess_match.copy();
+        position.apply_move(&result.best_move);
+        assert_eq!(MatchResult::WhiteWonCheckmate, position.result());
+
+        // the stalemate itself scores as a draw
+        let stalemate = chess_match
+            .legal_moves()
+            .into_iter()
+            .find(|m| m.to == loc("c7"))
+            .unwrap();
+        let mut position = chess_match.copy();
+        position.apply_move(&stalemate);
+        assert!(ai.search(&position).is_none());
+        assert_eq!(0, ai.negamax(&position, 1, -MATE_SCORE, MATE_SCORE, 1));
+    }
+
+    #[test]
+    fn test_takes_undefended_queen() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("a1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("d1"), 5),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("h8"), 0),
+            ChessPiece::new(PieceType::Queen, PieceColor::Black, loc("d5"), 9),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("h6"), 1),
+        ]);
+        chess_match.calculate_valid_moves();
+        let rook_id = chess_match.get_piece_at_location(loc("d1")).unwrap().id;
+
+        let mut ai = AiPlayer::new(SearchConfig {
+            max_depth: 2,
+            time_limit_ms: None,
+            ..SearchConfig::default()
+        });
+        assert_eq!(Some((rook_id, loc("d5"))), ai.best_move(&chess_match));
+    }
+
+    #[test]
+    fn test_coaches_with_the_searched_move() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.set_pieces(vec![
+            ChessPiece::new(PieceType::King, PieceColor::White, loc("g1"), 0),
+            ChessPiece::new(PieceType::Rook, PieceColor::White, loc("a1"), 5),
+            ChessPiece::new(PieceType::King, PieceColor::Black, loc("g8"), 0),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("f7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("g7"), 1),
+            ChessPiece::new(PieceType::Pawn, PieceColor::Black, loc("h7"), 1),
+        ]);
+        chess_match.calculate_valid_moves();
+
+        let mut ai = AiPlayer::new(SearchConfig {
+            max_depth: 2,
+            time_limit_ms: None,
+            ..SearchConfig::default()
+        });
+        let suggestion = chess_match.suggest_move(&mut ai).unwrap();
+
+        assert_eq!(loc("a8"), suggestion.chess_move.to);
+        assert_eq!("delivers checkmate", suggestion.explanation);
+    }
+
+    #[test]
+    fn test_first_iteration_ignores_time_limit() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+
+        let mut ai = AiPlayer::new(SearchConfig {
+            max_depth: 4,
+            time_limit_ms: Some(0),
+            ..SearchConfig::default()
+        });
+        let result = ai.search(&chess_match).unwrap();
+
+        assert_eq!(1, result.depth);
+        assert!(chess_match.legal_moves().contains(&result.best_move));
+    }
+
+    #[test]
+    fn test_plays_book_move_before_searching() {
+        let book = OpeningBook::from_json(r#"[{ "moves": "e2e4 e7e5 a2a3" }]"#).unwrap();
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        let mut ai = AiPlayer::new(SearchConfig {
+            max_depth: 1,
+            time_limit_ms: None,
+            ..SearchConfig::default()
+        })
+        .with_book(book);
+
+        for expected in ["e2e4", "e7e5", "a2a3"] {
+            let result = ai.search(&chess_match).unwrap();
+            assert_eq!(expected, result.best_move.to_string());
+            assert_eq!(0, result.depth);
+            chess_match.apply_uci_move(expected).unwrap();
+        }
+
+        // out of the book, so back to searching
+        assert_eq!(1, ai.search(&chess_match).unwrap().depth);
+    }
+
+    #[test]
+    fn test_config_round_trips_through_json() {
+        let config = SearchConfig {
+            max_depth: 4,
+            time_limit_ms: None,
+            ..SearchConfig::default()
+        };
+        let json = serde_json::to_string(&AiPlayer::new(config).config()).unwrap();
+
+        assert_eq!(config, serde_json::from_str::<SearchConfig>(&json).unwrap());
+    }
+
+    #[test]
+    fn test_transposition_table_saves_nodes() {
+        // a rook ending, where the same positions come up again through different move orders
+        let chess_match = ChessMatch::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        let config = SearchConfig {
+            max_depth: 3,
+            time_limit_ms: None,
+            ..SearchConfig::default()
+        };
+
+        let with_table = AiPlayer::new(config).search(&chess_match).unwrap();
+        let without_table = AiPlayer::new(SearchConfig {
+            hash_size_mb: 0,
+            ..config
+        })
+        .search(&chess_match)
+        .unwrap();
+
+        assert_eq!(without_table.score, with_table.score);
+        assert!(with_table.nodes < without_table.nodes);
+    }
+
+    #[test]
+    fn test_principal_variation() {
+        // a rook ladder, mate in two
+        let chess_match = ChessMatch::from_fen("7k/8/8/8/8/8/1R6/R5K1 w - - 0 1").unwrap();
+        let mut ai = AiPlayer::new(SearchLimit::Depth(3).config());
+
+        let result = ai.search(&chess_match).unwrap();
+        assert_eq!(MATE_SCORE - 3, result.score);
+        assert_eq!(3, result.pv.len());
+        assert_eq!(result.best_move, result.pv[0]);
+
+        // the line is playable from the position searched, and ends in mate
+        let mut position = chess_match.copy();
+        for m in &result.pv {
+            assert!(position.legal_moves().contains(m));
+            position.apply_move(m);
+        }
+        assert_eq!(MatchResult::WhiteWonCheckmate, position.result());
+    }
+
+    #[test]
+    fn test_search_limit_config() {
+        assert_eq!(4, SearchLimit::Depth(4).config().max_depth);
+        assert_eq!(None, SearchLimit::Depth(4).config().time_limit_ms);
+        assert_eq!(
+            Some(250),
+            SearchLimit::Time(Duration::from_millis(250))
+                .config()
+                .time_limit_ms
+        );
+    }
+
+    #[test]
+    fn test_move_margin_varies_the_move_played() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        let mut ai = AiPlayer::new(SearchConfig {
+            max_depth: 1,
+            time_limit_ms: None,
+            move_margin: 1000,
+            ..SearchConfig::default()
+        });
+
+        let played: HashSet<Move> = (0..20)
+            .map(|_| ai.search(&chess_match).unwrap().best_move)
+            .collect();
+        assert!(played.len() > 1);
+    }
+
+    #[test]
+    fn test_seeded_players_play_the_same_moves() {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        let config = SearchConfig {
+            max_depth: 1,
+            time_limit_ms: None,
+            move_margin: 1000,
+            ..SearchConfig::default()
+        };
+        let mut first = AiPlayer::with_seed(config, 7);
+        let mut second = AiPlayer::with_seed(config, 7);
+
+        for _ in 0..6 {
+            let chosen = first.search(&chess_match).unwrap().best_move;
+            assert_eq!(chosen, second.search(&chess_match).unwrap().best_move);
+            chess_match.apply_move(&chosen);
+        }
+    }
+
+    #[test]
+    fn test_move_margin_still_finds_mate() {
+        let chess_match = ChessMatch::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut ai = AiPlayer::new(Difficulty::Easy.config());
+
+        for _ in 0..5 {
+            assert_eq!(
+                "a1a8",
+                ai.search(&chess_match).unwrap().best_move.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_difficulty_names() {
+        for difficulty in Difficulty::ALL {
+            assert_eq!(Some(difficulty), Difficulty::named(difficulty.name()));
+        }
+        assert_eq!(None, Difficulty::named("impossible"));
+    }
+
+    #[test]
+    fn test_quiescence_sees_the_recapture() {
+        // the d5 pawn is defended, so taking it with the queen only looks good to a search
+        // that stops before black recaptures
+        let chess_match = ChessMatch::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mut ai = AiPlayer::new(SearchConfig {
+            max_depth: 1,
+            time_limit_ms: None,
+            ..SearchConfig::default()
+        });
+
+        let result = ai.search(&chess_match).unwrap();
+        assert_ne!("d1d5", result.best_move.to_string());
+    }
+}
@@ -0,0 +1,212 @@
+use uuid::Uuid;
+
+use crate::{
+    chess_match::{ChessMatch, DrawReason, MatchResult},
+    chess_move::Move,
+    piece_base::PieceColor,
+};
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct SeriesScore {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl SeriesScore {
+    /// Score from the first player's point of view, counting a draw as half a point.
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 * 0.5
+    }
+}
+
+/// Plays a full game between two move choosers, starting from the initial position. Each
+/// chooser is only called on its own turn and must return one of `ChessMatch::legal_moves`.
+/// If neither side has won after `max_moves` full moves the game is adjudicated a draw.
+pub fn play_match(
+    white: impl FnMut(&ChessMatch) -> Move,
+    black: impl FnMut(&ChessMatch) -> Move,
+    max_moves: u32,
+) -> MatchResult {
+    let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+    chess_match.calculate_valid_moves();
+    play_out(chess_match, white, black, max_moves).result()
+}
+
+/// Like `play_match`, but continues `chess_match` from its current position and returns it
+/// once the game is over, so its moves can be saved. A game stopped at the move limit is
+/// recorded as drawn.
+pub fn play_out(
+    mut chess_match: ChessMatch,
+    mut white: impl FnMut(&ChessMatch) -> Move,
+    mut black: impl FnMut(&ChessMatch) -> Move,
+    max_moves: u32,
+) -> ChessMatch {
+    for _ in 0..max_moves * 2 {
+        if chess_match.result() != MatchResult::InProgress {
+            return chess_match;
+        }
+
+        let (_, color) = chess_match.get_current_turn_and_color();
+        let chess_move = match color {
+            PieceColor::White => white(&chess_match),
+            PieceColor::Black => black(&chess_match),
+        };
+        chess_match.apply_move(&chess_move);
+    }
+
+    if chess_match.result() == MatchResult::InProgress {
+        chess_match.record_result(MatchResult::Draw {
+            reason: DrawReason::MoveLimit,
+        });
+    }
+    chess_match
+}
+
+/// Plays `games` games between two move choosers, alternating colors every game so neither
+/// side keeps the first-move advantage. The score is reported from `first`'s point of view.
+pub fn play_series(
+    first: &mut dyn FnMut(&ChessMatch) -> Move,
+    second: &mut dyn FnMut(&ChessMatch) -> Move,
+    games: u32,
+    max_moves: u32,
+) -> SeriesScore {
+    let mut score = SeriesScore::default();
+
+    for game in 0..games {
+        let first_is_white = game % 2 == 0;
+        let result = if first_is_white {
+            play_match(&mut *first, &mut *second, max_moves)
+        } else {
+            play_match(&mut *second, &mut *first, max_moves)
+        };
+
+        match (result.winner(), first_is_white) {
+            (Some(PieceColor::White), true) | (Some(PieceColor::Black), false) => score.wins += 1,
+            (Some(PieceColor::White), false) | (Some(PieceColor::Black), true) => score.losses += 1,
+            (None, _) => score.draws += 1,
+        }
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece_location::PieceLocation;
+
+    fn scripted(moves: Vec<(&'static str, &'static str)>) -> impl FnMut(&ChessMatch) -> Move {
+        let mut moves = moves.into_iter();
+        move |chess_match: &ChessMatch| {
+            let (from, to) = moves.next().expect("script ran out of moves");
+            let from = PieceLocation::new_from_string(from).unwrap();
+            let to = PieceLocation::new_from_string(to).unwrap();
+            chess_match
+                .legal_moves()
+                .into_iter()
+                .find(|m| m.from == from && m.to == to)
+                .expect("scripted move is not legal")
+        }
+    }
+
+    #[test]
+    fn test_play_match_fools_mate() {
+        let white = scripted(vec![("f2", "f3"), ("g2", "g4")]);
+        let black = scripted(vec![("e7", "e5"), ("d8", "h4")]);
+
+        assert_eq!(MatchResult::BlackWonCheckmate, play_match(white, black, 10));
+    }
+
+    #[test]
+    fn test_play_match_stops_at_move_limit() {
+        let first_legal = |chess_match: &ChessMatch| chess_match.legal_moves()[0].clone();
+
+        assert_eq!(
+            MatchResult::Draw {
+                reason: DrawReason::MoveLimit
+            },
+            play_match(first_legal, first_legal, 2)
+        );
+    }
+
+    #[test]
+    fn test_play_out_keeps_the_moves() {
+        let white = scripted(vec![("f2", "f3"), ("g2", "g4")]);
+        let black = scripted(vec![("e7", "e5"), ("d8", "h4")]);
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+
+        let finished = play_out(chess_match, white, black, 10);
+        assert_eq!(MatchResult::BlackWonCheckmate, finished.result());
+        assert_eq!(4, finished.get_log_entries().len());
+        assert!(finished.to_pgn().ends_with("2. g4 Qh4# 0-1\n"));
+    }
+
+    #[test]
+    fn test_play_series_alternates_colors_and_credits_results() {
+        // the moves of fool's mate by color, then knights back and forth
+        fn fools_mate_or_shuffle(chess_match: &ChessMatch) -> Move {
+            let (_, color) = chess_match.get_current_turn_and_color();
+            let script = match color {
+                PieceColor::White => ["f2f3", "g2g4"],
+                PieceColor::Black => ["e7e5", "d8h4"],
+            };
+            let scripted = script.get(chess_match.get_log_entries().len() / 2).copied();
+            chess_match
+                .legal_moves()
+                .into_iter()
+                .find(|m| Some(m.to_string().as_str()) == scripted)
+                .unwrap_or_else(|| shuffle(chess_match))
+        }
+        fn shuffle(chess_match: &ChessMatch) -> Move {
+            chess_match
+                .legal_moves()
+                .into_iter()
+                .find(|m| ["b1c3", "c3b1", "b8c6", "c6b8"].contains(&m.to_string().as_str()))
+                .expect("a knight can move")
+        }
+
+        let mut first_colors = vec![];
+        let mut first = |chess_match: &ChessMatch| {
+            let (_, color) = chess_match.get_current_turn_and_color();
+            if chess_match.get_log_entries().len() < 2 {
+                first_colors.push(color);
+            }
+            fools_mate_or_shuffle(chess_match)
+        };
+        // plays along with fool's mate for two games, then only shuffles
+        let mut games_started = 0;
+        let mut second = |chess_match: &ChessMatch| {
+            if chess_match.get_log_entries().len() < 2 {
+                games_started += 1;
+            }
+            if games_started <= 2 {
+                fools_mate_or_shuffle(chess_match)
+            } else {
+                shuffle(chess_match)
+            }
+        };
+
+        // first is mated as white, mates as black, then two games run to the move limit
+        let score = play_series(&mut first, &mut second, 4, 3);
+        assert_eq!(
+            SeriesScore {
+                wins: 1,
+                losses: 1,
+                draws: 2
+            },
+            score
+        );
+        assert_eq!(2.0, score.points());
+        assert_eq!(
+            vec![
+                PieceColor::White,
+                PieceColor::Black,
+                PieceColor::White,
+                PieceColor::Black
+            ],
+            first_colors
+        );
+    }
+}
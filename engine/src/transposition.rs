@@ -0,0 +1,147 @@
+use std::mem::size_of;
+
+use crate::{chess_move::Move, search::MATE_SCORE};
+
+/// How a stored score relates to the position's real score. A search that fails high only
+/// proves a lower bound, and one that fails low only an upper bound.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// What a search found out about one position.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TtEntry {
+    pub key: u64,
+    pub depth: u32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+/// Search results by Zobrist key, so a position reached again, by transposition or in a
+/// later iteration, can reuse its score or at least search its best move first.
+#[derive(Debug, Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<TtEntry>>,
+}
+
+impl TranspositionTable {
+    /// A table taking roughly `size_mb` megabytes, with room for at least one entry.
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let capacity = (size_mb * 1024 * 1024 / size_of::<Option<TtEntry>>()).max(1);
+        TranspositionTable {
+            entries: vec![None; capacity],
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+
+    /// The entry stored for `key`, with mate scores made relative to `ply` again.
+    pub fn probe(&self, key: u64, ply: i32) -> Option<TtEntry> {
+        self.entries[self.index(key)]
+            .as_ref()
+            .filter(|entry| entry.key == key)
+            .map(|entry| TtEntry {
+                score: from_stored_score(entry.score, ply),
+                ..entry.clone()
+            })
+    }
+
+    /// Stores a result, replacing whatever shares its slot unless that is the same position
+    /// searched deeper.
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: u32,
+        score: i32,
+        bound: Bound,
+        best_move: Option<Move>,
+        ply: i32,
+    ) {
+        let index = self.index(key);
+        if let Some(existing) = &self.entries[index] {
+            if existing.key == key && existing.depth > depth {
+                return;
+            }
+        }
+
+        self.entries[index] = Some(TtEntry {
+            key,
+            depth,
+            score: to_stored_score(score, ply),
+            bound,
+            best_move,
+        });
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+}
+
+/// Mate scores count plies from the root, so they are stored counting from the position
+/// itself, which stays right wherever in the tree the position turns up again.
+fn to_stored_score(score: i32, ply: i32) -> i32 {
+    if score >= MATE_SCORE - 1000 {
+        score + ply
+    } else if score <= -MATE_SCORE + 1000 {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn from_stored_score(score: i32, ply: i32) -> i32 {
+    if score >= MATE_SCORE - 1000 {
+        score - ply
+    } else if score <= -MATE_SCORE + 1000 {
+        score + ply
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut table = TranspositionTable::new(1);
+        assert!(table.capacity() > 1000);
+
+        table.store(42, 3, 120, Bound::Lower, None, 2);
+        let entry = table.probe(42, 5).unwrap();
+        assert_eq!(
+            (3, 120, Bound::Lower),
+            (entry.depth, entry.score, entry.bound)
+        );
+        assert_eq!(None, table.probe(43, 5));
+
+        // a shallower result doesn't replace a deeper one of the same position
+        table.store(42, 1, -50, Bound::Exact, None, 2);
+        assert_eq!(3, table.probe(42, 2).unwrap().depth);
+
+        table.clear();
+        assert_eq!(None, table.probe(42, 2));
+    }
+
+    #[test]
+    fn test_mate_scores_follow_the_ply() {
+        let mut table = TranspositionTable::new(1);
+        // mate 2 plies below a node at ply 3
+        table.store(7, 4, MATE_SCORE - 5, Bound::Exact, None, 3);
+
+        assert_eq!(MATE_SCORE - 3, table.probe(7, 1).unwrap().score);
+        assert_eq!(MATE_SCORE - 5, table.probe(7, 3).unwrap().score);
+    }
+}
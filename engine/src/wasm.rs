@@ -0,0 +1,127 @@
+use uuid::Uuid;
+use wasm_bindgen::prelude::*;
+
+use crate::{chess_match::ChessMatch, chess_move::MoveError, piece_location::PieceLocation};
+
+/// `ChessMatch` for JavaScript, e.g. to drive a browser chessboard. Structured values are
+/// handed over as JSON strings, and failures are thrown as JS `Error`s.
+#[wasm_bindgen(js_name = ChessMatch)]
+pub struct WasmMatch {
+    chess_match: ChessMatch,
+}
+
+#[wasm_bindgen(js_class = ChessMatch)]
+impl WasmMatch {
+    /// A new game from the starting position.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmMatch {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        WasmMatch { chess_match }
+    }
+
+    #[wasm_bindgen(js_name = fromFen)]
+    pub fn from_fen(fen: &str) -> Result<WasmMatch, JsError> {
+        Ok(WasmMatch {
+            chess_match: ChessMatch::from_fen(fen)?,
+        })
+    }
+
+    /// Loads a match saved with `toJson`.
+    #[wasm_bindgen(js_name = fromJson)]
+    pub fn from_json(json: &str) -> Result<WasmMatch, JsError> {
+        let mut chess_match = ChessMatch::from_json(json)?;
+        chess_match.calculate_valid_moves();
+        Ok(WasmMatch { chess_match })
+    }
+
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> String {
+        self.chess_match.get_json_string()
+    }
+
+    pub fn fen(&self) -> String {
+        self.chess_match.to_fen()
+    }
+
+    pub fn pgn(&self) -> String {
+        self.chess_match.to_pgn()
+    }
+
+    /// The whole position as a `MatchSnapshot` in JSON.
+    pub fn snapshot(&self) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&self.chess_match.snapshot())?)
+    }
+
+    /// Every legal move of the side to move, in UCI notation.
+    #[wasm_bindgen(js_name = legalMoves)]
+    pub fn legal_moves(&self) -> Vec<JsValue> {
+        self.chess_match
+            .legal_moves()
+            .iter()
+            .map(|m| JsValue::from(m.to_string()))
+            .collect()
+    }
+
+    /// The squares the piece on `square` can move to or capture on.
+    pub fn targets(&self, square: &str) -> Result<Vec<JsValue>, JsError> {
+        let piece = self.piece_on(square)?;
+        Ok(self
+            .chess_match
+            .targets_of(&piece)?
+            .iter()
+            .map(|l| JsValue::from(l.to_string()))
+            .collect())
+    }
+
+    /// Moves the piece on `from` to `to`, promoting to a queen, and returns the movement log
+    /// entry as JSON.
+    #[wasm_bindgen(js_name = movePiece)]
+    pub fn move_piece(&mut self, from: &str, to: &str) -> Result<String, JsError> {
+        let piece = self.piece_on(from)?;
+        let entry = self.chess_match.move_piece(&piece, &location(to)?)?;
+        Ok(serde_json::to_string(&entry)?)
+    }
+
+    /// Plays a move in any notation `ChessMatch::parse_move` reads and returns the movement
+    /// log entry as JSON.
+    #[wasm_bindgen(js_name = applySan)]
+    pub fn apply_san(&mut self, san: &str) -> Result<String, JsError> {
+        let entry = self.chess_match.apply_san(san)?;
+        Ok(serde_json::to_string(&entry)?)
+    }
+
+    /// Takes back the last move. Returns false if there was none, or if the game ended in a
+    /// resignation, timeout or agreed draw.
+    pub fn undo(&mut self) -> bool {
+        self.chess_match.undo_move().is_some()
+    }
+
+    #[wasm_bindgen(js_name = isGameOver)]
+    pub fn is_game_over(&self) -> bool {
+        self.chess_match.is_game_over()
+    }
+
+    /// The `MatchResult`, e.g. `InProgress` or `WhiteWonCheckmate`, as JSON.
+    pub fn result(&self) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&self.chess_match.result())?)
+    }
+
+    fn piece_on(&self, square: &str) -> Result<Uuid, MoveError> {
+        self.chess_match
+            .get_piece_at_location(location(square)?)
+            .map(|p| p.id)
+            .ok_or_else(|| MoveError::IllegalMove(format!("no piece on {}", square)))
+    }
+}
+
+impl Default for WasmMatch {
+    fn default() -> Self {
+        WasmMatch::new()
+    }
+}
+
+fn location(square: &str) -> Result<PieceLocation, MoveError> {
+    PieceLocation::new_from_string(square)
+        .map_err(|e| MoveError::InvalidNotation(format!("{}: {}", square, e)))
+}
@@ -0,0 +1,105 @@
+use crate::{
+    board::{Board, PIECE_TYPES},
+    chess_match::CastlingRights,
+    piece_base::PieceColor,
+};
+
+const SIDE_KEY: usize = 768;
+const CASTLING_KEYS: usize = 769;
+const EN_PASSANT_KEYS: usize = 773;
+
+/// One random key per piece on each square, then black to move, the four castling rights and
+/// the eight en passant files. Generated at compile time from a fixed seed, so a position's
+/// key is the same in every build and run.
+const KEYS: [u64; 781] = random_keys();
+
+/// Fills the key table with the splitmix64 sequence.
+const fn random_keys() -> [u64; 781] {
+    let mut keys = [0u64; 781];
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut i = 0;
+    while i < keys.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+
+    keys
+}
+
+/// The Zobrist hash of a position: the pieces on `board`, the side to move, the castling
+/// rights and the file of the en passant square, if any. Equal positions hash the same no
+/// matter how they were reached.
+pub fn key(
+    board: &Board,
+    side_to_move: PieceColor,
+    castling: CastlingRights,
+    en_passant_file: Option<usize>,
+) -> u64 {
+    let mut key = 0;
+    for (color_index, color) in [PieceColor::White, PieceColor::Black].iter().enumerate() {
+        for (type_index, piece_type) in PIECE_TYPES.iter().enumerate() {
+            for square in Board::squares(board.pieces(*piece_type, *color)) {
+                key ^= KEYS[(color_index * 6 + type_index) * 64 + square];
+            }
+        }
+    }
+
+    if side_to_move == PieceColor::Black {
+        key ^= KEYS[SIDE_KEY];
+    }
+    let rights = [
+        castling.white_king_side,
+        castling.white_queen_side,
+        castling.black_king_side,
+        castling.black_queen_side,
+    ];
+    for (i, _) in rights
+        .iter()
+        .enumerate()
+        .filter(|(_, has_right)| **has_right)
+    {
+        key ^= KEYS[CASTLING_KEYS + i];
+    }
+    if let Some(file) = en_passant_file {
+        key ^= KEYS[EN_PASSANT_KEYS + file];
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chess_match::ChessMatch;
+
+    #[test]
+    fn test_transpositions_share_a_key() {
+        let mut first = ChessMatch::from_fen(crate::fen::STARTING_FEN).unwrap();
+        let mut second = first.copy();
+        let start = first.zobrist_key();
+        for m in ["g1f3", "g8f6", "b1c3"] {
+            first.apply_uci_move(m).unwrap();
+        }
+        for m in ["b1c3", "g8f6", "g1f3"] {
+            second.apply_uci_move(m).unwrap();
+        }
+
+        assert_eq!(first.zobrist_key(), second.zobrist_key());
+        assert_ne!(start, first.zobrist_key());
+
+        // coming back to the same position gives back the same key
+        let repeated = first.zobrist_key();
+        for m in ["b8c6", "c3b1", "c6b8", "b1c3"] {
+            first.apply_uci_move(m).unwrap();
+        }
+        assert_eq!(repeated, first.zobrist_key());
+
+        // the same pieces with the other side to move are a different position
+        let white = ChessMatch::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let black = ChessMatch::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(white.zobrist_key(), black.zobrist_key());
+    }
+}
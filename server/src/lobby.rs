@@ -0,0 +1,475 @@
+use std::{collections::HashMap, fmt::Display, sync::mpsc::Sender};
+
+use chess_engine::{
+    chess_match::ChessMatch, chess_move::MoveError, clock::TimeControl, piece_base::PieceColor,
+};
+use log::info;
+use uuid::Uuid;
+
+use crate::protocol::{ClientMessage, ServerMessage};
+
+#[derive(Debug, PartialEq)]
+pub enum LobbyError {
+    MatchNotFound(Uuid),
+    SeatTaken(PieceColor),
+    AlreadySeated(PieceColor),
+    NotSeated(Uuid),
+    Move(MoveError),
+}
+
+impl Display for LobbyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LobbyError::MatchNotFound(match_id) => write!(f, "No match with id {}", match_id),
+            LobbyError::SeatTaken(color) => write!(f, "{:?} is already taken", color),
+            LobbyError::AlreadySeated(color) => {
+                write!(f, "You already play {:?} in this match", color)
+            }
+            LobbyError::NotSeated(match_id) => {
+                write!(f, "You are not playing in match {}", match_id)
+            }
+            LobbyError::Move(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// A connected client sitting at one side of the board.
+struct Seat {
+    client_id: Uuid,
+    sender: Sender<ServerMessage>,
+}
+
+struct Room {
+    chess_match: ChessMatch,
+    white: Option<Seat>,
+    black: Option<Seat>,
+    /// The client that asked for the match, so it can be closed if nobody ever joins.
+    created_by: Uuid,
+}
+
+impl Room {
+    fn seat(&mut self, color: PieceColor) -> &mut Option<Seat> {
+        match color {
+            PieceColor::White => &mut self.white,
+            PieceColor::Black => &mut self.black,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.white.is_none() && self.black.is_none()
+    }
+
+    fn color_of(&self, client_id: &Uuid) -> Option<PieceColor> {
+        let sits = |seat: &Option<Seat>| seat.as_ref().map(|s| s.client_id) == Some(*client_id);
+        if sits(&self.white) {
+            Some(PieceColor::White)
+        } else if sits(&self.black) {
+            Some(PieceColor::Black)
+        } else {
+            None
+        }
+    }
+
+    /// Sends `message` to both players. A player whose connection went away just misses it.
+    fn broadcast(&self, message: ServerMessage) {
+        for seat in [&self.white, &self.black].into_iter().flatten() {
+            seat.sender.send(message.clone()).ok();
+        }
+    }
+
+    fn broadcast_state(&self) {
+        self.broadcast(ServerMessage::State {
+            snapshot: self.chess_match.snapshot(),
+        });
+    }
+}
+
+/// Every match the server is hosting, and which connected client plays which side of each.
+/// Clients are told about changes through the `Sender` they joined with. A match is closed
+/// once it is over or everyone has left it.
+#[derive(Default)]
+pub struct Lobby {
+    rooms: HashMap<Uuid, Room>,
+}
+
+impl Lobby {
+    pub fn new() -> Lobby {
+        Lobby::default()
+    }
+
+    /// Handles one message from `client_id`, replying through `sender`. Failures are sent
+    /// back to the client as `ServerMessage::Error`.
+    pub fn handle(
+        &mut self,
+        client_id: Uuid,
+        sender: &Sender<ServerMessage>,
+        message: ClientMessage,
+    ) {
+        let result = match message {
+            ClientMessage::CreateMatch { time_control } => {
+                let match_id = self.create_match(client_id, time_control);
+                sender.send(ServerMessage::MatchCreated { match_id }).ok();
+                Ok(())
+            }
+            ClientMessage::Join { match_id, color } => {
+                self.join(match_id, color, client_id, sender.clone())
+            }
+            ClientMessage::Move { match_id, san } => self.play(match_id, client_id, &san),
+            ClientMessage::MoveFromTo {
+                match_id,
+                from,
+                to,
+                promotion,
+            } => {
+                let promotion = promotion
+                    .map(|p| p.get_notation_text().to_lowercase())
+                    .unwrap_or_default();
+                self.play(match_id, client_id, &format!("{}{}{}", from, to, promotion))
+            }
+        };
+
+        if let Err(e) = result {
+            sender
+                .send(ServerMessage::Error {
+                    message: e.to_string(),
+                })
+                .ok();
+        }
+    }
+
+    pub fn create_match(&mut self, client_id: Uuid, time_control: Option<TimeControl>) -> Uuid {
+        let mut chess_match = ChessMatch::new(Uuid::new_v4(), Uuid::new_v4());
+        chess_match.calculate_valid_moves();
+        if let Some(time_control) = time_control {
+            chess_match.set_time_control(time_control);
+        }
+
+        let match_id = chess_match.get_match_id();
+        info!("created match {}", match_id);
+        self.rooms.insert(
+            match_id,
+            Room {
+                chess_match,
+                white: None,
+                black: None,
+                created_by: client_id,
+            },
+        );
+        match_id
+    }
+
+    /// Sits `client_id` at `color`, tells them the player id they play as and sends both
+    /// players the current state. A client can only play one side of a match.
+    pub fn join(
+        &mut self,
+        match_id: Uuid,
+        color: PieceColor,
+        client_id: Uuid,
+        sender: Sender<ServerMessage>,
+    ) -> Result<(), LobbyError> {
+        let room = self
+            .rooms
+            .get_mut(&match_id)
+            .ok_or(LobbyError::MatchNotFound(match_id))?;
+        if let Some(seated) = room.color_of(&client_id) {
+            return Err(LobbyError::AlreadySeated(seated));
+        }
+        if room.seat(color).is_some() {
+            return Err(LobbyError::SeatTaken(color));
+        }
+
+        let player_id = match color {
+            PieceColor::White => room.chess_match.get_white_player_id(),
+            PieceColor::Black => room.chess_match.get_black_player_id(),
+        };
+        sender
+            .send(ServerMessage::Joined {
+                match_id,
+                color,
+                player_id,
+            })
+            .ok();
+        *room.seat(color) = Some(Seat { client_id, sender });
+        info!(
+            "client {} joined match {} as {:?}",
+            client_id, match_id, color
+        );
+        room.broadcast_state();
+        Ok(())
+    }
+
+    /// Plays `text` for the side `client_id` sits at, then sends both players the new log
+    /// entry and state. The match is closed once it is over, including when a move comes in
+    /// after the mover's flag fell.
+    pub fn play(&mut self, match_id: Uuid, client_id: Uuid, text: &str) -> Result<(), LobbyError> {
+        let room = self
+            .rooms
+            .get_mut(&match_id)
+            .ok_or(LobbyError::MatchNotFound(match_id))?;
+        let color = room
+            .color_of(&client_id)
+            .ok_or(LobbyError::NotSeated(match_id))?;
+        if room.chess_match.get_current_turn_and_color().1 != color {
+            return Err(LobbyError::Move(MoveError::NotYourTurn(color)));
+        }
+
+        let played = room.chess_match.apply_san(text);
+        match &played {
+            Ok(entry) => {
+                room.broadcast(ServerMessage::MovePlayed {
+                    match_id,
+                    entry: entry.clone(),
+                });
+                room.broadcast_state();
+            }
+            // the move was refused because it came too late, so the match was lost on time
+            Err(_) if room.chess_match.is_game_over() => room.broadcast_state(),
+            Err(_) => {}
+        }
+        if room.chess_match.is_game_over() {
+            info!("match {} is over", match_id);
+            self.rooms.remove(&match_id);
+        }
+        played.map(|_| ()).map_err(LobbyError::Move)
+    }
+
+    /// Frees every seat `client_id` held, so someone else can take over their side, and
+    /// closes the matches nobody is left in, including those the client created that nobody
+    /// joined.
+    pub fn leave(&mut self, client_id: Uuid) {
+        self.rooms.retain(|match_id, room| {
+            let seated = room.color_of(&client_id);
+            if let Some(color) = seated {
+                *room.seat(color) = None;
+            }
+            let abandoned = room.is_empty() && (seated.is_some() || room.created_by == client_id);
+            if abandoned {
+                info!("closing abandoned match {}", match_id);
+            }
+            !abandoned
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Receiver};
+
+    use chess_engine::chess_match::MatchResult;
+
+    use super::*;
+
+    fn client() -> (Uuid, Sender<ServerMessage>, Receiver<ServerMessage>) {
+        let (sender, receiver) = mpsc::channel();
+        (Uuid::new_v4(), sender, receiver)
+    }
+
+    type Client = (Uuid, Receiver<ServerMessage>);
+
+    fn seated_match(
+        lobby: &mut Lobby,
+        time_control: Option<TimeControl>,
+    ) -> (Uuid, Client, Client) {
+        let match_id = lobby.create_match(Uuid::new_v4(), time_control);
+        let (white, white_sender, white_receiver) = client();
+        let (black, black_sender, black_receiver) = client();
+        lobby
+            .join(match_id, PieceColor::White, white, white_sender)
+            .unwrap();
+        lobby
+            .join(match_id, PieceColor::Black, black, black_sender)
+            .unwrap();
+        // drop the join messages
+        white_receiver.try_iter().count();
+        black_receiver.try_iter().count();
+        (match_id, (white, white_receiver), (black, black_receiver))
+    }
+
+    #[test]
+    fn test_join_gives_the_match_player_ids() {
+        let mut lobby = Lobby::new();
+        let (sender, receiver) = mpsc::channel();
+        lobby.handle(
+            Uuid::new_v4(),
+            &sender,
+            ClientMessage::CreateMatch { time_control: None },
+        );
+        let match_id = match receiver.try_recv().unwrap() {
+            ServerMessage::MatchCreated { match_id } => match_id,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        let (black, black_sender, black_receiver) = client();
+        lobby.handle(
+            black,
+            &black_sender,
+            ClientMessage::Join {
+                match_id,
+                color: PieceColor::Black,
+            },
+        );
+        let black_player = lobby.rooms[&match_id].chess_match.get_black_player_id();
+        assert_eq!(
+            ServerMessage::Joined {
+                match_id,
+                color: PieceColor::Black,
+                player_id: black_player
+            },
+            black_receiver.try_recv().unwrap()
+        );
+        assert!(matches!(
+            black_receiver.try_recv(),
+            Ok(ServerMessage::State { .. })
+        ));
+
+        let (other, other_sender, _) = client();
+        assert_eq!(
+            Err(LobbyError::SeatTaken(PieceColor::Black)),
+            lobby.join(match_id, PieceColor::Black, other, other_sender)
+        );
+    }
+
+    #[test]
+    fn test_moves_are_broadcast_to_both_players() {
+        let mut lobby = Lobby::new();
+        let (match_id, (white, white_receiver), (black, black_receiver)) =
+            seated_match(&mut lobby, None);
+
+        lobby.play(match_id, white, "e4").unwrap();
+        let (_, black_sender, _) = client();
+        lobby.handle(
+            black,
+            &black_sender,
+            ClientMessage::MoveFromTo {
+                match_id,
+                from: "e7".to_string(),
+                to: "e5".to_string(),
+                promotion: None,
+            },
+        );
+
+        for receiver in [white_receiver, black_receiver] {
+            let messages: Vec<ServerMessage> = receiver.try_iter().collect();
+            assert_eq!(4, messages.len());
+            assert!(matches!(messages[0], ServerMessage::MovePlayed { .. }));
+            match &messages[3] {
+                ServerMessage::State { snapshot } => {
+                    assert_eq!(vec!["e4", "e5"], snapshot.move_history);
+                    assert_eq!(MatchResult::InProgress, snapshot.result);
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_only_the_side_to_move_can_play() {
+        let mut lobby = Lobby::new();
+        let (match_id, (white, _), (black, _)) = seated_match(&mut lobby, None);
+
+        assert_eq!(
+            Err(LobbyError::Move(MoveError::NotYourTurn(PieceColor::Black))),
+            lobby.play(match_id, black, "e5")
+        );
+        assert_eq!(
+            Err(LobbyError::NotSeated(match_id)),
+            lobby.play(match_id, Uuid::new_v4(), "e4")
+        );
+        assert!(matches!(
+            lobby.play(match_id, white, "e5"),
+            Err(LobbyError::Move(_))
+        ));
+
+        lobby.leave(white);
+        assert_eq!(
+            Err(LobbyError::NotSeated(match_id)),
+            lobby.play(match_id, white, "e4")
+        );
+    }
+
+    #[test]
+    fn test_one_client_cannot_take_both_seats() {
+        let mut lobby = Lobby::new();
+        let match_id = lobby.create_match(Uuid::new_v4(), None);
+        let (client_id, sender, _receiver) = client();
+
+        lobby
+            .join(match_id, PieceColor::White, client_id, sender.clone())
+            .unwrap();
+        assert_eq!(
+            Err(LobbyError::AlreadySeated(PieceColor::White)),
+            lobby.join(match_id, PieceColor::Black, client_id, sender.clone())
+        );
+        assert_eq!(
+            Err(LobbyError::AlreadySeated(PieceColor::White)),
+            lobby.join(match_id, PieceColor::White, client_id, sender)
+        );
+    }
+
+    #[test]
+    fn test_finished_matches_are_closed() {
+        let mut lobby = Lobby::new();
+        let (match_id, (white, white_receiver), (black, _)) = seated_match(&mut lobby, None);
+
+        for (player, san) in [(white, "f3"), (black, "e5"), (white, "g4"), (black, "Qh4#")] {
+            lobby.play(match_id, player, san).unwrap();
+        }
+
+        // the final position still reaches both players
+        match white_receiver.try_iter().last() {
+            Some(ServerMessage::State { snapshot }) => {
+                assert_eq!(MatchResult::BlackWonCheckmate, snapshot.result)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(lobby.rooms.is_empty());
+        assert_eq!(
+            Err(LobbyError::MatchNotFound(match_id)),
+            lobby.play(match_id, white, "e4")
+        );
+    }
+
+    #[test]
+    fn test_moving_after_the_flag_fell_ends_the_match() {
+        let mut lobby = Lobby::new();
+        // no time at all, so black's flag falls as soon as white's move starts the clock
+        let (match_id, (white, white_receiver), (black, black_receiver)) =
+            seated_match(&mut lobby, Some(TimeControl::increment(0, 0)));
+
+        lobby.play(match_id, white, "e4").unwrap();
+        assert_eq!(
+            Err(LobbyError::Move(MoveError::GameOver)),
+            lobby.play(match_id, black, "e5")
+        );
+
+        for receiver in [white_receiver, black_receiver] {
+            match receiver.try_iter().last() {
+                Some(ServerMessage::State { snapshot }) => {
+                    assert_eq!(MatchResult::WhiteWonTimeout, snapshot.result)
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert!(lobby.rooms.is_empty());
+    }
+
+    #[test]
+    fn test_abandoned_matches_are_closed() {
+        let mut lobby = Lobby::new();
+        let (match_id, (white, _), (black, _)) = seated_match(&mut lobby, None);
+
+        // the match waits for white to come back while black is still there
+        lobby.leave(white);
+        assert!(lobby.rooms.contains_key(&match_id));
+        lobby.leave(black);
+        assert!(!lobby.rooms.contains_key(&match_id));
+
+        // a match nobody joined goes with the client that created it, and only with them
+        let creator = Uuid::new_v4();
+        let unjoined = lobby.create_match(creator, None);
+        lobby.leave(Uuid::new_v4());
+        assert!(lobby.rooms.contains_key(&unjoined));
+        lobby.leave(creator);
+        assert!(lobby.rooms.is_empty());
+    }
+}
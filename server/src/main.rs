@@ -0,0 +1,97 @@
+mod lobby;
+mod protocol;
+
+use std::{
+    env,
+    error::Error,
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use log::{info, warn};
+use tungstenite::{Message, WebSocket};
+use uuid::Uuid;
+
+use crate::{
+    lobby::Lobby,
+    protocol::{ClientMessage, ServerMessage},
+};
+
+/// How long a connection waits for the client before passing on messages from the lobby.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+    let address = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:9001".to_string());
+    let listener = TcpListener::bind(&address)?;
+    info!("listening on {}", address);
+
+    let lobby = Arc::new(Mutex::new(Lobby::new()));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let lobby = Arc::clone(&lobby);
+        thread::spawn(move || {
+            if let Err(e) = serve(stream, lobby) {
+                warn!("connection closed: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Runs one client connection: passes its messages to the lobby and writes back whatever the
+/// lobby sends it, until the client goes away.
+fn serve(stream: TcpStream, lobby: Arc<Mutex<Lobby>>) -> Result<(), Box<dyn Error>> {
+    let mut socket = tungstenite::accept(stream).map_err(|e| e.to_string())?;
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    let client_id = Uuid::new_v4();
+    let (sender, receiver) = mpsc::channel();
+    info!("client {} connected", client_id);
+
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        loop {
+            match socket.read_message() {
+                Ok(Message::Text(text)) => match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(message) => lobby.lock().unwrap().handle(client_id, &sender, message),
+                    Err(e) => send(
+                        &mut socket,
+                        &ServerMessage::Error {
+                            message: format!("Unreadable message: {}", e),
+                        },
+                    )?,
+                },
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+
+            for message in receiver.try_iter() {
+                send(&mut socket, &message)?;
+            }
+        }
+    })();
+
+    lobby.lock().unwrap().leave(client_id);
+    info!("client {} disconnected", client_id);
+    result
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, message: &ServerMessage) -> Result<(), Box<dyn Error>> {
+    socket.write_message(Message::Text(serde_json::to_string(message)?))?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use chess_engine::{
+    clock::TimeControl,
+    match_snapshot::MatchSnapshot,
+    movement_log::MovementLogEntry,
+    piece_base::{PieceColor, PieceType},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What a client can send, as JSON text frames tagged by `type`, e.g.
+/// `{"type":"join","match_id":"...","color":"White"}`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    CreateMatch {
+        #[serde(default)]
+        time_control: Option<TimeControl>,
+    },
+    Join {
+        match_id: Uuid,
+        color: PieceColor,
+    },
+    /// A move in any notation the engine reads: SAN like `Nf3`, or coordinates like `g1f3`.
+    Move {
+        match_id: Uuid,
+        san: String,
+    },
+    MoveFromTo {
+        match_id: Uuid,
+        from: String,
+        to: String,
+        #[serde(default)]
+        promotion: Option<PieceType>,
+    },
+}
+
+/// What the server sends back, tagged the same way as `ClientMessage`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    MatchCreated {
+        match_id: Uuid,
+    },
+    /// Sent only to the joining client, with the player id the match knows them by.
+    Joined {
+        match_id: Uuid,
+        color: PieceColor,
+        player_id: Uuid,
+    },
+    /// The whole match, sent to both players whenever it changes.
+    State {
+        snapshot: MatchSnapshot,
+    },
+    /// The movement log entry of a move just played, sent to both players.
+    MovePlayed {
+        match_id: Uuid,
+        entry: MovementLogEntry,
+    },
+    Error {
+        message: String,
+    },
+}